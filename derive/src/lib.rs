@@ -0,0 +1,170 @@
+//! Derive macros for [anymap](https://docs.rs/anymap).
+//!
+//! Don’t depend on this crate directly: enable anymap’s `derive` Cargo feature instead, and use
+//! the macros from there.
+
+#![warn(missing_docs)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, Index, Member};
+use syn::{PathArguments, Type};
+
+/// Implements `anymap::Bundle` for a struct, so that each of its fields can be inserted into or
+/// removed from a map under its own type.
+///
+/// The struct behaves just like the tuple of its field types, in order, so it can have up to
+/// twelve fields, and its `Displaced` type is the tuple of an `Option` of each field type.
+#[proc_macro_derive(AnyMapBundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bundle(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn bundle(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match input.data {
+        Data::Struct(data) => data.fields,
+        _ => return Err(Error::new_spanned(&input.ident, "AnyMapBundle can only be derived for structs")),
+    };
+    if fields.is_empty() {
+        return Err(Error::new_spanned(&input.ident, "AnyMapBundle needs at least one field"));
+    }
+    let members = members(&fields);
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    generics.params.push(syn::parse_quote!(__M: ?Sized));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+    where_clause.predicates.push(syn::parse_quote!((#(#types,)*): ::anymap::Bundle<__M>));
+    let tuple = quote!((#(#types,)*));
+
+    Ok(quote! {
+        impl #impl_generics ::anymap::Bundle<__M> for #name #ty_generics #where_clause {
+            type Displaced = <#tuple as ::anymap::Bundle<__M>>::Displaced;
+
+            #[inline]
+            fn insert_into(self, map: &mut __M) -> Self::Displaced {
+                <#tuple as ::anymap::Bundle<__M>>::insert_into((#(self.#members,)*), map)
+            }
+
+            #[inline]
+            fn remove_from(map: &mut __M) -> Self::Displaced {
+                <#tuple as ::anymap::Bundle<__M>>::remove_from(map)
+            }
+
+            #[inline]
+            fn contained_in(map: &__M) -> bool {
+                <#tuple as ::anymap::Bundle<__M>>::contained_in(map)
+            }
+        }
+    })
+}
+
+/// Implements `TryFrom<&M>` for a struct, for any variety of map `M`, by looking up each field
+/// in the map by its type and cloning it.
+///
+/// Fields of type `Option<T>` are looked up as `T`, and are allowed to be missing; if any other
+/// field is missing, the conversion fails with an `anymap::MissingError` naming its type.
+///
+/// The struct can have up to twelve fields.
+#[proc_macro_derive(FromAnyMap)]
+pub fn derive_from_any_map(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_any_map(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn from_any_map(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match input.data {
+        Data::Struct(data) => data.fields,
+        _ => return Err(Error::new_spanned(&input.ident, "FromAnyMap can only be derived for structs")),
+    };
+    if fields.is_empty() {
+        return Err(Error::new_spanned(&input.ident, "FromAnyMap needs at least one field"));
+    }
+    let members = members(&fields);
+    let lookups = fields.iter().map(|field| option_inner(&field.ty)).collect::<Vec<_>>();
+    let types = lookups.iter().map(|&(ty, _)| ty).collect::<Vec<_>>();
+    let vars = (0..fields.len())
+        .map(|i| quote::format_ident!("__field{}", i))
+        .collect::<Vec<_>>();
+    let values = lookups.iter().zip(&vars).map(|(&(ty, optional), var)| if optional {
+        quote!(#var.cloned())
+    } else {
+        quote!(#var.cloned().ok_or_else(::anymap::MissingError::new::<#ty>)?)
+    });
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    generics.params.push(syn::parse_quote!('__m));
+    generics.params.push(syn::parse_quote!(__A: ?Sized + ::anymap::Downcast));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+    // (There’s at least one field, so this leaves the where clause non-empty.)
+    for ty in &types {
+        where_clause.predicates.push(syn::parse_quote!(#ty: ::core::clone::Clone));
+    }
+    let tuple = quote!((#(#types,)*));
+    let callback = quote::format_ident!("__anymap_from_any_map_{}", name);
+
+    // A generic impl over any map type would conflict with the blanket TryFrom implementation, so
+    // anymap calls back once for each variety of Map that its enabled features provide.
+    Ok(quote! {
+        const _: () = {
+            macro_rules! #callback {
+                ($($map:tt)*) => {
+                    impl #impl_generics ::core::convert::TryFrom<&'__m $($map)*<__A>>
+                        for #name #ty_generics
+                        #where_clause,
+                        #tuple: ::anymap::Fetch<
+                            '__m,
+                            $($map)*<__A>,
+                            Refs = (#(::core::option::Option<&'__m #types>,)*),
+                        >,
+                    {
+                        type Error = ::anymap::MissingError;
+
+                        fn try_from(map: &'__m $($map)*<__A>)
+                            -> ::core::result::Result<Self, Self::Error>
+                        {
+                            let (#(#vars,)*) = <#tuple as ::anymap::Fetch<'__m, _>>::fetch(map);
+                            ::core::result::Result::Ok(#name {
+                                #(#members: #values,)*
+                            })
+                        }
+                    }
+                }
+            }
+            ::anymap::__for_each_map!(#callback);
+        };
+    })
+}
+
+// Returns the type to look up for a field, and whether it’s optional.
+fn option_inner(ty: &Type) -> (&Type, bool) {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if segment.ident == "Option" {
+                if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                        return (inner, true);
+                    }
+                }
+            }
+        }
+    }
+    (ty, false)
+}
+
+fn members(fields: &Fields) -> Vec<Member> {
+    fields.iter().enumerate().map(|(i, field)| match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(i)),
+    }).collect()
+}
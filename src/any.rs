@@ -1,43 +1,69 @@
 use core::fmt;
 use core::any::{Any, TypeId};
-#[cfg(not(feature = "std"))]
+use core::hash::{Hash, Hasher};
+#[cfg(all(feature = "alloc", not(feature = "std")))]
 use alloc::boxed::Box;
 
 #[doc(hidden)]
 pub trait CloneToAny {
     /// Clone `self` into a new `Box<dyn CloneAny>` object.
+    #[cfg(feature = "alloc")]
     fn clone_to_any(&self) -> Box<dyn CloneAny>;
+
+    /// Convert a `Box<dyn CloneAny>` into a `Box<dyn Any>`; trait upcasting is too new for us.
+    #[cfg(feature = "alloc")]
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 
 impl<T: Any + Clone> CloneToAny for T {
+    #[cfg(feature = "alloc")]
     #[inline]
     fn clone_to_any(&self) -> Box<dyn CloneAny> {
         Box::new(self.clone())
     }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
-macro_rules! impl_clone {
-    ($t:ty) => {
-        impl Clone for Box<$t> {
-            #[inline]
-            fn clone(&self) -> Box<$t> {
-                // SAFETY: this dance is to reapply any Send/Sync marker. I’m not happy about this
-                // approach, given that I used to do it in safe code, but then came a dodgy
-                // future-compatibility warning where_clauses_object_safety, which is spurious for
-                // auto traits but still super annoying (future-compatibility lints seem to mean
-                // your bin crate needs a corresponding allow!). Although I explained my plight¹
-                // and it was all explained and agreed upon, no action has been taken. So I finally
-                // caved and worked around it by doing it this way, which matches what’s done for
-                // core::any², so it’s probably not *too* bad.
-                //
-                // ¹ https://github.com/rust-lang/rust/issues/51443#issuecomment-421988013
-                // ² https://github.com/rust-lang/rust/blob/e7825f2b690c9a0d21b6f6d84c404bb53b151b38/library/alloc/src/boxed.rs#L1613-L1616
-                let clone: Box<dyn CloneAny> = (**self).clone_to_any();
-                let raw: *mut dyn CloneAny = Box::into_raw(clone);
-                unsafe { Box::from_raw(raw as *mut $t) }
+// Implements Clone for `Box<$t>` by way of `$clone_to`, a method returning a `Box<$base>`;
+// exported for `define_any_bound!`, hence the absolute paths.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_clone {
+    ($t:ty, $base:ty, $clone_to:ident) => {
+        $crate::__if_alloc! {
+            impl ::core::clone::Clone for $crate::__Box<$t> {
+                #[inline]
+                fn clone(&self) -> $crate::__Box<$t> {
+                    // SAFETY: this dance is to reapply any Send/Sync marker. I’m not happy about
+                    // this approach, given that I used to do it in safe code, but then came a
+                    // dodgy future-compatibility warning where_clauses_object_safety, which is
+                    // spurious for auto traits but still super annoying (future-compatibility
+                    // lints seem to mean your bin crate needs a corresponding allow!). Although I
+                    // explained my plight¹ and it was all explained and agreed upon, no action has
+                    // been taken. So I finally caved and worked around it by doing it this way,
+                    // which matches what’s done for core::any², so it’s probably not *too* bad.
+                    //
+                    // ¹ https://github.com/rust-lang/rust/issues/51443#issuecomment-421988013
+                    // ² https://github.com/rust-lang/rust/blob/e7825f2b690c9a0d21b6f6d84c404bb53b151b38/library/alloc/src/boxed.rs#L1613-L1616
+                    let clone: $crate::__Box<$base> = (**self).$clone_to();
+                    let raw: *mut $base = $crate::__Box::into_raw(clone);
+                    unsafe {
+                        $crate::__Box::from_raw(::core::mem::transmute::<*mut $base, *mut $t>(raw))
+                    }
+                }
             }
         }
+    }
+}
 
+// For the traits without Debug as a supertrait, so that maps of them can still be debugged.
+macro_rules! impl_debug {
+    ($t:ty) => {
         impl fmt::Debug for $t {
             #[inline]
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -47,6 +73,43 @@ macro_rules! impl_clone {
     }
 }
 
+// The conversions from a `Map` to one with a weaker bound, which keep the boxes; invoked by
+// `everything!`, with `Map` in scope. Dropping auto traits is a plain coercion, but getting from
+// `CloneAny` to `Any` needs `into_any`, and then the auto traits reapplying as in `__impl_clone!`.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_weaken {
+    (coerce: $from:ty => $($to:ty),+) => {
+        $(impl_weaken!(@impl $from => $to, |value| value);)+
+    };
+    (into_any: $from:ty => $($to:ty),+) => {
+        $(impl_weaken!(@impl $from => $to, |value: Box<$from>| {
+            let raw: *mut dyn Any = Box::into_raw(value.into_any());
+            // SAFETY: this only adds the auto traits that $from had.
+            unsafe { Box::from_raw(core::mem::transmute::<*mut dyn Any, *mut $to>(raw)) }
+        });)+
+    };
+    (@impl $from:ty => $to:ty, $convert:expr) => {
+        impl<S: BuildHasher + Clone> From<Map<$from, S>> for Map<$to, S> {
+            #[inline]
+            fn from(map: Map<$from, S>) -> Map<$to, S> {
+                let mut raw: RawMap<$to, S> = RawMap::with_capacity_and_hasher(
+                    map.raw.len(),
+                    map.raw.hasher().clone(),
+                );
+                for (type_id, value) in map.raw {
+                    let value: Box<$to> = $convert(value);
+                    let _ = raw.insert(type_id, value);
+                }
+                Map {
+                    raw,
+                    #[cfg(feature = "type-names")]
+                    names: map.names,
+                }
+            }
+        }
+    };
+}
+
 /// Methods for downcasting from an `Any`-like trait object.
 ///
 /// This should only be implemented on trait objects for subtraits of `Any`, though you can
@@ -82,23 +145,87 @@ pub trait Downcast {
 
     /// Downcast from `Box<Any>` to `Box<T>`, without checking the type matches.
     ///
+    /// This is a provided method, so that whether the `alloc` feature is enabled makes no
+    /// difference to implementations of this trait.
+    ///
     /// # Safety
     ///
     /// The caller must ensure that `T` matches the trait object, on pain of *undefined behaviour*.
-    unsafe fn downcast_unchecked<T: 'static>(self: Box<Self>) -> Box<T>;
+    #[cfg(feature = "alloc")]
+    #[inline]
+    unsafe fn downcast_unchecked<T: 'static>(self: Box<Self>) -> Box<T> {
+        Box::from_raw(Box::into_raw(self) as *mut T)
+    }
 }
 
 /// A trait for the conversion of an object into a boxed trait object.
+///
+/// It’s implemented for every `T` that `A` can be [coerced from](CoerceFrom), and serves as the
+/// bound saying which types may be stored in a map of a given `A`. Only
+/// [`ptr_into_ptr`](IntoBox::ptr_into_ptr) is required; the `Box` methods, which need the `alloc`
+/// feature, are built on it, so implementations don’t depend on that feature. The maps trust
+/// these methods to hand back the value they were given, downcasting it later without checking.
 pub trait IntoBox<A: ?Sized + Downcast>: Any {
+    /// Convert a pointer to self into a pointer to the appropriate trait object, which must point
+    /// to the same place.
+    fn ptr_into_ptr(ptr: *mut Self) -> *mut A where Self: Sized;
+
     /// Convert self into the appropriate boxed form.
-    fn into_box(self) -> Box<A>;
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn into_box(self) -> Box<A> where Self: Sized {
+        Box::new(self).box_into_box()
+    }
+
+    /// Convert a box of self into the appropriate boxed form, without reallocating.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn box_into_box(self: Box<Self>) -> Box<A> where Self: Sized {
+        // SAFETY: ptr_into_ptr returns the very pointer it was given, merely unsized.
+        unsafe { Box::from_raw(Self::ptr_into_ptr(Box::into_raw(self))) }
+    }
+}
+
+impl<A: ?Sized + Downcast + CoerceFrom<T>, T: Any> IntoBox<A> for T {
+    #[inline]
+    fn ptr_into_ptr(ptr: *mut T) -> *mut A {
+        A::coerce_ptr(ptr)
+    }
 }
 
-macro_rules! implement {
+/// A trait object type that values of type `T` can be stored as, such as `dyn Any` for any `T:
+/// Any`; this is what decides which types a map of a given `A` accepts.
+///
+/// It’s implemented on the trait object rather than on the value type so that other crates can
+/// implement it for their own trait objects, which [`define_any_bound!`](crate::define_any_bound)
+/// does.
+///
+/// # Safety
+///
+/// `coerce_ptr` must return the very pointer it was given, merely unsized; the maps trust that the
+/// value behind it is a `T` when downcasting.
+pub unsafe trait CoerceFrom<T> {
+    /// Convert a pointer to `T` into a pointer to this type.
+    fn coerce_ptr(value: *mut T) -> *mut Self;
+
+    /// Convert a box of `T` into a box of this type.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn coerce_box(value: Box<T>) -> Box<Self> {
+        // SAFETY: coerce_ptr returns the very pointer it was given, merely unsized.
+        unsafe { Box::from_raw(Self::coerce_ptr(Box::into_raw(value))) }
+    }
+}
+
+// Implements Downcast and CoerceFrom for a trait object type; exported for `define_any_bound!`,
+// hence the absolute paths.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __implement_any {
     ($any_trait:ident $(+ $auto_traits:ident)*) => {
-        impl Downcast for dyn $any_trait $(+ $auto_traits)* {
+        impl $crate::Downcast for dyn $any_trait $(+ $auto_traits)* {
             #[inline]
-            fn type_id(&self) -> TypeId {
+            fn type_id(&self) -> ::core::any::TypeId {
                 self.type_id()
             }
 
@@ -112,24 +239,151 @@ macro_rules! implement {
                 &mut *(self as *mut Self as *mut T)
             }
 
-            #[inline]
-            unsafe fn downcast_unchecked<T: 'static>(self: Box<Self>) -> Box<T> {
-                Box::from_raw(Box::into_raw(self) as *mut T)
-            }
         }
 
-        impl<T: $any_trait $(+ $auto_traits)*> IntoBox<dyn $any_trait $(+ $auto_traits)*> for T {
+        unsafe impl<T: $any_trait $(+ $auto_traits)*> $crate::CoerceFrom<T>
+            for dyn $any_trait $(+ $auto_traits)*
+        {
             #[inline]
-            fn into_box(self) -> Box<dyn $any_trait $(+ $auto_traits)*> {
-                Box::new(self)
+            fn coerce_ptr(value: *mut T) -> *mut Self {
+                value
             }
         }
     }
 }
 
-implement!(Any);
-implement!(Any + Send);
-implement!(Any + Send + Sync);
+// Passes its input through if the `alloc` feature is enabled; for macros, which can’t see our
+// Cargo features.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_alloc {
+    ($($item:tt)*) => { $($item)* }
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_alloc {
+    ($($item:tt)*) => {}
+}
+
+/// Defines a trait for use as a map’s `A` parameter, like [`CloneAny`], but with supertraits of
+/// your choosing.
+///
+/// `define_any_bound!(pub trait ShowAny: fmt::Display + fmt::Debug)` defines a trait `ShowAny`,
+/// implemented for every `T: Any + fmt::Display + fmt::Debug`, and makes maps of `dyn ShowAny`,
+/// `dyn ShowAny + Send` and `dyn ShowAny + Send + Sync` accept exactly those types. Values
+/// borrowed from such a map through [`Map::values`](crate::Map::values) come as `&dyn ShowAny`,
+/// so you can use those supertraits without knowing the concrete types.
+///
+/// `Clone` can’t be the supertrait of a trait object, so it needs a helper trait, like
+/// `CloneAny`’s; to make the map cloneable, put `Clone` first in the list and name the helper
+/// trait for the macro to define (which it can’t make up a name for by itself) before that:
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use core::fmt;
+/// use anymap::Map;
+///
+/// anymap::define_any_bound! {
+///     /// Anything that can be shown to the user.
+///     pub trait ShowAny(CloneToShowAny): Clone + fmt::Display
+/// }
+///
+/// let mut map: Map<dyn ShowAny> = Map::new();
+/// map.insert(42i32);
+/// map.insert("str");
+/// let clone = map.clone();
+/// let mut shown = clone.values().map(|value| value.to_string()).collect::<Vec<_>>();
+/// shown.sort();
+/// assert_eq!(shown, ["42", "str"]);
+/// ```
+///
+/// The trait can’t have any items of its own, nor generics.
+#[macro_export]
+macro_rules! define_any_bound {
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $name:ident($clone_to:ident): Clone $(+ $($bound:tt)+)?
+    ) => {
+        $(#[$attr])*
+        $vis trait $name: ::core::any::Any + $clone_to $(+ $($bound)+)? { }
+        impl<T: ::core::any::Any + ::core::clone::Clone $(+ $($bound)+)?> $name for T { }
+
+        #[doc(hidden)]
+        $vis trait $clone_to {
+            $crate::__if_alloc! {
+                /// Clone `self` into a new boxed trait object.
+                fn clone_to_any(&self) -> $crate::__Box<dyn $name>;
+            }
+        }
+
+        impl<T: ::core::any::Any + ::core::clone::Clone $(+ $($bound)+)?> $clone_to for T {
+            $crate::__if_alloc! {
+                #[inline]
+                fn clone_to_any(&self) -> $crate::__Box<dyn $name> {
+                    $crate::__Box::new(self.clone())
+                }
+            }
+        }
+
+        $crate::impl_any_bound!($name);
+        $crate::__impl_clone!(dyn $name, dyn $name, clone_to_any);
+        $crate::__impl_clone!(dyn $name + Send, dyn $name, clone_to_any);
+        $crate::__impl_clone!(dyn $name + Send + Sync, dyn $name, clone_to_any);
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $name:ident $(: $($bound:tt)+)?
+    ) => {
+        $(#[$attr])*
+        $vis trait $name: ::core::any::Any $(+ $($bound)+)? { }
+        impl<T: ::core::any::Any $(+ $($bound)+)?> $name for T { }
+        $crate::impl_any_bound!($name);
+    };
+}
+
+__implement_any!(Any);
+__implement_any!(Any + Send);
+__implement_any!(Any + Send + Sync);
+
+/// Makes a trait of your own usable as a map’s `A` parameter, for a map that holds one value of
+/// each of its implementing types.
+///
+/// The trait must have `Any` as a supertrait and be dyn-compatible; this then lets `dyn Trait`,
+/// `dyn Trait + Send` and `dyn Trait + Send + Sync` be used as `A`, with all the usual typed
+/// access, and [`Map::values`](crate::Map::values) handing out `&dyn Trait`. (If the trait needs no
+/// items of its own, [`define_any_bound!`](crate::define_any_bound) can define it too.)
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use core::any::Any;
+/// use anymap::Map;
+///
+/// trait Plugin: Any {
+///     fn name(&self) -> &str;
+/// }
+/// anymap::impl_any_bound!(Plugin);
+///
+/// struct Spellcheck { language: &'static str }
+/// impl Plugin for Spellcheck {
+///     fn name(&self) -> &str { "spellcheck" }
+/// }
+///
+/// let mut plugins: Map<dyn Plugin> = Map::new();
+/// plugins.insert(Spellcheck { language: "en" });
+/// assert_eq!(plugins.get::<Spellcheck>().unwrap().language, "en");
+/// assert_eq!(plugins.values().map(|plugin| plugin.name()).collect::<Vec<_>>(), ["spellcheck"]);
+/// ```
+#[macro_export]
+macro_rules! impl_any_bound {
+    ($name:ident) => {
+        $crate::__implement_any!($name);
+        $crate::__implement_any!($name + Send);
+        $crate::__implement_any!($name + Send + Sync);
+    }
+}
 
 /// [`Any`], but with cloning.
 ///
@@ -137,9 +391,181 @@ implement!(Any + Send + Sync);
 /// See [`core::any`] for more details on `Any` in general.
 pub trait CloneAny: Any + CloneToAny { }
 impl<T: Any + Clone> CloneAny for T { }
-implement!(CloneAny);
-implement!(CloneAny + Send);
-implement!(CloneAny + Send + Sync);
-impl_clone!(dyn CloneAny);
-impl_clone!(dyn CloneAny + Send);
-impl_clone!(dyn CloneAny + Send + Sync);
+__implement_any!(CloneAny);
+__implement_any!(CloneAny + Send);
+__implement_any!(CloneAny + Send + Sync);
+__impl_clone!(dyn CloneAny, dyn CloneAny, clone_to_any);
+__impl_clone!(dyn CloneAny + Send, dyn CloneAny, clone_to_any);
+__impl_clone!(dyn CloneAny + Send + Sync, dyn CloneAny, clone_to_any);
+impl_debug!(dyn CloneAny);
+impl_debug!(dyn CloneAny + Send);
+impl_debug!(dyn CloneAny + Send + Sync);
+
+/// [`Any`], but with debugging.
+///
+/// Every type with no non-`'static` references that implements `Debug` implements `DebugAny`, and
+/// since `dyn DebugAny` implements `Debug` in turn, a `Map<dyn DebugAny>` can show every value.
+pub trait DebugAny: Any + fmt::Debug { }
+impl<T: Any + fmt::Debug> DebugAny for T { }
+__implement_any!(DebugAny);
+__implement_any!(DebugAny + Send);
+__implement_any!(DebugAny + Send + Sync);
+
+#[doc(hidden)]
+pub trait PartialEqToAny {
+    /// Compare `self` with a `dyn PartialEqAny`, which is unequal if it’s of a different type.
+    fn eq_any(&self, other: &dyn PartialEqAny) -> bool;
+}
+
+impl<T: Any + PartialEq> PartialEqToAny for T {
+    #[inline]
+    fn eq_any(&self, other: &dyn PartialEqAny) -> bool {
+        Downcast::type_id(other) == TypeId::of::<T>()
+            && *self == *unsafe { other.downcast_ref_unchecked::<T>() }
+    }
+}
+
+macro_rules! impl_partial_eq {
+    ($t:ty, $eq:ident) => {
+        impl PartialEq for $t {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                self.$eq(other)
+            }
+        }
+    }
+}
+
+/// [`Any`], but with equality.
+///
+/// Every type with no non-`'static` references that implements `PartialEq` implements
+/// `PartialEqAny`. Two `dyn PartialEqAny` values are equal if they’re of the same type and that
+/// type’s `PartialEq` says so, which makes a `Map<dyn PartialEqAny>` comparable as a whole.
+pub trait PartialEqAny: Any + PartialEqToAny { }
+impl<T: Any + PartialEq> PartialEqAny for T { }
+__implement_any!(PartialEqAny);
+__implement_any!(PartialEqAny + Send);
+__implement_any!(PartialEqAny + Send + Sync);
+impl_partial_eq!(dyn PartialEqAny, eq_any);
+impl_partial_eq!(dyn PartialEqAny + Send, eq_any);
+impl_partial_eq!(dyn PartialEqAny + Send + Sync, eq_any);
+impl_debug!(dyn PartialEqAny);
+impl_debug!(dyn PartialEqAny + Send);
+impl_debug!(dyn PartialEqAny + Send + Sync);
+
+#[doc(hidden)]
+pub trait HashToAny {
+    /// Feed `self` into a `dyn Hasher`.
+    fn hash_any(&self, state: &mut dyn Hasher);
+}
+
+impl<T: Any + Hash> HashToAny for T {
+    #[inline]
+    fn hash_any(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state)
+    }
+}
+
+macro_rules! impl_hash {
+    ($t:ty) => {
+        impl Hash for $t {
+            #[inline]
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.hash_any(state)
+            }
+        }
+    }
+}
+
+/// [`Any`], but with hashing.
+///
+/// Every type with no non-`'static` references that implements `Hash` implements `HashAny`, and
+/// a `Map<dyn HashAny>` implements `Hash` in turn.
+pub trait HashAny: Any + HashToAny { }
+impl<T: Any + Hash> HashAny for T { }
+__implement_any!(HashAny);
+__implement_any!(HashAny + Send);
+__implement_any!(HashAny + Send + Sync);
+impl_hash!(dyn HashAny);
+impl_hash!(dyn HashAny + Send);
+impl_hash!(dyn HashAny + Send + Sync);
+impl_debug!(dyn HashAny);
+impl_debug!(dyn HashAny + Send);
+impl_debug!(dyn HashAny + Send + Sync);
+
+#[doc(hidden)]
+pub trait CloneDebugToAny {
+    /// Clone `self` into a new `Box<dyn CloneDebugAny>` object.
+    #[cfg(feature = "alloc")]
+    fn clone_debug_to_any(&self) -> Box<dyn CloneDebugAny>;
+}
+
+impl<T: Any + Clone + fmt::Debug> CloneDebugToAny for T {
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn clone_debug_to_any(&self) -> Box<dyn CloneDebugAny> {
+        Box::new(self.clone())
+    }
+}
+
+/// [`Any`], but with both cloning and debugging: [`CloneAny`] and [`DebugAny`] together.
+///
+/// Every type with no non-`'static` references that implements both `Clone` and `Debug`
+/// implements `CloneDebugAny`.
+pub trait CloneDebugAny: Any + CloneDebugToAny + fmt::Debug { }
+impl<T: Any + Clone + fmt::Debug> CloneDebugAny for T { }
+__implement_any!(CloneDebugAny);
+__implement_any!(CloneDebugAny + Send);
+__implement_any!(CloneDebugAny + Send + Sync);
+__impl_clone!(dyn CloneDebugAny, dyn CloneDebugAny, clone_debug_to_any);
+__impl_clone!(dyn CloneDebugAny + Send, dyn CloneDebugAny, clone_debug_to_any);
+__impl_clone!(dyn CloneDebugAny + Send + Sync, dyn CloneDebugAny, clone_debug_to_any);
+
+#[doc(hidden)]
+pub trait ClonePartialEqToAny {
+    /// Clone `self` into a new `Box<dyn ClonePartialEqAny>` object.
+    #[cfg(feature = "alloc")]
+    fn clone_partial_eq_to_any(&self) -> Box<dyn ClonePartialEqAny>;
+
+    /// Compare `self` with a `dyn ClonePartialEqAny`, which is unequal if it’s of a different
+    /// type.
+    fn eq_clone_any(&self, other: &dyn ClonePartialEqAny) -> bool;
+}
+
+impl<T: Any + Clone + PartialEq> ClonePartialEqToAny for T {
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn clone_partial_eq_to_any(&self) -> Box<dyn ClonePartialEqAny> {
+        Box::new(self.clone())
+    }
+
+    #[inline]
+    fn eq_clone_any(&self, other: &dyn ClonePartialEqAny) -> bool {
+        Downcast::type_id(other) == TypeId::of::<T>()
+            && *self == *unsafe { other.downcast_ref_unchecked::<T>() }
+    }
+}
+
+/// [`Any`], but with both cloning and equality: [`CloneAny`] and [`PartialEqAny`] together.
+///
+/// Every type with no non-`'static` references that implements both `Clone` and `PartialEq`
+/// implements `ClonePartialEqAny`. This is what [`Map::diff`](crate::Map::diff) needs, to tell
+/// which values changed and keep copies of them.
+pub trait ClonePartialEqAny: Any + ClonePartialEqToAny { }
+impl<T: Any + Clone + PartialEq> ClonePartialEqAny for T { }
+__implement_any!(ClonePartialEqAny);
+__implement_any!(ClonePartialEqAny + Send);
+__implement_any!(ClonePartialEqAny + Send + Sync);
+__impl_clone!(dyn ClonePartialEqAny, dyn ClonePartialEqAny, clone_partial_eq_to_any);
+__impl_clone!(dyn ClonePartialEqAny + Send, dyn ClonePartialEqAny, clone_partial_eq_to_any);
+__impl_clone!(
+    dyn ClonePartialEqAny + Send + Sync,
+    dyn ClonePartialEqAny,
+    clone_partial_eq_to_any
+);
+impl_debug!(dyn ClonePartialEqAny);
+impl_debug!(dyn ClonePartialEqAny + Send);
+impl_debug!(dyn ClonePartialEqAny + Send + Sync);
+impl_partial_eq!(dyn ClonePartialEqAny, eq_clone_any);
+impl_partial_eq!(dyn ClonePartialEqAny + Send, eq_clone_any);
+impl_partial_eq!(dyn ClonePartialEqAny + Send + Sync, eq_clone_any);
@@ -0,0 +1,195 @@
+//! A map handing out shared handles to its values.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::hash::BuildHasherDefault;
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::hash_map::HashMap;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use crate::any::{Downcast, IntoBox};
+use crate::TypeIdHasher;
+#[cfg(not(feature = "std"))]
+use crate::hashbrown::Map;
+#[cfg(feature = "std")]
+use crate::Map;
+
+/// Turns an `Arc<A>` holding a `T` into an `Arc<T>`.
+///
+/// # Safety
+///
+/// The value must be of type `T`.
+#[inline]
+unsafe fn downcast_arc<A: ?Sized, T>(value: Arc<A>) -> Arc<T> {
+    Arc::from_raw(Arc::into_raw(value) as *const T)
+}
+
+/// A collection containing zero or one values for any given type, each in an `Arc`, so that
+/// [`get_arc`](ArcMap::get_arc) can hand out a handle to it that outlives any borrow of the map.
+///
+/// This suits long-lived services shared between tasks or threads: rather than wrapping every
+/// value in an `Arc` by hand and storing `Arc<T>`, store the `T` and fetch an `Arc<T>` when it’s
+/// needed. Cloning the map clones the handles, not the values.
+///
+/// ```rust
+/// use anymap::arc::ArcMap;
+/// use core::any::Any;
+/// use std::sync::Arc;
+///
+/// struct Database { url: &'static str }
+///
+/// let mut services = ArcMap::<dyn Any + Send + Sync>::new();
+/// services.insert(Database { url: "db1" });
+/// let db: Arc<Database> = services.get_arc().unwrap();
+/// std::thread::spawn(move || assert_eq!(db.url, "db1")).join().unwrap();
+/// ```
+pub struct ArcMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    raw: HashMap<TypeId, Arc<A>, BuildHasherDefault<TypeIdHasher>>,
+}
+
+impl<A: ?Sized + Downcast> Default for ArcMap<A> {
+    #[inline]
+    fn default() -> ArcMap<A> {
+        ArcMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> Clone for ArcMap<A> {
+    #[inline]
+    fn clone(&self) -> ArcMap<A> {
+        ArcMap { raw: self.raw.clone() }
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Debug for ArcMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ArcMap").field("len", &self.raw.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for ArcMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> ArcMap<A> {
+        ArcMap {
+            raw: map.into_raw().into_iter().map(|(type_id, value)| (type_id, Arc::from(value)))
+                .collect(),
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> ArcMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> ArcMap<A> {
+        ArcMap { raw: HashMap::default() }
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Removes all items from the collection. Handles already handed out stay valid.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.raw.clear()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.raw.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        self.raw.get(&TypeId::of::<T>())
+            .map(|any| unsafe { any.downcast_ref_unchecked::<T>() })
+    }
+
+    /// Returns a shared handle to the value stored in the collection for the type `T`, if it
+    /// exists.
+    #[inline]
+    pub fn get_arc<T: IntoBox<A>>(&self) -> Option<Arc<T>> {
+        self.raw.get(&TypeId::of::<T>())
+            .map(|any| unsafe { downcast_arc::<A, T>(any.clone()) })
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`, if it
+    /// exists and no handle to it from [`get_arc`](ArcMap::get_arc) or a clone of the collection
+    /// is still alive.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+        self.raw.get_mut(&TypeId::of::<T>())
+            .and_then(Arc::get_mut)
+            .map(|any| unsafe { any.downcast_mut_unchecked::<T>() })
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, a handle to that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<Arc<T>> {
+        self.raw.insert(TypeId::of::<T>(), Arc::from(value.into_box()))
+            .map(|any| unsafe { downcast_arc::<A, T>(any) })
+    }
+
+    /// Removes the `T` value from the collection, returning a handle to it if there was one or
+    /// `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&mut self) -> Option<Arc<T>> {
+        self.raw.remove(&TypeId::of::<T>())
+            .map(|any| unsafe { downcast_arc::<A, T>(any) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_arc_map() {
+        let mut map = ArcMap::<dyn Any + Send + Sync>::new();
+        assert_eq!(map.insert(A(1)), None);
+        assert_eq!(map.insert(B(2)), None);
+        assert!(map.contains::<A>() && !map.contains::<u8>());
+        map.get_mut::<A>().unwrap().0 = 10;
+
+        let a = map.get_arc::<A>().unwrap();
+        assert_eq!((&*a, map.get::<A>()), (&A(10), Some(&A(10))));
+        assert!(map.get_mut::<A>().is_none());
+        assert!(Arc::ptr_eq(&a, &map.clone().get_arc().unwrap()));
+        drop(a);
+        assert!(map.get_mut::<A>().is_some());
+
+        let old = map.insert(A(20)).unwrap();
+        assert_eq!(Arc::try_unwrap(old), Ok(A(10)));
+        assert_eq!(map.remove::<B>().as_deref(), Some(&B(2)));
+        assert_eq!(format!("{:?}", map), "ArcMap { len: 1, .. }");
+        map.clear();
+        assert!(map.is_empty());
+
+        let mut boxed: Map<dyn Any + Send + Sync> = Map::new();
+        let _ = boxed.insert(B(3));
+        let map = ArcMap::from(boxed);
+        assert_eq!(map.get_arc::<B>().as_deref(), Some(&B(3)));
+    }
+}
@@ -0,0 +1,589 @@
+//! A map for sharing between async tasks.
+
+use core::any::{Any, TypeId};
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::any::{Downcast, IntoBox};
+use crate::Map;
+
+/// A [`Map`] behind an async read-write lock, with typed access through `&self`.
+///
+/// This is [`SyncMap`](crate::sync::SyncMap) for async code: rather than blocking the thread,
+/// waiting for the lock yields to the executor, and the guards are `Send` (given `Send + Sync`
+/// values, as the default `dyn Any + Send + Sync` ensures), so they can be held across `.await`s
+/// in tasks that move between threads. The lock is the crate’s own, so it works with any
+/// executor. A writer waiting for the lock keeps further readers out, so writers can’t be starved.
+///
+/// ```rust
+/// # struct Noop;
+/// # impl std::task::Wake for Noop { fn wake(self: std::sync::Arc<Self>) {} }
+/// # fn block_on<F: core::future::Future>(f: F) -> F::Output {
+/// #     let waker = std::task::Waker::from(std::sync::Arc::new(Noop));
+/// #     let mut f = core::pin::pin!(f);
+/// #     let mut cx = core::task::Context::from_waker(&waker);
+/// #     loop { if let core::task::Poll::Ready(v) = f.as_mut().poll(&mut cx) { return v } }
+/// # }
+/// use anymap::async_map::AsyncMap;
+///
+/// # block_on(async {
+/// let data: AsyncMap = AsyncMap::new();
+/// data.insert(42i32).await;
+/// *data.get_mut::<i32>().await.unwrap() += 1;
+/// assert_eq!(*data.get::<i32>().await.unwrap(), 43);
+/// # });
+/// ```
+pub struct AsyncMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    state: Mutex<State>,
+    map: UnsafeCell<Map<A>>,
+}
+
+#[derive(Default)]
+struct State {
+    readers: usize,
+    writer: bool,
+    writers_waiting: usize,
+    /// Types being initialised by `get_or_init_with`.
+    initializing: Vec<TypeId>,
+    wakers: Vec<Waker>,
+}
+
+impl State {
+    fn wake_all(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+// SAFETY: access to the map is governed by `state`, just as `std::sync::RwLock` governs its value.
+unsafe impl<A: ?Sized + Downcast + Send + Sync> Sync for AsyncMap<A> {}
+
+impl<A: ?Sized + Downcast> Default for AsyncMap<A> {
+    #[inline]
+    fn default() -> AsyncMap<A> {
+        AsyncMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for AsyncMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> AsyncMap<A> {
+        AsyncMap {
+            state: Mutex::default(),
+            map: UnsafeCell::new(map),
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Debug for AsyncMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncMap").finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> AsyncMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> AsyncMap<A> {
+        AsyncMap::from(Map::new())
+    }
+
+    /// Returns the map, consuming the lock.
+    #[inline]
+    pub fn into_inner(self) -> Map<A> {
+        self.map.into_inner()
+    }
+
+    /// Returns a mutable reference to the map; no locking is needed, since the borrow is
+    /// exclusive.
+    #[inline]
+    pub fn get_map_mut(&mut self) -> &mut Map<A> {
+        self.map.get_mut()
+    }
+
+    #[inline]
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the map for reading, waiting until that’s possible.
+    #[inline]
+    pub async fn read(&self) -> AsyncReadGuard<'_, Map<A>, A> {
+        Acquire { map: self, write: false, waiting: false }.await;
+        AsyncReadGuard { map: self, value: self.map.get() }
+    }
+
+    /// Locks the map for writing, waiting until that’s possible.
+    #[inline]
+    pub async fn write(&self) -> AsyncWriteGuard<'_, Map<A>, A> {
+        Acquire { map: self, write: true, waiting: false }.await;
+        AsyncWriteGuard { map: self, value: self.map.get() }
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub async fn len(&self) -> usize {
+        self.read().await.len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub async fn is_empty(&self) -> bool {
+        self.read().await.is_empty()
+    }
+
+    /// Removes all items from the collection.
+    #[inline]
+    pub async fn clear(&self) {
+        self.write().await.clear()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub async fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.read().await.contains::<T>()
+    }
+
+    /// Returns a guard dereferencing to the value stored in the collection for the type `T`, if
+    /// it exists. The map is locked for reading until the guard is dropped.
+    #[inline]
+    pub async fn get<T: IntoBox<A>>(&self) -> Option<AsyncReadGuard<'_, T, A>> {
+        AsyncReadGuard::try_map(self.read().await, |map| map.get::<T>()).ok()
+    }
+
+    /// Returns a clone of the value stored in the collection for the type `T`, if it exists,
+    /// holding the lock only for as long as that takes.
+    #[inline]
+    pub async fn get_cloned<T: IntoBox<A> + Clone>(&self) -> Option<T> {
+        self.read().await.get_cloned::<T>()
+    }
+
+    /// Returns a guard dereferencing mutably to the value stored in the collection for the type
+    /// `T`, if it exists. The map is locked for writing until the guard is dropped.
+    #[inline]
+    pub async fn get_mut<T: IntoBox<A>>(&self) -> Option<AsyncWriteGuard<'_, T, A>> {
+        AsyncWriteGuard::try_map(self.write().await, |map| map.get_mut::<T>()).ok()
+    }
+
+    /// Returns a guard dereferencing to the value stored in the collection for the type `T`,
+    /// inserting the output of the future `f` returns first if there was none.
+    ///
+    /// However many tasks call this at once, only one runs `f`, with no lock held while it’s
+    /// awaited; the rest wait for it to finish and share its value. If that task is cancelled or
+    /// panics before it’s done, another caller takes over, running its own `f`. Once the value
+    /// is there, the map is locked for reading until the guard is dropped.
+    pub async fn get_or_init_with<T, F, Fut>(&self, f: F) -> AsyncReadGuard<'_, T, A>
+    where
+        T: IntoBox<A>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let type_id = TypeId::of::<T>();
+        loop {
+            let map = match AsyncReadGuard::try_map(self.read().await, |map| map.get::<T>()) {
+                Ok(value) => return value,
+                Err(map) => map,
+            };
+            // With the map locked for reading, no one can be partway through inserting it.
+            let claimed = {
+                let mut state = self.state();
+                let claimed = !state.initializing.contains(&type_id);
+                if claimed {
+                    state.initializing.push(type_id);
+                }
+                claimed
+            };
+            drop(map);
+            if claimed {
+                break;
+            }
+            Initialized { map: self, type_id }.await;
+        }
+        let claim = Claim { map: self, type_id };
+        let value = f().await;
+        let value = AsyncWriteGuard::map(self.write().await, |map| map.get_or_insert(value));
+        let value = AsyncWriteGuard::downgrade(value);
+        drop(claim);
+        value
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub async fn insert<T: IntoBox<A>>(&self, value: T) -> Option<T> {
+        self.write().await.insert(value)
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub async fn remove<T: IntoBox<A>>(&self) -> Option<T> {
+        self.write().await.remove::<T>()
+    }
+}
+
+/// The future of taking an `AsyncMap`’s lock.
+struct Acquire<'a, A: ?Sized + Downcast> {
+    map: &'a AsyncMap<A>,
+    write: bool,
+    /// Whether this is a writer counted in `writers_waiting`.
+    waiting: bool,
+}
+
+impl<A: ?Sized + Downcast> Future for Acquire<'_, A> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let map = self.map;
+        let mut state = map.state();
+        if self.write {
+            if !state.writer && state.readers == 0 {
+                state.writer = true;
+                if self.waiting {
+                    state.writers_waiting -= 1;
+                    self.waiting = false;
+                }
+                return Poll::Ready(());
+            }
+            if !self.waiting {
+                state.writers_waiting += 1;
+                self.waiting = true;
+            }
+        } else if !state.writer && state.writers_waiting == 0 {
+            state.readers += 1;
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<A: ?Sized + Downcast> Drop for Acquire<'_, A> {
+    fn drop(&mut self) {
+        if self.waiting {
+            // Readers held back for this writer may go ahead now.
+            let mut state = self.map.state();
+            state.writers_waiting -= 1;
+            state.wake_all();
+        }
+    }
+}
+
+/// A claim to initialise a type in an `AsyncMap`, released when dropped.
+struct Claim<'a, A: ?Sized + Downcast> {
+    map: &'a AsyncMap<A>,
+    type_id: TypeId,
+}
+
+impl<A: ?Sized + Downcast> Drop for Claim<'_, A> {
+    fn drop(&mut self) {
+        let mut state = self.map.state();
+        state.initializing.retain(|type_id| *type_id != self.type_id);
+        state.wake_all();
+    }
+}
+
+/// The future of a type in an `AsyncMap` no longer being initialised.
+struct Initialized<'a, A: ?Sized + Downcast> {
+    map: &'a AsyncMap<A>,
+    type_id: TypeId,
+}
+
+impl<A: ?Sized + Downcast> Future for Initialized<'_, A> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.map.state();
+        if !state.initializing.contains(&self.type_id) {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// A guard holding an [`AsyncMap`] locked for reading, dereferencing to the map or a value in it.
+pub struct AsyncReadGuard<'a, T: ?Sized, A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    map: &'a AsyncMap<A>,
+    value: *const T,
+}
+
+// SAFETY: as for `std::sync::RwLockReadGuard`, except that releasing the lock from another thread
+// is fine here.
+unsafe impl<T: ?Sized + Sync, A: ?Sized + Downcast + Send + Sync> Send
+    for AsyncReadGuard<'_, T, A> {}
+unsafe impl<T: ?Sized + Sync, A: ?Sized + Downcast + Send + Sync> Sync
+    for AsyncReadGuard<'_, T, A> {}
+
+impl<'a, T: ?Sized, A: ?Sized + Downcast> AsyncReadGuard<'a, T, A> {
+    /// Makes a guard for part of the guarded data. This is an associated function, lest it get in
+    /// the way of `T`’s methods.
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> AsyncReadGuard<'a, U, A> {
+        let value: *const U = f(&this);
+        let map = this.map;
+        core::mem::forget(this);
+        AsyncReadGuard { map, value }
+    }
+
+    /// Makes a guard for part of the guarded data, or returns the guard unchanged if `f` returns
+    /// `None`. This is an associated function, lest it get in the way of `T`’s methods.
+    pub fn try_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(this: Self, f: F)
+        -> Result<AsyncReadGuard<'a, U, A>, Self>
+    {
+        match f(&this).map(|value| value as *const U) {
+            Some(value) => {
+                let map = this.map;
+                core::mem::forget(this);
+                Ok(AsyncReadGuard { map, value })
+            }
+            None => Err(this),
+        }
+    }
+}
+
+impl<T: ?Sized, A: ?Sized + Downcast> Deref for AsyncReadGuard<'_, T, A> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the lock is held for reading for as long as this guard lives.
+        unsafe { &*self.value }
+    }
+}
+
+impl<T: ?Sized, A: ?Sized + Downcast> Drop for AsyncReadGuard<'_, T, A> {
+    fn drop(&mut self) {
+        let mut state = self.map.state();
+        state.readers -= 1;
+        if state.readers == 0 {
+            state.wake_all();
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, A: ?Sized + Downcast> fmt::Debug for AsyncReadGuard<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A guard holding an [`AsyncMap`] locked for writing, dereferencing to the map or a value in it.
+pub struct AsyncWriteGuard<'a, T: ?Sized, A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    map: &'a AsyncMap<A>,
+    value: *mut T,
+}
+
+// SAFETY: as for `std::sync::RwLockWriteGuard`, except that releasing the lock from another thread
+// is fine here.
+unsafe impl<T: ?Sized + Send + Sync, A: ?Sized + Downcast + Send + Sync> Send
+    for AsyncWriteGuard<'_, T, A> {}
+unsafe impl<T: ?Sized + Send + Sync, A: ?Sized + Downcast + Send + Sync> Sync
+    for AsyncWriteGuard<'_, T, A> {}
+
+impl<'a, T: ?Sized, A: ?Sized + Downcast> AsyncWriteGuard<'a, T, A> {
+    /// Makes a guard for part of the guarded data. This is an associated function, lest it get in
+    /// the way of `T`’s methods.
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(mut this: Self, f: F)
+        -> AsyncWriteGuard<'a, U, A>
+    {
+        let value: *mut U = f(&mut this);
+        let map = this.map;
+        core::mem::forget(this);
+        AsyncWriteGuard { map, value }
+    }
+
+    /// Turns the write lock into a read lock, without letting any writer in between. This is an
+    /// associated function, lest it get in the way of `T`’s methods.
+    pub fn downgrade(this: Self) -> AsyncReadGuard<'a, T, A> {
+        let (map, value) = (this.map, this.value);
+        core::mem::forget(this);
+        let mut state = map.state();
+        state.writer = false;
+        state.readers += 1;
+        state.wake_all();
+        AsyncReadGuard { map, value }
+    }
+
+    /// Makes a guard for part of the guarded data, or returns the guard unchanged if `f` returns
+    /// `None`. This is an associated function, lest it get in the way of `T`’s methods.
+    pub fn try_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(mut this: Self, f: F)
+        -> Result<AsyncWriteGuard<'a, U, A>, Self>
+    {
+        match f(&mut this).map(|value| value as *mut U) {
+            Some(value) => {
+                let map = this.map;
+                core::mem::forget(this);
+                Ok(AsyncWriteGuard { map, value })
+            }
+            None => Err(this),
+        }
+    }
+}
+
+impl<T: ?Sized, A: ?Sized + Downcast> Deref for AsyncWriteGuard<'_, T, A> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the lock is held for writing for as long as this guard lives.
+        unsafe { &*self.value }
+    }
+}
+
+impl<T: ?Sized, A: ?Sized + Downcast> DerefMut for AsyncWriteGuard<'_, T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as in `deref`.
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<T: ?Sized, A: ?Sized + Downcast> Drop for AsyncWriteGuard<'_, T, A> {
+    fn drop(&mut self) {
+        let mut state = self.map.state();
+        state.writer = false;
+        state.wake_all();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, A: ?Sized + Downcast> fmt::Debug for AsyncWriteGuard<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    /// A future that’s pending the first time it’s polled, to let others in.
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    fn assert_send<T: Send>(t: T) -> T {
+        t
+    }
+
+    #[test]
+    fn test_async_map() {
+        let map = AsyncMap::<dyn Any + Send + Sync>::new();
+        block_on(async {
+            assert_eq!(map.insert(A(0)).await, None);
+            assert_eq!(map.insert(B(1)).await, None);
+            let b = assert_send(map.get::<B>()).await.unwrap();
+            assert_eq!(*b, B(1));
+            // Readers share the lock.
+            assert_eq!(map.get_cloned::<A>().await, Some(A(0)));
+            drop(b);
+            assert!(map.get_mut::<u8>().await.is_none());
+        });
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| block_on(async {
+                    for _ in 0..100 {
+                        let mut a = map.get_mut::<A>().await.unwrap();
+                        let b = map.get_cloned::<B>();
+                        a.0 += 1;
+                        drop(a);
+                        let _ = b.await;
+                    }
+                }));
+            }
+        });
+        block_on(async {
+            assert_eq!(map.remove::<A>().await, Some(A(400)));
+            assert!(!map.contains::<A>().await);
+            assert_eq!(map.len().await, 1);
+            map.clear().await;
+            assert!(map.is_empty().await);
+        });
+        let mut map = map;
+        let _ = map.get_map_mut().insert(A(1));
+        assert_eq!(map.into_inner().get::<A>(), Some(&A(1)));
+    }
+
+    #[test]
+    fn test_get_or_init_with() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let map = AsyncMap::<dyn Any + Send + Sync>::new();
+        let calls = AtomicUsize::new(0);
+        let (map, calls) = (&map, &calls);
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let _ = scope.spawn(move || block_on(async move {
+                    let a = assert_send(map.get_or_init_with(|| async move {
+                        let _ = calls.fetch_add(1, Ordering::Relaxed);
+                        for _ in 0..10 {
+                            YieldNow(false).await;
+                        }
+                        A(i)
+                    })).await;
+                    assert_eq!(a.0, map.get_cloned::<A>().await.unwrap().0);
+                }));
+            }
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // An abandoned initialisation leaves the way clear for the next.
+        block_on(async {
+            let mut abandoned = core::pin::pin!(map.get_or_init_with(|| async {
+                YieldNow(false).await;
+                B(1)
+            }));
+            let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+            assert!(abandoned.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+        });
+        let b = block_on(map.get_or_init_with(|| async { B(2) }));
+        assert_eq!(*b, B(2));
+    }
+}
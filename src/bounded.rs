@@ -0,0 +1,119 @@
+//! A map that refuses to hold more than a fixed number of values.
+
+/// A map that refuses to hold more than a fixed number of values.
+///
+/// `M` is the map it wraps, a `Map` from either of the std and hashbrown implementations, made
+/// into a `BoundedMap` with `Map::into_bounded`. Inserting a value of a new type when the limit
+/// has been reached fails, handing the value back; replacing a value already present always
+/// succeeds. Everything that can’t grow the map is available through `Deref` to the inner `Map`.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// let mut data = anymap::AnyMap::new();
+/// # let _ = data.insert(());
+/// let mut bounded = data.into_bounded(2).unwrap();
+/// assert_eq!(bounded.insert(1i32), Ok(None));
+/// assert_eq!(bounded.insert("str"), Err("str"));
+/// assert_eq!(bounded.insert(2i32), Ok(Some(1)));
+/// assert_eq!(bounded.len(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BoundedMap<M> {
+    pub(crate) map: M,
+    pub(crate) limit: usize,
+}
+
+impl<M> BoundedMap<M> {
+    /// Returns the maximum number of values the collection will hold.
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl<M> core::ops::Deref for BoundedMap<M> {
+    type Target = M;
+
+    #[inline]
+    fn deref(&self) -> &M {
+        &self.map
+    }
+}
+
+// The methods for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+macro_rules! impl_bounded_map {
+    () => {
+        impl<A: ?Sized + Downcast> crate::bounded::BoundedMap<Map<A>> {
+            /// Creates an empty collection which will hold no more than `limit` values.
+            #[inline]
+            pub fn new(limit: usize) -> Self {
+                crate::bounded::BoundedMap {
+                    map: Map::new(),
+                    limit,
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> Map<A, S> {
+            /// Converts this into a [`BoundedMap`](crate::bounded::BoundedMap) which will hold no
+            /// more than `limit` values, or returns it unchanged if it already holds more than
+            /// that.
+            #[inline]
+            pub fn into_bounded(self, limit: usize) -> Result<crate::bounded::BoundedMap<Self>, Self> {
+                if self.len() <= limit {
+                    Ok(crate::bounded::BoundedMap { map: self, limit })
+                } else {
+                    Err(self)
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> crate::bounded::BoundedMap<Map<A, S>> {
+            /// Returns true if the collection can’t take a value of any new type.
+            #[inline]
+            pub fn is_full(&self) -> bool {
+                self.map.len() >= self.limit
+            }
+
+            /// Sets the value stored in the collection for the type `T`.
+            ///
+            /// If the collection already had a value of type `T`, that value is returned in
+            /// `Ok(Some)`; if not, `Ok(None)`. If it had no value of type `T` and is full, `value`
+            /// is returned in `Err` and the collection is unchanged.
+            #[inline]
+            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Result<Option<T>, T> {
+                if self.is_full() && !self.map.contains::<T>() {
+                    Err(value)
+                } else {
+                    Ok(self.map.insert(value))
+                }
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// if it exists.
+            #[inline]
+            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+                self.map.get_mut()
+            }
+
+            /// Removes the `T` value from the collection,
+            /// returning it if there was one or `None` if there was not.
+            #[inline]
+            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+                self.map.remove()
+            }
+
+            /// Removes all items from the collection. Keeps the allocated memory for reuse.
+            #[inline]
+            pub fn clear(&mut self) {
+                self.map.clear()
+            }
+
+            /// Converts this into the inner `Map`, with no limit.
+            #[inline]
+            pub fn into_inner(self) -> Map<A, S> {
+                self.map
+            }
+        }
+    }
+}
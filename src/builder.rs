@@ -0,0 +1,48 @@
+//! Setting up a map in a chain of method calls.
+
+/// A builder for a map, created by `Map::builder`.
+///
+/// `M` is the map being built: a `Map` from either of the std and hashbrown implementations. Once
+/// built, the map is an ordinary `Map`; if you want it to be read-only from then on, just don’t
+/// make it mutable (or share it behind an `Rc` or `Arc`).
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use anymap::AnyMap;
+///
+/// let map = AnyMap::builder()
+///     .with(42i32)
+///     .with_default::<String>()
+///     .build();
+/// assert_eq!(map.get_all::<(i32, String)>(), (Some(&42), Some(&String::new())));
+/// ```
+#[derive(Debug)]
+pub struct Builder<M> {
+    pub(crate) map: M,
+}
+
+// The methods for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+macro_rules! impl_builder {
+    () => {
+        impl<A: ?Sized + Downcast, S: BuildHasher> crate::builder::Builder<Map<A, S>> {
+            /// Sets the value for the type `T`, replacing any previous value.
+            #[inline]
+            pub fn with<T: IntoBox<A>>(mut self, value: T) -> Self {
+                let _ = self.map.insert(value);
+                self
+            }
+
+            /// Sets the value for the type `T` to `T::default()`, replacing any previous value.
+            #[inline]
+            pub fn with_default<T: IntoBox<A> + Default>(self) -> Self {
+                self.with(T::default())
+            }
+
+            /// Finishes building, returning the map.
+            #[inline]
+            pub fn build(self) -> Map<A, S> {
+                self.map
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Casting the values in a map to trait objects of traits they implement.
+
+use core::any::TypeId;
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::any::Downcast;
+
+/// A trait object type that references to `T` can be cast to, such as `dyn Shutdown` for any
+/// `T: Shutdown`; this is what [`Map::bind`](crate::Map::bind) needs to get at a value as a
+/// trait object.
+///
+/// It’s implemented on the trait object, rather than on `T`, so that you can implement it for
+/// all the types that implement your trait at once, which [`impl_cast!`](crate::impl_cast)
+/// does.
+pub trait CastFrom<T> {
+    /// Cast a reference to `T` to a reference to this type.
+    fn cast_ref(value: &T) -> &Self;
+
+    /// Cast a mutable reference to `T` to a mutable reference to this type.
+    fn cast_mut(value: &mut T) -> &mut Self;
+}
+
+/// Implements [`CastFrom`] for a trait of your own, so that values of every type implementing it
+/// can be reached through a map as trait objects, as with [`Map::bind`](crate::Map::bind).
+///
+/// `impl_cast!(Shutdown)` makes `dyn Shutdown`, `dyn Shutdown + Send` and
+/// `dyn Shutdown + Send + Sync` castable from every `T: Shutdown` (and `Send`, and `Sync`, as
+/// needed).
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// trait Greet {
+///     fn greet(&self) -> String;
+/// }
+/// anymap::impl_cast!(Greet);
+///
+/// struct English;
+/// impl Greet for English {
+///     fn greet(&self) -> String { "hello".into() }
+/// }
+///
+/// let mut map = anymap::AnyMap::new();
+/// map.bind::<dyn Greet, _>(English);
+/// assert_eq!(map.get_trait::<dyn Greet>().unwrap().greet(), "hello");
+/// ```
+#[macro_export]
+macro_rules! impl_cast {
+    ($trait:ident) => {
+        $crate::impl_cast!(@impl [$trait], dyn $trait);
+        $crate::impl_cast!(@impl [$trait + Send], dyn $trait + Send);
+        $crate::impl_cast!(@impl [$trait + Send + Sync], dyn $trait + Send + Sync);
+    };
+    (@impl [$($bound:tt)+], $t:ty) => {
+        impl<T: $($bound)+ + 'static> $crate::CastFrom<T> for $t {
+            #[inline]
+            fn cast_ref(value: &T) -> &Self {
+                value
+            }
+
+            #[inline]
+            fn cast_mut(value: &mut T) -> &mut Self {
+                value
+            }
+        }
+    };
+}
+
+/// How to cast the value of one type in a map of `A` to `Tr`.
+struct Caster<Tr: ?Sized, A: ?Sized> {
+    type_id: TypeId,
+    cast_ref: fn(&A) -> &Tr,
+    cast_mut: fn(&mut A) -> &mut Tr,
+}
+
+impl<Tr: ?Sized, A: ?Sized> Clone for Caster<Tr, A> {
+    #[inline]
+    fn clone(&self) -> Caster<Tr, A> {
+        *self
+    }
+}
+
+impl<Tr: ?Sized, A: ?Sized> Copy for Caster<Tr, A> { }
+
+/// The types in a map of `A` whose values can be cast to the trait object `Tr`, and which of
+/// them is bound to it, as recorded by [`Map::bind`](crate::Map::bind) and
+/// [`Map::register_cast`](crate::Map::register_cast).
+///
+/// A map holds one for each `Tr` under the type `TraitCasts<Tr, A>`, so it’s counted among the
+/// map’s values.
+pub struct TraitCasts<Tr: ?Sized, A: ?Sized> {
+    casters: Vec<Caster<Tr, A>>,
+    bound: Option<TypeId>,
+}
+
+impl<Tr: ?Sized, A: ?Sized + Downcast> TraitCasts<Tr, A> {
+    #[inline]
+    pub(crate) fn new() -> TraitCasts<Tr, A> {
+        TraitCasts {
+            casters: Vec::new(),
+            bound: None,
+        }
+    }
+
+    /// Records how to cast a `T` to `Tr`, returning false if it was already known.
+    pub(crate) fn record<T: 'static>(&mut self) -> bool where Tr: CastFrom<T> {
+        let new = self.caster(TypeId::of::<T>()).is_none();
+        if new {
+            self.casters.push(Caster {
+                type_id: TypeId::of::<T>(),
+                // SAFETY: the map only calls these with the value under `TypeId::of::<T>()`.
+                cast_ref: |value| Tr::cast_ref(unsafe { value.downcast_ref_unchecked::<T>() }),
+                cast_mut: |value| Tr::cast_mut(unsafe { value.downcast_mut_unchecked::<T>() }),
+            });
+        }
+        new
+    }
+
+    #[inline]
+    pub(crate) fn bind(&mut self, type_id: TypeId) {
+        self.bound = Some(type_id);
+    }
+
+    #[inline]
+    fn caster(&self, type_id: TypeId) -> Option<&Caster<Tr, A>> {
+        self.casters.iter().find(|caster| caster.type_id == type_id)
+    }
+
+    /// Casts `value`, which must be the map’s value for `type_id`.
+    #[inline]
+    pub(crate) fn cast_ref<'a>(&self, type_id: TypeId, value: &'a A) -> Option<&'a Tr> {
+        self.caster(type_id).map(|caster| (caster.cast_ref)(value))
+    }
+
+    /// Returns the function to cast the map’s value for `type_id` mutably.
+    #[inline]
+    pub(crate) fn cast_mut(&self, type_id: TypeId) -> Option<fn(&mut A) -> &mut Tr> {
+        self.caster(type_id).map(|caster| caster.cast_mut)
+    }
+
+    /// Returns the type bound to `Tr`, if any.
+    #[inline]
+    pub fn bound(&self) -> Option<TypeId> {
+        self.bound
+    }
+
+    /// Returns an iterator over the types known to cast to `Tr`, in the order they were
+    /// recorded.
+    #[inline]
+    pub fn type_ids(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.casters.iter().map(|caster| caster.type_id)
+    }
+}
+
+impl<Tr: ?Sized, A: ?Sized> Clone for TraitCasts<Tr, A> {
+    #[inline]
+    fn clone(&self) -> TraitCasts<Tr, A> {
+        TraitCasts {
+            casters: self.casters.clone(),
+            bound: self.bound,
+        }
+    }
+}
+
+impl<Tr: ?Sized, A: ?Sized> fmt::Debug for TraitCasts<Tr, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TraitCasts")
+            .field("types", &self.casters.len())
+            .field("bound", &self.bound)
+            .finish()
+    }
+}
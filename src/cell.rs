@@ -0,0 +1,207 @@
+//! A single-threaded map with typed mutable access through `&self`.
+
+use core::any::Any;
+use core::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+
+use crate::any::{Downcast, IntoBox};
+#[cfg(not(feature = "std"))]
+use crate::hashbrown::Map;
+#[cfg(feature = "std")]
+use crate::Map;
+
+/// A collection containing zero or one values for any given type, each in its own `RefCell`, so
+/// that [`borrow`](CellMap::borrow) and [`borrow_mut`](CellMap::borrow_mut) work through `&self`.
+///
+/// This is the single-threaded counterpart of [`ShareMap`](crate::sync::ShareMap): one shared
+/// context can be passed around by reference, and values of different types borrowed mutably at
+/// the same time, with no `RefCell` around the whole map. The set of types is changed through
+/// `&mut self`.
+///
+/// ```rust
+/// use anymap::cell::CellMap;
+///
+/// struct Counter(u32);
+///
+/// let mut context = CellMap::<dyn core::any::Any>::new();
+/// context.insert(Counter(0));
+/// context.insert(String::from("log"));
+/// let (mut counter, mut log) = (context.borrow_mut::<Counter>(), context.borrow_mut::<String>());
+/// counter.as_mut().unwrap().0 += 1;
+/// log.as_mut().unwrap().push_str(": counted");
+/// drop((counter, log));
+/// assert_eq!(context.borrow::<Counter>().unwrap().0, 1);
+/// assert_eq!(*context.borrow::<String>().unwrap(), "log: counted");
+/// ```
+pub struct CellMap<A: ?Sized + Downcast = dyn Any> {
+    map: Map<A>,
+}
+
+impl<A: ?Sized + Downcast> Default for CellMap<A> {
+    #[inline]
+    fn default() -> CellMap<A> {
+        CellMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> Clone for CellMap<A> where Box<A>: Clone {
+    /// # Panics
+    ///
+    /// Panics if any value is currently mutably borrowed.
+    #[inline]
+    fn clone(&self) -> CellMap<A> {
+        CellMap { map: self.map.clone() }
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Debug for CellMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CellMap").field("len", &self.map.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> CellMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> CellMap<A> {
+        CellMap { map: Map::new() }
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes all items from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T>(&self) -> bool where RefCell<T>: IntoBox<A> {
+        self.map.contains::<RefCell<T>>()
+    }
+
+    /// Immutably borrows the value stored in the collection for the type `T`, if it exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed. For a non-panicking variant, use
+    /// [`try_borrow`](CellMap::try_borrow).
+    #[inline]
+    pub fn borrow<T>(&self) -> Option<Ref<'_, T>> where RefCell<T>: IntoBox<A> {
+        self.map.get::<RefCell<T>>().map(RefCell::borrow)
+    }
+
+    /// Immutably borrows the value stored in the collection for the type `T`, if it exists,
+    /// returning an error if the value is currently mutably borrowed.
+    #[inline]
+    pub fn try_borrow<T>(&self) -> Option<Result<Ref<'_, T>, BorrowError>>
+    where
+        RefCell<T>: IntoBox<A>,
+    {
+        self.map.get::<RefCell<T>>().map(RefCell::try_borrow)
+    }
+
+    /// Mutably borrows the value stored in the collection for the type `T`, if it exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed. For a non-panicking variant, use
+    /// [`try_borrow_mut`](CellMap::try_borrow_mut).
+    #[inline]
+    pub fn borrow_mut<T>(&self) -> Option<RefMut<'_, T>> where RefCell<T>: IntoBox<A> {
+        self.map.get::<RefCell<T>>().map(RefCell::borrow_mut)
+    }
+
+    /// Mutably borrows the value stored in the collection for the type `T`, if it exists,
+    /// returning an error if the value is currently borrowed.
+    #[inline]
+    pub fn try_borrow_mut<T>(&self) -> Option<Result<RefMut<'_, T>, BorrowMutError>>
+    where
+        RefCell<T>: IntoBox<A>,
+    {
+        self.map.get::<RefCell<T>>().map(RefCell::try_borrow_mut)
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`, if it
+    /// exists; this needs no borrow checking at runtime, since the borrow is exclusive.
+    #[inline]
+    pub fn get_mut<T>(&mut self) -> Option<&mut T> where RefCell<T>: IntoBox<A> {
+        self.map.get_mut::<RefCell<T>>().map(RefCell::get_mut)
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T>(&mut self, value: T) -> Option<T> where RefCell<T>: IntoBox<A> {
+        self.map.insert(RefCell::new(value)).map(RefCell::into_inner)
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T>(&mut self) -> Option<T> where RefCell<T>: IntoBox<A> {
+        self.map.remove::<RefCell<T>>().map(RefCell::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+    use crate::CloneAny;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_cell_map() {
+        let mut map = CellMap::<dyn CloneAny>::new();
+        assert_eq!(map.insert(A(1)), None);
+        assert_eq!(map.insert(B(2)), None);
+        assert!(map.contains::<A>() && !map.contains::<u8>());
+        assert!(map.borrow::<u8>().is_none() && map.borrow_mut::<u8>().is_none());
+
+        let mut a = map.borrow_mut::<A>().unwrap();
+        let b = map.borrow::<B>().unwrap();
+        a.0 += b.0;
+        assert!(map.try_borrow::<A>().unwrap().is_err());
+        assert!(map.try_borrow_mut::<B>().unwrap().is_err());
+        assert!(map.try_borrow::<B>().unwrap().is_ok());
+        drop((a, b));
+        assert_eq!(*map.try_borrow_mut::<A>().unwrap().unwrap(), A(3));
+
+        let clone = map.clone();
+        map.get_mut::<B>().unwrap().0 = 20;
+        assert_eq!(map.insert(B(30)), Some(B(20)));
+        assert_eq!(clone.borrow::<B>().as_deref(), Some(&B(2)));
+        assert_eq!(map.remove::<A>(), Some(A(3)));
+        assert_eq!(format!("{:?}", map), "CellMap { len: 1, .. }");
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(CellMap::<dyn Any>::default().len(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cell_map_conflict() {
+        let mut map = CellMap::<dyn Any>::new();
+        let _ = map.insert(A(1));
+        let _a = map.borrow_mut::<A>();
+        let _ = map.borrow::<A>();
+    }
+}
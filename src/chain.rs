@@ -0,0 +1,134 @@
+//! A map layered over a parent map.
+
+/// A map layered over a parent map: a lookup that misses in its own, local layer falls through to
+/// the parent, while changes only ever touch the local layer.
+///
+/// `M` is the map in each layer, a `Map` from either of the std and hashbrown implementations,
+/// usually layered with `Map::chain`. This is for configuration overridden in a narrower scope,
+/// such as global defaults overridden per request: the parent is only borrowed, so each scope
+/// pays for just its own overrides. Removing a local value lets the parent’s show through again.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// let mut data = anymap::AnyMap::new();
+/// data.insert(80u16);
+/// data.insert("localhost");
+/// let mut request = data.chain();
+/// request.insert(8080u16);
+/// assert_eq!(request.get::<u16>(), Some(&8080));
+/// assert_eq!(request.get::<&str>(), Some(&"localhost"));
+/// assert_eq!(request.len(), 2);
+/// request.remove::<u16>();
+/// assert_eq!(request.get::<u16>(), Some(&80));
+/// ```
+#[derive(Debug)]
+pub struct ChainMap<'a, M> {
+    pub(crate) local: M,
+    pub(crate) parent: &'a M,
+}
+
+impl<'a, M> ChainMap<'a, M> {
+    /// Layers `local` over `parent`.
+    #[inline]
+    pub fn new(local: M, parent: &'a M) -> ChainMap<'a, M> {
+        ChainMap { local, parent }
+    }
+
+    /// Returns a reference to the local layer.
+    #[inline]
+    pub fn local(&self) -> &M {
+        &self.local
+    }
+
+    /// Returns a mutable reference to the local layer.
+    #[inline]
+    pub fn local_mut(&mut self) -> &mut M {
+        &mut self.local
+    }
+
+    /// Returns a reference to the parent.
+    #[inline]
+    pub fn parent(&self) -> &'a M {
+        self.parent
+    }
+
+    /// Converts this into the local layer, letting go of the parent.
+    #[inline]
+    pub fn into_local(self) -> M {
+        self.local
+    }
+}
+
+// The methods for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+macro_rules! impl_chain_map {
+    () => {
+        impl<A: ?Sized + Downcast, S: BuildHasher + Clone> Map<A, S> {
+            /// Returns a [`ChainMap`](crate::chain::ChainMap) with an empty local layer over this
+            /// collection.
+            #[inline]
+            pub fn chain(&self) -> crate::chain::ChainMap<'_, Self> {
+                crate::chain::ChainMap::new(Map::with_hasher(self.hasher().clone()), self)
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast, S: BuildHasher> crate::chain::ChainMap<'a, Map<A, S>> {
+            /// Returns the number of types with a value in either layer.
+            #[inline]
+            pub fn len(&self) -> usize {
+                self.local.len() + self.parent.difference(&self.local).count()
+            }
+
+            /// Returns true if there are no items in either layer.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.local.is_empty() && self.parent.is_empty()
+            }
+
+            /// Returns true if either layer contains a value of type `T`.
+            #[inline]
+            pub fn contains<T: IntoBox<A>>(&self) -> bool {
+                self.local.contains::<T>() || self.parent.contains::<T>()
+            }
+
+            /// Returns a reference to the local value for the type `T` if there is one, or else
+            /// to the parent’s, if it exists.
+            #[inline]
+            pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+                self.local.get::<T>().or_else(|| self.parent.get::<T>())
+            }
+
+            /// Returns a mutable reference to the local value for the type `T`, if it exists. The
+            /// parent can’t be changed through this.
+            #[inline]
+            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+                self.local.get_mut::<T>()
+            }
+
+            /// Sets the local value for the type `T`, overriding the parent’s.
+            /// If the local layer already had a value of type `T`, that value is returned.
+            /// Otherwise, `None` is returned.
+            #[inline]
+            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+                self.local.insert(value)
+            }
+
+            /// Removes the local `T` value, returning it if there was one or `None` if there was
+            /// not. The parent’s value, if any, shows through again.
+            #[inline]
+            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+                self.local.remove::<T>()
+            }
+
+            /// Returns a single map with clones of every value this one would give, the local
+            /// ones taking precedence.
+            pub fn flatten(&self) -> Map<A, S> where Box<A>: Clone, S: Clone {
+                let mut map = self.parent.clone();
+                let local = self.local.raw.iter().map(|(&type_id, value)| (type_id, value.clone()));
+                map.raw.extend(local);
+                #[cfg(feature = "type-names")]
+                map.names.extend(self.local.names.iter().map(|(&type_id, &name)| (type_id, name)));
+                map
+            }
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! The API of anymap 0.12 (and so of anymap2, which forked from it), for moving code to this
+//! version a piece at a time.
+//!
+//! Most of 0.12’s `Map` API is unchanged, and `Map` and its entry types are re-exported here as
+//! they are. What changed is around the edges: the `any` module and its `UncheckedAnyExt` are
+//! gone, and the raw map, once a wrapper whose `insert` was unsafe, reachable safely through
+//! `AsRef`, `AsMut` and `Into`, is now the bare `HashMap` behind `Map::as_raw` and friends. This
+//! module brings back the old shapes, with the old names, so that code like
+//!
+//! ```rust
+//! use anymap::compat::{any::UncheckedAnyExt, raw::RawMap, AnyMap};
+//!
+//! fn total_len<A: ?Sized + UncheckedAnyExt>(raw: &RawMap<A>) -> usize {
+//!     raw.len()
+//! }
+//!
+//! let mut map = AnyMap::new();
+//! map.insert(1u8);
+//! let raw: &mut RawMap = map.as_mut();
+//! assert_eq!(total_len(raw), 1);
+//! assert!(raw.remove(&core::any::TypeId::of::<u8>()).is_some());
+//! ```
+//!
+//! compiles against both, switching `anymap` for `anymap::compat` in its paths. New code
+//! shouldn’t use it.
+
+use core::any::Any;
+
+use crate::any::Downcast;
+
+pub use crate::{Entry, Map, OccupiedEntry, VacantEntry};
+
+/// A map of `dyn Any`, as in 0.12.
+pub type AnyMap = Map<dyn Any>;
+
+/// The `anymap::any` module of 0.12.
+pub mod any {
+    pub use core::any::Any;
+    pub use crate::any::{CloneAny, IntoBox};
+    pub use crate::any::Downcast as UncheckedAnyExt;
+}
+
+/// The `anymap::raw` module of 0.12: a raw map whose inserting methods are unsafe, so that safe
+/// mutable access to it, by `AsMut`, can’t break the `Map` it’s borrowed from.
+pub mod raw {
+    use core::any::{Any, TypeId};
+    use core::fmt;
+    use core::ops::{Deref, Index, IndexMut};
+    use std::collections::hash_map;
+
+    use crate::any::Downcast;
+
+    /// The raw, underlying form of a [`Map`](crate::Map), as in 0.12.
+    ///
+    /// It dereferences to the `HashMap` inside, [`crate::RawMap`], for reading.
+    #[repr(transparent)]
+    pub struct RawMap<A: ?Sized + Downcast = dyn Any> {
+        inner: crate::RawMap<A>,
+    }
+
+    impl<A: ?Sized + Downcast> RawMap<A> {
+        #[inline]
+        pub(crate) fn from_ref(inner: &crate::RawMap<A>) -> &RawMap<A> {
+            // SAFETY: RawMap is a transparent wrapper.
+            unsafe { &*(inner as *const crate::RawMap<A> as *const RawMap<A>) }
+        }
+
+        #[inline]
+        pub(crate) fn from_mut(inner: &mut crate::RawMap<A>) -> &mut RawMap<A> {
+            // SAFETY: RawMap is a transparent wrapper.
+            unsafe { &mut *(inner as *mut crate::RawMap<A> as *mut RawMap<A>) }
+        }
+
+        #[inline]
+        pub(crate) fn from_inner(inner: crate::RawMap<A>) -> RawMap<A> {
+            RawMap { inner }
+        }
+
+        /// Create an empty collection.
+        #[inline]
+        pub fn new() -> RawMap<A> {
+            RawMap { inner: crate::RawMap::default() }
+        }
+
+        /// Creates an empty collection with the given initial capacity.
+        #[inline]
+        pub fn with_capacity(capacity: usize) -> RawMap<A> {
+            RawMap { inner: crate::RawMap::with_capacity_and_hasher(capacity, Default::default()) }
+        }
+
+        /// Returns the number of elements the collection can hold without reallocating.
+        #[inline]
+        pub fn capacity(&self) -> usize {
+            self.inner.capacity()
+        }
+
+        /// Reserves capacity for at least `additional` more elements to be inserted
+        /// in the collection.
+        #[inline]
+        pub fn reserve(&mut self, additional: usize) {
+            self.inner.reserve(additional)
+        }
+
+        /// Shrinks the capacity of the collection as much as possible.
+        #[inline]
+        pub fn shrink_to_fit(&mut self) {
+            self.inner.shrink_to_fit()
+        }
+
+        /// An iterator visiting all entries in arbitrary order.
+        #[inline]
+        pub fn iter(&self) -> Iter<'_, A> {
+            Iter { inner: self.inner.iter() }
+        }
+
+        /// An iterator visiting all entries in arbitrary order, with mutable references.
+        #[inline]
+        pub fn iter_mut(&mut self) -> IterMut<'_, A> {
+            IterMut { inner: self.inner.iter_mut() }
+        }
+
+        /// Clears the map, returning all items as an iterator. Keeps the allocated memory for
+        /// reuse.
+        #[inline]
+        pub fn drain(&mut self) -> Drain<'_, A> {
+            Drain { inner: self.inner.drain() }
+        }
+
+        /// Removes all items from the collection. Keeps the allocated memory for reuse.
+        #[inline]
+        pub fn clear(&mut self) {
+            self.inner.clear()
+        }
+
+        /// Gets the entry for the given type in the collection for in-place manipulation.
+        #[inline]
+        pub fn entry(&mut self, key: TypeId) -> Entry<'_, A> {
+            match self.inner.entry(key) {
+                hash_map::Entry::Occupied(e) => Entry::Occupied(OccupiedEntry { inner: e }),
+                hash_map::Entry::Vacant(e) => Entry::Vacant(VacantEntry { inner: e }),
+            }
+        }
+
+        /// Returns a reference to the value corresponding to the key.
+        #[inline]
+        pub fn get(&self, key: &TypeId) -> Option<&A> {
+            self.inner.get(key).map(|value| &**value)
+        }
+
+        /// Returns true if the map contains a value for the specified key.
+        #[inline]
+        pub fn contains_key(&self, key: &TypeId) -> bool {
+            self.inner.contains_key(key)
+        }
+
+        /// Returns a mutable reference to the value corresponding to the key.
+        #[inline]
+        pub fn get_mut(&mut self, key: &TypeId) -> Option<&mut A> {
+            self.inner.get_mut(key).map(|value| &mut **value)
+        }
+
+        /// Inserts a key-value pair from the map. If the key already had a value present in the
+        /// map, that value is returned. Otherwise, `None` is returned.
+        ///
+        /// # Safety
+        ///
+        /// `key` must be the `TypeId` of the value’s type, or *undefined behaviour* will occur
+        /// when the value is accessed through the `Map`.
+        #[inline]
+        pub unsafe fn insert(&mut self, key: TypeId, value: Box<A>) -> Option<Box<A>> {
+            self.inner.insert(key, value)
+        }
+
+        /// Removes a key from the map, returning the value at the key if the key was previously
+        /// in the map.
+        #[inline]
+        pub fn remove(&mut self, key: &TypeId) -> Option<Box<A>> {
+            self.inner.remove(key)
+        }
+
+        /// Returns the `HashMap` inside.
+        #[inline]
+        pub fn into_inner(self) -> crate::RawMap<A> {
+            self.inner
+        }
+    }
+
+    impl<A: ?Sized + Downcast> Default for RawMap<A> {
+        #[inline]
+        fn default() -> RawMap<A> {
+            RawMap::new()
+        }
+    }
+
+    impl<A: ?Sized + Downcast> Clone for RawMap<A> where Box<A>: Clone {
+        #[inline]
+        fn clone(&self) -> RawMap<A> {
+            RawMap { inner: self.inner.clone() }
+        }
+    }
+
+    impl<A: ?Sized + Downcast> fmt::Debug for RawMap<A> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("RawMap").field("len", &self.inner.len()).finish_non_exhaustive()
+        }
+    }
+
+    impl<A: ?Sized + Downcast> Deref for RawMap<A> {
+        type Target = crate::RawMap<A>;
+
+        #[inline]
+        fn deref(&self) -> &crate::RawMap<A> {
+            &self.inner
+        }
+    }
+
+    impl<A: ?Sized + Downcast> Index<TypeId> for RawMap<A> {
+        type Output = A;
+
+        #[inline]
+        fn index(&self, key: TypeId) -> &A {
+            self.get(&key).expect("no entry found for key")
+        }
+    }
+
+    impl<A: ?Sized + Downcast> IndexMut<TypeId> for RawMap<A> {
+        #[inline]
+        fn index_mut(&mut self, key: TypeId) -> &mut A {
+            self.get_mut(&key).expect("no entry found for key")
+        }
+    }
+
+    impl<'a, A: ?Sized + Downcast> IntoIterator for &'a RawMap<A> {
+        type Item = &'a A;
+        type IntoIter = Iter<'a, A>;
+
+        #[inline]
+        fn into_iter(self) -> Iter<'a, A> {
+            self.iter()
+        }
+    }
+
+    impl<'a, A: ?Sized + Downcast> IntoIterator for &'a mut RawMap<A> {
+        type Item = &'a mut A;
+        type IntoIter = IterMut<'a, A>;
+
+        #[inline]
+        fn into_iter(self) -> IterMut<'a, A> {
+            self.iter_mut()
+        }
+    }
+
+    impl<A: ?Sized + Downcast> IntoIterator for RawMap<A> {
+        type Item = Box<A>;
+        type IntoIter = IntoIter<A>;
+
+        #[inline]
+        fn into_iter(self) -> IntoIter<A> {
+            IntoIter { inner: self.inner.into_iter() }
+        }
+    }
+
+    macro_rules! impl_iterator {
+        (
+            $(#[$attr:meta])*
+            $ty:ident$(<$lt:lifetime>)?, $item:ty, $inner:ty, |$x:ident| $map:expr
+        ) => {
+            $(#[$attr])*
+            pub struct $ty<$($lt,)? A: ?Sized + Downcast> {
+                inner: $inner,
+            }
+
+            impl<$($lt,)? A: ?Sized + Downcast> Iterator for $ty<$($lt,)? A> {
+                type Item = $item;
+
+                #[inline]
+                fn next(&mut self) -> Option<$item> {
+                    self.inner.next().map(|$x| $map)
+                }
+
+                #[inline]
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    self.inner.size_hint()
+                }
+            }
+
+            impl<$($lt,)? A: ?Sized + Downcast> ExactSizeIterator for $ty<$($lt,)? A> { }
+        };
+    }
+
+    impl_iterator! {
+        /// An iterator over references to the values of a [`RawMap`].
+        Iter<'a>, &'a A, hash_map::Iter<'a, TypeId, Box<A>>, |x| &**x.1
+    }
+    impl_iterator! {
+        /// An iterator over mutable references to the values of a [`RawMap`].
+        IterMut<'a>, &'a mut A, hash_map::IterMut<'a, TypeId, Box<A>>, |x| &mut **x.1
+    }
+    impl_iterator! {
+        /// An iterator over the values of a [`RawMap`], consuming it.
+        IntoIter, Box<A>, hash_map::IntoIter<TypeId, Box<A>>, |x| x.1
+    }
+    impl_iterator! {
+        /// An iterator draining the values of a [`RawMap`].
+        Drain<'a>, Box<A>, hash_map::Drain<'a, TypeId, Box<A>>, |x| x.1
+    }
+
+    /// A view into a single occupied location in a [`RawMap`].
+    pub struct OccupiedEntry<'a, A: ?Sized + Downcast> {
+        inner: hash_map::OccupiedEntry<'a, TypeId, Box<A>>,
+    }
+
+    /// A view into a single empty location in a [`RawMap`].
+    pub struct VacantEntry<'a, A: ?Sized + Downcast> {
+        inner: hash_map::VacantEntry<'a, TypeId, Box<A>>,
+    }
+
+    /// A view into a single location in a [`RawMap`], which may be vacant or occupied.
+    pub enum Entry<'a, A: ?Sized + Downcast> {
+        /// An occupied Entry
+        Occupied(OccupiedEntry<'a, A>),
+        /// A vacant Entry
+        Vacant(VacantEntry<'a, A>),
+    }
+
+    impl<'a, A: ?Sized + Downcast> Entry<'a, A> {
+        /// Ensures a value is in the entry by inserting the default if empty, and returns
+        /// a mutable reference to the value in the entry.
+        ///
+        /// # Safety
+        ///
+        /// The value must be of the type the entry is for, as with [`RawMap::insert`].
+        #[inline]
+        pub unsafe fn or_insert(self, default: Box<A>) -> &'a mut A {
+            match self {
+                Entry::Occupied(inner) => inner.into_mut(),
+                Entry::Vacant(inner) => inner.insert(default),
+            }
+        }
+
+        /// Ensures a value is in the entry by inserting the result of the default function if
+        /// empty, and returns a mutable reference to the value in the entry.
+        ///
+        /// # Safety
+        ///
+        /// The value must be of the type the entry is for, as with [`RawMap::insert`].
+        #[inline]
+        pub unsafe fn or_insert_with<F: FnOnce() -> Box<A>>(self, default: F) -> &'a mut A {
+            match self {
+                Entry::Occupied(inner) => inner.into_mut(),
+                Entry::Vacant(inner) => inner.insert(default()),
+            }
+        }
+    }
+
+    impl<'a, A: ?Sized + Downcast> OccupiedEntry<'a, A> {
+        /// Gets a reference to the value in the entry.
+        #[inline]
+        pub fn get(&self) -> &A {
+            self.inner.get()
+        }
+
+        /// Gets a mutable reference to the value in the entry.
+        #[inline]
+        pub fn get_mut(&mut self) -> &mut A {
+            self.inner.get_mut()
+        }
+
+        /// Converts the OccupiedEntry into a mutable reference to the value in the entry
+        /// with a lifetime bound to the collection itself.
+        #[inline]
+        pub fn into_mut(self) -> &'a mut A {
+            self.inner.into_mut()
+        }
+
+        /// Sets the value of the entry, and returns the entry’s old value.
+        ///
+        /// # Safety
+        ///
+        /// The value must be of the type the entry is for, as with [`RawMap::insert`].
+        #[inline]
+        pub unsafe fn insert(&mut self, value: Box<A>) -> Box<A> {
+            self.inner.insert(value)
+        }
+
+        /// Takes the value out of the entry, and returns it.
+        #[inline]
+        pub fn remove(self) -> Box<A> {
+            self.inner.remove()
+        }
+    }
+
+    impl<'a, A: ?Sized + Downcast> VacantEntry<'a, A> {
+        /// Sets the value of the entry with the VacantEntry’s key,
+        /// and returns a mutable reference to it.
+        ///
+        /// # Safety
+        ///
+        /// The value must be of the type the entry is for, as with [`RawMap::insert`].
+        #[inline]
+        pub unsafe fn insert(self, value: Box<A>) -> &'a mut A {
+            self.inner.insert(value)
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> AsRef<raw::RawMap<A>> for Map<A> {
+    #[inline]
+    fn as_ref(&self) -> &raw::RawMap<A> {
+        raw::RawMap::from_ref(self.as_raw())
+    }
+}
+
+/// Safe, since the raw map can’t be inserted into without `unsafe`.
+impl<A: ?Sized + Downcast> AsMut<raw::RawMap<A>> for Map<A> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut raw::RawMap<A> {
+        // raw::RawMap only inserts through unsafe methods, with the same contract as Map’s.
+        raw::RawMap::from_mut(&mut self.raw)
+    }
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for raw::RawMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> raw::RawMap<A> {
+        raw::RawMap::from_inner(map.into_raw())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::any::TypeId;
+
+    use super::*;
+    use super::raw::RawMap;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_raw() {
+        let mut map = AnyMap::new();
+        let _ = map.insert(A(1));
+        let _ = map.insert(B(2));
+        {
+            let raw: &RawMap = map.as_ref();
+            assert_eq!(raw.len(), 2);
+            assert!(raw.contains_key(&TypeId::of::<A>()));
+            assert_eq!(raw.iter().count(), 2);
+            assert!(raw[TypeId::of::<B>()].is::<B>());
+        }
+        {
+            let raw: &mut RawMap = map.as_mut();
+            raw.get_mut(&TypeId::of::<A>()).unwrap().downcast_mut::<A>().unwrap().0 = 10;
+            let b = raw.remove(&TypeId::of::<B>()).unwrap();
+            match raw.entry(TypeId::of::<B>()) {
+                raw::Entry::Vacant(entry) => unsafe { let _ = entry.insert(b); },
+                raw::Entry::Occupied(_) => unreachable!(),
+            }
+            match raw.entry(TypeId::of::<A>()) {
+                raw::Entry::Occupied(entry) => assert!(entry.get().is::<A>()),
+                raw::Entry::Vacant(_) => unreachable!(),
+            }
+        }
+        assert_eq!(map.get(), Some(&A(10)));
+        assert_eq!(map.get(), Some(&B(2)));
+
+        let mut raw = RawMap::from(map);
+        assert_eq!(raw.drain().count(), 2);
+        unsafe {
+            let _ = raw.insert(TypeId::of::<A>(), Box::new(A(3)));
+        }
+        let map = unsafe { Map::from_raw(raw.into_inner()) };
+        assert_eq!(map.get(), Some(&A(3)));
+    }
+}
@@ -0,0 +1,290 @@
+//! A map sharded across several locks, for many threads at once.
+
+use core::any::{Any, TypeId};
+use core::hash::{BuildHasher, BuildHasherDefault};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::any::{Downcast, IntoBox};
+use crate::sync::{MappedReadGuard, MappedWriteGuard};
+use crate::{Map, TypeIdHasher};
+
+/// A collection containing zero or one values for any given type, like a
+/// [`SyncMap`](crate::sync::SyncMap), but split into shards, each behind its own `RwLock`.
+///
+/// Each type’s value lives in one shard, chosen by its `TypeId`, so threads working with
+/// different types mostly take different locks rather than all contending for one. The methods
+/// are as for `SyncMap`, taking just the one shard’s lock; those that look at the whole map,
+/// such as [`len`](ConcurrentMap::len), take each shard’s lock in turn, so they don’t see a
+/// single consistent moment if other threads are busy with it.
+///
+/// Lock poisoning is ignored, as for `SyncMap`.
+///
+/// ```rust
+/// use anymap::concurrent::ConcurrentMap;
+///
+/// let data: ConcurrentMap = ConcurrentMap::new();
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| data.insert(42i32));
+///     scope.spawn(|| data.insert("str"));
+/// });
+/// assert_eq!(*data.get::<i32>().unwrap(), 42);
+/// assert_eq!(data.len(), 2);
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    shards: Box<[RwLock<Map<A>>]>,
+}
+
+impl<A: ?Sized + Downcast> Default for ConcurrentMap<A> {
+    #[inline]
+    fn default() -> ConcurrentMap<A> {
+        ConcurrentMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for ConcurrentMap<A> {
+    fn from(mut map: Map<A>) -> ConcurrentMap<A> {
+        let mut concurrent = ConcurrentMap::new();
+        for i in 0..concurrent.shards.len() {
+            let shard = map.split_off_with(|type_id, _| concurrent.shard_index(&type_id) == i);
+            *concurrent.shards[i].get_mut().unwrap_or_else(PoisonError::into_inner) = shard;
+        }
+        concurrent
+    }
+}
+
+impl<A: ?Sized + Downcast> ConcurrentMap<A> {
+    /// Create an empty collection, with four times as many shards as the machine has threads
+    /// (rounded up to a power of two).
+    #[inline]
+    pub fn new() -> ConcurrentMap<A> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        ConcurrentMap::with_shard_amount(threads * 4)
+    }
+
+    /// Create an empty collection with the given number of shards, rounded up to a power of two.
+    #[inline]
+    pub fn with_shard_amount(shard_amount: usize) -> ConcurrentMap<A> {
+        ConcurrentMap {
+            shards: (0..shard_amount.max(1).next_power_of_two())
+                .map(|_| RwLock::new(Map::new()))
+                .collect(),
+        }
+    }
+
+    /// Returns the number of shards.
+    #[inline]
+    pub fn shard_amount(&self) -> usize {
+        self.shards.len()
+    }
+
+    #[inline]
+    fn shard_index(&self, type_id: &TypeId) -> usize {
+        // The low bits pick the bucket within the shard’s table and the top seven are hashbrown’s
+        // tag, so take the shard from the middle, lest the shards’ tables be lopsided.
+        let hash = BuildHasherDefault::<TypeIdHasher>::default().hash_one(type_id);
+        (hash >> 32) as usize & (self.shards.len() - 1)
+    }
+
+    #[inline]
+    fn read_shard(&self, index: usize) -> RwLockReadGuard<'_, Map<A>> {
+        self.shards[index].read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[inline]
+    fn write_shard(&self, index: usize) -> RwLockWriteGuard<'_, Map<A>> {
+        self.shards[index].write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the shard that the type `T` belongs in for reading, blocking until that’s possible.
+    #[inline]
+    pub fn read_shard_of<T: IntoBox<A>>(&self) -> RwLockReadGuard<'_, Map<A>> {
+        self.read_shard(self.shard_index(&TypeId::of::<T>()))
+    }
+
+    /// Locks the shard that the type `T` belongs in for writing, blocking until that’s
+    /// possible.
+    #[inline]
+    pub fn write_shard_of<T: IntoBox<A>>(&self) -> RwLockWriteGuard<'_, Map<A>> {
+        self.write_shard(self.shard_index(&TypeId::of::<T>()))
+    }
+
+    /// Returns the map, merging the shards back together.
+    pub fn into_inner(self) -> Map<A> {
+        let mut map = Map::new();
+        for shard in self.shards.into_vec() {
+            let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+            map.merge_with(shard, |_, _, new| new);
+        }
+        map
+    }
+
+    /// Returns the number of items in the collection.
+    pub fn len(&self) -> usize {
+        (0..self.shards.len()).map(|i| self.read_shard(i).len()).sum()
+    }
+
+    /// Returns true if there are no items in the collection.
+    pub fn is_empty(&self) -> bool {
+        (0..self.shards.len()).all(|i| self.read_shard(i).is_empty())
+    }
+
+    /// Removes all items from the collection.
+    pub fn clear(&self) {
+        for i in 0..self.shards.len() {
+            self.write_shard(i).clear();
+        }
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.read_shard_of::<T>().contains::<T>()
+    }
+
+    /// Returns a guard dereferencing to the value stored in the collection for the type `T`, if
+    /// it exists. The shard is locked for reading until the guard is dropped.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<MappedReadGuard<'_, T, A>> {
+        let guard = self.read_shard_of::<T>();
+        let value: *const T = guard.get::<T>()?;
+        Some(MappedReadGuard { _guard: guard, value })
+    }
+
+    /// Returns a clone of the value stored in the collection for the type `T`, if it exists,
+    /// holding the shard’s lock only for as long as that takes.
+    #[inline]
+    pub fn get_cloned<T: IntoBox<A> + Clone>(&self) -> Option<T> {
+        self.read_shard_of::<T>().get_cloned::<T>()
+    }
+
+    /// Returns a guard dereferencing mutably to the value stored in the collection for the type
+    /// `T`, if it exists. The shard is locked for writing until the guard is dropped.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&self) -> Option<MappedWriteGuard<'_, T, A>> {
+        let mut guard = self.write_shard_of::<T>();
+        let value: *mut T = guard.get_mut::<T>()?;
+        Some(MappedWriteGuard { _guard: guard, value })
+    }
+
+    /// Returns a guard dereferencing mutably to the value stored in the collection for the type
+    /// `T`, inserting the result of `f` first if there was none. The shard is locked for writing
+    /// until the guard is dropped (and while `f` runs).
+    #[inline]
+    pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&self, f: F)
+        -> MappedWriteGuard<'_, T, A>
+    {
+        let mut guard = self.write_shard_of::<T>();
+        let value: *mut T = guard.get_or_insert_with(f);
+        MappedWriteGuard { _guard: guard, value }
+    }
+
+    /// Changes the value stored in the collection for the type `T` with `f`, if `check` passes
+    /// on it, returning whether it did.
+    ///
+    /// `check` is first run with the shard locked only for reading, so a value that doesn’t need
+    /// changing holds up no one; if it passes, it’s run again with the shard locked for writing,
+    /// in case the value changed in between, and only then is `f` run.
+    pub fn compare_and_update<T, C, F>(&self, mut check: C, f: F) -> bool
+    where
+        T: IntoBox<A>,
+        C: FnMut(&T) -> bool,
+        F: FnOnce(&mut T),
+    {
+        if !self.read_shard_of::<T>().get::<T>().is_some_and(&mut check) {
+            return false;
+        }
+        match self.write_shard_of::<T>().get_mut::<T>() {
+            Some(value) if check(value) => {
+                f(value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the value stored in the collection for the type `T` with what `f` makes of it,
+    /// like `AtomicUsize::fetch_update`, returning the value it replaced.
+    ///
+    /// `f` runs with no lock held, on a clone of the value; the result is then stored only if
+    /// the value is still equal to that clone, and otherwise `f` is run again on the new value.
+    /// If `f` returns `None`, nothing is stored and `Err(Some(value))` is returned; if there is
+    /// no value of type `T`, `Err(None)`.
+    pub fn fetch_update<T, F>(&self, mut f: F) -> Result<T, Option<T>>
+    where
+        T: IntoBox<A> + Clone + PartialEq,
+        F: FnMut(&T) -> Option<T>,
+    {
+        let mut current = self.get_cloned::<T>().ok_or(None)?;
+        loop {
+            let new = match f(&current) {
+                Some(new) => new,
+                None => return Err(Some(current)),
+            };
+            let mut shard = self.write_shard_of::<T>();
+            let value = shard.get_mut::<T>().ok_or(None)?;
+            if *value == current {
+                return Ok(core::mem::replace(value, new));
+            }
+            current = value.clone();
+        }
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&self, value: T) -> Option<T> {
+        self.write_shard_of::<T>().insert(value)
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&self) -> Option<T> {
+        self.write_shard_of::<T>().remove::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+    #[derive(Clone, Debug, PartialEq)] struct C(i32);
+
+    #[test]
+    fn test_concurrent_map() {
+        let map = ConcurrentMap::<dyn Any + Send + Sync>::with_shard_amount(3);
+        assert_eq!(map.shard_amount(), 4);
+        assert_eq!(map.insert(A(0)), None);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| map.get_mut::<A>().unwrap().0 += 1);
+                let _ = scope.spawn(|| map.get_or_insert_with(|| B(0)).0 += 1);
+                let _ = scope.spawn(|| map.insert(C(1)));
+            }
+        });
+        assert_eq!(*map.get::<A>().unwrap(), A(4));
+        assert_eq!(map.get_cloned::<B>(), Some(B(4)));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.remove::<C>(), Some(C(1)));
+        assert!(!map.contains::<C>());
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| map.fetch_update::<A, _>(|a| Some(A(a.0 * 2))));
+                let _ = scope.spawn(|| map.compare_and_update::<B, _, _>(|b| b.0 < 6, |b| b.0 += 1));
+            }
+        });
+        assert_eq!(map.get_cloned::<A>(), Some(A(64)));
+        assert_eq!(map.get_cloned::<B>(), Some(B(6)));
+        assert_eq!(map.fetch_update::<A, _>(|_| None), Err(Some(A(64))));
+        assert_eq!(map.fetch_update::<C, _>(|c| Some(c.clone())), Err(None));
+        assert!(!map.compare_and_update::<C, _, _>(|_| true, |_| ()));
+        let map = ConcurrentMap::from(map.into_inner());
+        assert_eq!(map.len(), 2);
+        map.clear();
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+//! A map with cheap snapshots, sharing its values until they’re changed.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::hash::{BuildHasher, BuildHasherDefault};
+use std::collections::hash_map::HashMap;
+use std::sync::Arc;
+
+use crate::any::{Downcast, IntoBox};
+use crate::{Map, TypeIdHasher};
+
+/// Returns the `T` held by `value`, cloning it if anything else shares it.
+///
+/// # Safety
+///
+/// The value must be of type `T`.
+#[inline]
+unsafe fn unwrap_or_clone<A: ?Sized, T: Clone>(value: Arc<A>) -> T {
+    let value = Arc::from_raw(Arc::into_raw(value) as *const T);
+    Arc::try_unwrap(value).unwrap_or_else(|value| T::clone(&value))
+}
+
+/// A collection containing zero or one values for any given type, with cheap, immutable
+/// [`Snapshot`]s that share storage with it.
+///
+/// Each value sits in its own `Arc`. Taking a [`snapshot`](CowMap::snapshot) clones those `Arc`s,
+/// not the values; changing a value afterwards through [`get_mut`](CowMap::get_mut) clones just
+/// that value, if a snapshot still shares it, and [`restore`](CowMap::restore) rolls the whole
+/// map back. That makes it a good fit for rollback-able simulation state, or double-buffering
+/// between an update and a render: only what’s touched gets copied.
+///
+/// Copying on write needs the values to be `Clone`. By default the values are
+/// `dyn Any + Send + Sync`, so that snapshots can be sent to other threads; as with [`Map`], `A`
+/// can be any other trait object this crate supports, and `S` the hasher.
+///
+/// ```rust
+/// use anymap::cow::CowMap;
+///
+/// let mut state: CowMap = CowMap::new();
+/// state.insert(1i32);
+/// state.insert(String::from("big, rarely changed"));
+/// let before = state.snapshot();
+/// *state.get_mut::<i32>().unwrap() += 1;
+/// assert_eq!(before.get::<i32>(), Some(&1));
+/// assert_eq!(state.get::<i32>(), Some(&2));
+/// // The string was never written to, so it’s still shared.
+/// assert!(std::ptr::eq(before.get::<String>().unwrap(), state.get::<String>().unwrap()));
+/// state.restore(&before);
+/// assert_eq!(state.get::<i32>(), Some(&1));
+/// ```
+pub struct CowMap<
+    A: ?Sized + Downcast = dyn Any + Send + Sync,
+    S = BuildHasherDefault<TypeIdHasher>,
+> {
+    raw: HashMap<TypeId, Arc<A>, S>,
+}
+
+/// An immutable view of a [`CowMap`] at the moment [`CowMap::snapshot`] was called, sharing its
+/// values with the map until the map changes them.
+pub struct Snapshot<
+    A: ?Sized + Downcast = dyn Any + Send + Sync,
+    S = BuildHasherDefault<TypeIdHasher>,
+> {
+    raw: HashMap<TypeId, Arc<A>, S>,
+}
+
+impl<A: ?Sized + Downcast, S: Default> Default for CowMap<A, S> {
+    #[inline]
+    fn default() -> CowMap<A, S> {
+        CowMap { raw: HashMap::default() }
+    }
+}
+
+impl<A: ?Sized + Downcast, S: Default> Default for Snapshot<A, S> {
+    #[inline]
+    fn default() -> Snapshot<A, S> {
+        Snapshot { raw: HashMap::default() }
+    }
+}
+
+impl<A: ?Sized + Downcast, S: Clone> Clone for CowMap<A, S> {
+    #[inline]
+    fn clone(&self) -> CowMap<A, S> {
+        CowMap { raw: self.raw.clone() }
+    }
+}
+
+impl<A: ?Sized + Downcast, S: Clone> Clone for Snapshot<A, S> {
+    #[inline]
+    fn clone(&self) -> Snapshot<A, S> {
+        Snapshot { raw: self.raw.clone() }
+    }
+}
+
+impl<A: ?Sized + Downcast, S> fmt::Debug for CowMap<A, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CowMap").field("len", &self.raw.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast, S> fmt::Debug for Snapshot<A, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Snapshot").field("len", &self.raw.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast, S> From<Snapshot<A, S>> for CowMap<A, S> {
+    #[inline]
+    fn from(snapshot: Snapshot<A, S>) -> CowMap<A, S> {
+        CowMap { raw: snapshot.raw }
+    }
+}
+
+impl<A: ?Sized + Downcast, S: BuildHasher + Clone> From<Map<A, S>> for CowMap<A, S> {
+    #[inline]
+    fn from(map: Map<A, S>) -> CowMap<A, S> {
+        let mut shared = HashMap::with_capacity_and_hasher(map.len(), map.hasher().clone());
+        let raw = map.into_raw();
+        shared.extend(raw.into_iter().map(|(type_id, value)| (type_id, Arc::from(value))));
+        CowMap { raw: shared }
+    }
+}
+
+impl<A: ?Sized + Downcast, S: Default> CowMap<A, S> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> CowMap<A, S> {
+        CowMap::default()
+    }
+}
+
+impl<A: ?Sized + Downcast, S: BuildHasher> CowMap<A, S> {
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.raw.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        // SAFETY: the table only ever holds a T under TypeId::of::<T>().
+        self.raw.get(&TypeId::of::<T>()).map(|any| unsafe { any.downcast_ref_unchecked::<T>() })
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`, if it
+    /// exists, first cloning it if a snapshot shares it.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A> + Clone>(&mut self) -> Option<&mut T> {
+        let value = self.raw.get_mut(&TypeId::of::<T>())?;
+        if Arc::get_mut(value).is_none() {
+            // SAFETY: the table only ever holds a T under TypeId::of::<T>().
+            let copy = unsafe { value.downcast_ref_unchecked::<T>() }.clone();
+            *value = Arc::from(copy.into_box());
+        }
+        // SAFETY: as above; and the value was just unshared, if it wasn’t already.
+        Arc::get_mut(value).map(|any| unsafe { any.downcast_mut_unchecked::<T>() })
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned (cloned, if a
+    /// snapshot shares it). Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A> + Clone>(&mut self, value: T) -> Option<T> {
+        // SAFETY: the table only ever holds a T under TypeId::of::<T>().
+        self.raw.insert(TypeId::of::<T>(), Arc::from(value.into_box()))
+            .map(|any| unsafe { unwrap_or_clone::<A, T>(any) })
+    }
+
+    /// Removes the `T` value from the collection, returning it (cloned, if a snapshot shares it)
+    /// if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A> + Clone>(&mut self) -> Option<T> {
+        // SAFETY: the table only ever holds a T under TypeId::of::<T>().
+        self.raw.remove(&TypeId::of::<T>()).map(|any| unsafe { unwrap_or_clone::<A, T>(any) })
+    }
+
+    /// Removes all items from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.raw.clear()
+    }
+
+    /// Returns a snapshot of the collection as it is now, sharing all of its values.
+    #[inline]
+    pub fn snapshot(&self) -> Snapshot<A, S>
+    where
+        S: Clone,
+    {
+        Snapshot { raw: self.raw.clone() }
+    }
+
+    /// Puts the collection back as it was when `snapshot` was taken.
+    #[inline]
+    pub fn restore(&mut self, snapshot: &Snapshot<A, S>)
+    where
+        S: Clone,
+    {
+        self.raw.clone_from(&snapshot.raw)
+    }
+}
+
+impl<A: ?Sized + Downcast, S: BuildHasher> Snapshot<A, S> {
+    /// Returns the number of items in the snapshot.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Returns true if there are no items in the snapshot.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Returns true if the snapshot contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.raw.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the value in the snapshot for the type `T`, if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        // SAFETY: the table only ever holds a T under TypeId::of::<T>().
+        self.raw.get(&TypeId::of::<T>()).map(|any| unsafe { any.downcast_ref_unchecked::<T>() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+    #[derive(Clone, Debug, PartialEq)] struct C(i32);
+
+    #[test]
+    fn test_cow_map() {
+        let mut map = CowMap::<dyn Any + Send + Sync>::new();
+        assert_eq!(map.insert(A(1)), None);
+        assert_eq!(map.insert(B(2)), None);
+
+        // Unshared values are changed in place.
+        let a: *const A = map.get::<A>().unwrap();
+        map.get_mut::<A>().unwrap().0 = 10;
+        assert!(core::ptr::eq(a, map.get::<A>().unwrap()));
+
+        let snapshot = map.snapshot();
+        map.get_mut::<A>().unwrap().0 = 20;
+        assert!(!core::ptr::eq(a, map.get::<A>().unwrap()));
+        assert!(core::ptr::eq(snapshot.get::<B>().unwrap(), map.get::<B>().unwrap()));
+        assert_eq!(map.insert(C(3)), None);
+        assert_eq!(map.remove::<B>(), Some(B(2)));
+        assert_eq!(snapshot.get::<A>(), Some(&A(10)));
+        assert_eq!(snapshot.get::<B>(), Some(&B(2)));
+        assert!(!snapshot.contains::<C>());
+        assert_eq!((map.len(), snapshot.len()), (2, 2));
+
+        map.restore(&snapshot);
+        assert_eq!(map.get::<A>(), Some(&A(10)));
+        assert!(!map.contains::<C>());
+        drop(snapshot);
+        assert_eq!(map.remove::<A>(), Some(A(10)));
+        map.clear();
+        assert!(CowMap::from(map.snapshot()).is_empty());
+
+        // Any trait object will do, if snapshots needn’t leave the thread.
+        let mut boxed: Map<dyn crate::any::CloneAny> = Map::new();
+        let _ = boxed.insert(Rc::new(A(1)));
+        let mut map = CowMap::from(boxed);
+        let snapshot = map.snapshot();
+        *map.get_mut::<Rc<A>>().unwrap() = Rc::new(A(2));
+        assert_eq!(snapshot.get::<Rc<A>>().map(|a| a.0), Some(1));
+        assert_eq!(map.remove::<Rc<A>>().map(|a| a.0), Some(2));
+    }
+}
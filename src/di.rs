@@ -0,0 +1,292 @@
+//! Dependency injection: values built on demand by registered factories, and cached.
+
+use core::any::{Any, TypeId};
+use core::cell::RefCell;
+use core::fmt;
+use std::error::Error;
+
+use crate::frozen::FrozenAnyMap;
+use crate::Map;
+
+/// Any error, as a factory may fail with.
+type BoxError = Box<dyn Error + Send + Sync>;
+
+type FactoryFn<T> = dyn Fn(&Container) -> Result<T, BoxError>;
+
+/// A factory for values of type `T`, stored in the container’s map under its own type.
+struct Factory<T>(Box<FactoryFn<T>>);
+
+/// A dependency injection container: register a factory for each type, then
+/// [`resolve`](Container::resolve) the one you want, and it’s built, along with everything it
+/// depends on, the first time it’s asked for.
+///
+/// A factory gets the container, so it can resolve its own dependencies, and returns a
+/// `Result` with any error, so that its own failures and its dependencies’ can be passed on with
+/// `?`. Each value is built once
+/// and then cached: every `resolve` hands out a reference to the same one, which lives as long
+/// as the borrow of the container. (For values to be shared more widely, build them as `Arc`s.)
+///
+/// A dependency cycle, where a type’s factory ends up resolving that same type, is reported as
+/// an error rather than recursing forever.
+///
+/// ```rust
+/// use anymap::di::Container;
+///
+/// struct Config { url: &'static str }
+/// struct Database { url: &'static str }
+/// struct Users<'a> { database: &'a Database }
+///
+/// let mut container = Container::new();
+/// container.insert(Config { url: "postgres://localhost" });
+/// container.register(|c| Ok(Database { url: c.resolve::<Config>()?.url }));
+///
+/// let users = Users { database: container.resolve::<Database>().unwrap() };
+/// assert_eq!(users.database.url, "postgres://localhost");
+///
+/// // The error says what was being built when something was missing.
+/// let mut container = Container::new();
+/// container.register(|c| Ok(Database { url: c.resolve::<Config>()?.url }));
+/// let error = container.resolve::<Database>().err().unwrap();
+/// assert!(error.to_string().contains("Database"));
+/// assert!(std::error::Error::source(&error).unwrap().to_string().contains("Config"));
+/// ```
+#[derive(Default)]
+pub struct Container {
+    factories: Map<dyn Any>,
+    values: FrozenAnyMap<dyn Any>,
+    resolving: RefCell<Vec<(TypeId, &'static str)>>,
+}
+
+// Not showing the values, for the same reason as `FrozenAnyMap`.
+impl fmt::Debug for Container {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Container")
+            .field("factories", &self.factories.len())
+            .field("values", &self.values.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Takes a type back off the stack of those being resolved when dropped.
+struct Resolving<'a>(&'a RefCell<Vec<(TypeId, &'static str)>>);
+
+impl Drop for Resolving<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.borrow_mut().pop();
+    }
+}
+
+impl Container {
+    /// Create an empty container.
+    #[inline]
+    pub fn new() -> Container {
+        Container::default()
+    }
+
+    /// Registers `factory` to build the value of type `T` when it’s first resolved, replacing
+    /// any factory previously registered for `T`. A value of type `T` already built or inserted
+    /// is kept.
+    #[inline]
+    pub fn register<T, F>(&mut self, factory: F)
+    where
+        T: 'static,
+        F: Fn(&Container) -> Result<T, Box<dyn Error + Send + Sync>> + 'static,
+    {
+        let _ = self.factories.insert(Factory::<T>(Box::new(factory)));
+    }
+
+    /// Returns true if there is a factory registered for the type `T`.
+    #[inline]
+    pub fn is_registered<T: 'static>(&self) -> bool {
+        self.factories.contains::<Factory<T>>()
+    }
+
+    /// Sets the value for the type `T` directly, without any factory.
+    /// If the container already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        let previous = self.values.remove::<T>();
+        let _ = self.values.insert(value);
+        previous
+    }
+
+    /// Returns a reference to the value of type `T`, if it has already been built or inserted,
+    /// without building it.
+    #[inline]
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values.get::<T>()
+    }
+
+    /// Returns a reference to the value of type `T`, building it with its registered factory
+    /// first if it hasn’t been built yet.
+    ///
+    /// This fails if there’s no factory for `T`, if `T` turns out to depend on itself, or if
+    /// its factory fails, which includes when one of its dependencies can’t be resolved; the
+    /// error’s [`source`](Error::source) then gives the factory’s error, and so on down the
+    /// chain.
+    pub fn resolve<T: 'static>(&self) -> Result<&T, ResolveError> {
+        if let Some(value) = self.values.get::<T>() {
+            return Ok(value);
+        }
+        let type_name = core::any::type_name::<T>();
+        let factory = match self.factories.get::<Factory<T>>() {
+            Some(factory) => factory,
+            None => return Err(ResolveError::new(type_name, ErrorKind::Unregistered)),
+        };
+        let value = {
+            let mut resolving = self.resolving.borrow_mut();
+            if let Some(start) = resolving.iter().position(|&(id, _)| id == TypeId::of::<T>()) {
+                let mut cycle = resolving[start..].iter()
+                    .map(|&(_, name)| name)
+                    .collect::<Vec<_>>();
+                cycle.push(type_name);
+                return Err(ResolveError::new(type_name, ErrorKind::Cycle(cycle)));
+            }
+            resolving.push((TypeId::of::<T>(), type_name));
+            drop(resolving);
+            let _resolving = Resolving(&self.resolving);
+            (factory.0)(self).map_err(|error| ResolveError {
+                type_name,
+                kind: ErrorKind::Failed,
+                source: Some(error),
+            })?
+        };
+        Ok(self.values.insert(value))
+    }
+}
+
+/// What went wrong in a [`ResolveError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// No factory was registered for the type.
+    Unregistered,
+    /// The type depends on itself; this lists the types in the cycle, starting and ending with
+    /// the same one.
+    Cycle(Vec<&'static str>),
+    /// The type’s factory failed; the error’s [`source`](Error::source) says why.
+    Failed,
+}
+
+/// An error indicating that a value couldn’t be resolved; see [`Container::resolve`].
+#[derive(Debug)]
+pub struct ResolveError {
+    type_name: &'static str,
+    kind: ErrorKind,
+    source: Option<BoxError>,
+}
+
+impl ResolveError {
+    #[inline]
+    fn new(type_name: &'static str, kind: ErrorKind) -> ResolveError {
+        ResolveError {
+            type_name,
+            kind,
+            source: None,
+        }
+    }
+
+    /// Returns the name of the type that couldn’t be resolved, as given by
+    /// `core::any::type_name`.
+    #[inline]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns what went wrong.
+    #[inline]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Returns the innermost error in the chain, the cause of all the others.
+    pub fn root_cause(&self) -> &(dyn Error + 'static) {
+        let mut error: &(dyn Error + 'static) = self;
+        while let Some(source) = error.source() {
+            error = source;
+        }
+        error
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Unregistered => write!(f, "no factory registered for `{}`", self.type_name),
+            ErrorKind::Cycle(cycle) => {
+                write!(f, "dependency cycle resolving `{}`: ", self.type_name)?;
+                for (i, name) in cycle.iter().enumerate() {
+                    let arrow = if i == 0 { "" } else { " -> " };
+                    write!(f, "{}`{}`", arrow, name)?;
+                }
+                Ok(())
+            },
+            ErrorKind::Failed => write!(f, "failed to build `{}`", self.type_name),
+        }
+    }
+}
+
+impl Error for ResolveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+    #[derive(Clone, Debug, PartialEq)] struct C(i32);
+
+    #[test]
+    fn test_resolve() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let built = Rc::new(Cell::new(0));
+        let mut container = Container::new();
+        assert_eq!(container.insert(A(1)), None);
+        let counter = built.clone();
+        container.register(move |c| {
+            counter.set(counter.get() + 1);
+            Ok(B(c.resolve::<A>()?.0 + 1))
+        });
+        assert!(container.is_registered::<B>() && !container.is_registered::<A>());
+        assert_eq!(container.get::<B>(), None);
+        let b = container.resolve::<B>().unwrap();
+        assert!(core::ptr::eq(b, container.resolve::<B>().unwrap()));
+        assert_eq!((b, built.get()), (&B(2), 1));
+        assert_eq!(format!("{:?}", container), "Container { factories: 1, values: 2, .. }");
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let mut container = Container::new();
+        container.register(|c| Ok(A(c.resolve::<B>()?.0)));
+        container.register(|c| Ok(B(c.resolve::<C>()?.0)));
+        let error = container.resolve::<A>().err().unwrap();
+        let names = [
+            core::any::type_name::<A>(),
+            core::any::type_name::<B>(),
+            core::any::type_name::<C>(),
+        ];
+        assert_eq!((error.type_name(), error.kind()), (names[0], &ErrorKind::Failed));
+        let unregistered = error.root_cause().downcast_ref::<ResolveError>().unwrap();
+        assert_eq!(unregistered.kind(), &ErrorKind::Unregistered);
+        assert_eq!(unregistered.to_string(), format!("no factory registered for `{}`", names[2]));
+
+        container.register(|c| Ok(C(c.resolve::<A>()?.0)));
+        let error = container.resolve::<A>().err().unwrap();
+        let cycle = error.root_cause().downcast_ref::<ResolveError>().unwrap();
+        assert_eq!(cycle.kind(), &ErrorKind::Cycle(vec![names[0], names[1], names[2], names[0]]));
+        assert!(container.resolving.borrow().is_empty());
+
+        container.register::<C, _>(|_| Err("out of Cs".into()));
+        let error = container.resolve::<B>().err().unwrap();
+        assert_eq!(error.root_cause().to_string(), "out of Cs");
+        assert!(container.get::<B>().is_none());
+    }
+}
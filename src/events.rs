@@ -0,0 +1,190 @@
+//! A map that reports its changes to subscribers.
+
+use core::any::{Any, TypeId};
+use core::ops::Deref;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::any::{Downcast, IntoBox};
+use crate::Map;
+
+/// A change to an [`ObservedMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MapEvent {
+    /// The type of the value that changed.
+    pub type_id: TypeId,
+    /// What happened to it.
+    pub kind: MapEventKind,
+}
+
+/// What happened in a [`MapEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MapEventKind {
+    /// A value of a type not in the map was added.
+    Inserted,
+    /// A value was removed.
+    Removed,
+    /// A value was replaced by another of the same type.
+    Replaced,
+}
+
+/// A [`Map`] that sends a [`MapEvent`] to each of its subscribers whenever a value is inserted,
+/// replaced or removed, so tooling can follow along without polling.
+///
+/// Changes made in place, through a mutable reference to a value, aren’t reported. Everything
+/// that can’t change the map is available through `Deref` to the inner `Map`.
+///
+/// ```rust
+/// use anymap::events::{MapEventKind, ObservedMap};
+///
+/// let mut data = ObservedMap::<dyn std::any::Any>::new();
+/// let events = data.subscribe();
+/// data.insert(1i32);
+/// data.insert(2i32);
+/// data.remove::<i32>();
+/// let kinds = events.try_iter().map(|event| event.kind).collect::<Vec<_>>();
+/// assert_eq!(kinds, [MapEventKind::Inserted, MapEventKind::Replaced, MapEventKind::Removed]);
+/// ```
+#[derive(Debug)]
+pub struct ObservedMap<A: ?Sized + Downcast = dyn Any> {
+    map: Map<A>,
+    subscribers: Vec<Sender<MapEvent>>,
+}
+
+impl<A: ?Sized + Downcast> Default for ObservedMap<A> {
+    #[inline]
+    fn default() -> ObservedMap<A> {
+        ObservedMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for ObservedMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> ObservedMap<A> {
+        ObservedMap {
+            map,
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> ObservedMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> ObservedMap<A> {
+        ObservedMap::from(Map::new())
+    }
+
+    /// Returns a receiver of events for every change from now on. Dropping the receiver
+    /// unsubscribes.
+    #[inline]
+    pub fn subscribe(&mut self) -> Receiver<MapEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    fn emit(&mut self, type_id: TypeId, kind: MapEventKind) {
+        let event = MapEvent { type_id, kind };
+        self.subscribers.retain(|subscriber| subscriber.send(event).is_ok());
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+        let previous = self.map.insert(value);
+        let kind = match previous {
+            Some(_) => MapEventKind::Replaced,
+            None => MapEventKind::Inserted,
+        };
+        self.emit(TypeId::of::<T>(), kind);
+        previous
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// if it exists. Changes made through it aren’t reported.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+        self.map.get_mut()
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// inserting the result of `f` first if there was none.
+    #[inline]
+    pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&mut self, f: F) -> &mut T {
+        if !self.map.contains::<T>() {
+            let value = f();
+            self.emit(TypeId::of::<T>(), MapEventKind::Inserted);
+            return self.map.get_or_insert(value);
+        }
+        self.map.get_or_insert_with(f)
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+        let removed = self.map.remove::<T>();
+        if removed.is_some() {
+            self.emit(TypeId::of::<T>(), MapEventKind::Removed);
+        }
+        removed
+    }
+
+    /// Removes all items from the collection, reporting each removal. Keeps the allocated memory
+    /// for reuse.
+    pub fn clear(&mut self) {
+        let removed = self.map.as_raw().keys().copied().collect::<Vec<_>>();
+        self.map.clear();
+        for type_id in removed {
+            self.emit(type_id, MapEventKind::Removed);
+        }
+    }
+
+    /// Converts this into the inner `Map`, dropping the subscriptions.
+    #[inline]
+    pub fn into_inner(self) -> Map<A> {
+        self.map
+    }
+}
+
+impl<A: ?Sized + Downcast> Deref for ObservedMap<A> {
+    type Target = Map<A>;
+
+    #[inline]
+    fn deref(&self) -> &Map<A> {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_observed_map() {
+        let mut map = ObservedMap::<dyn Any>::new();
+        let _ = map.insert(A(1));
+        let events = map.subscribe();
+        let dropped = map.subscribe();
+        drop(dropped);
+        let _ = map.get_or_insert_with(|| B(2));
+        let _ = map.get_or_insert_with(|| B(3));
+        map.get_mut::<B>().unwrap().0 = 4;
+        assert_eq!(map.remove::<B>(), Some(B(4)));
+        assert_eq!(map.remove::<B>(), None);
+        map.clear();
+        let event = |type_id, kind| MapEvent { type_id, kind };
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), [
+            event(TypeId::of::<B>(), MapEventKind::Inserted),
+            event(TypeId::of::<B>(), MapEventKind::Removed),
+            event(TypeId::of::<A>(), MapEventKind::Removed),
+        ]);
+        assert_eq!(map.subscribers.len(), 1);
+        assert!(map.into_inner().is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! An append-only map that can grow through a shared reference.
+
+use core::any::{Any, TypeId};
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hash::BuildHasherDefault;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use std::collections::HashMap;
+
+use crate::any::{Downcast, IntoBox};
+use crate::{Map, TypeIdHasher};
+
+/// A collection containing zero or one values for any given type, which can be added to through
+/// `&self`, handing out references that live as long as the borrow of the map.
+///
+/// This is [`Map`] made append-only, after the manner of the `elsa` crate: values are boxed, so
+/// they don’t move when the map grows, and through `&self` they can be neither replaced nor
+/// removed, so a `&T` from [`get`](FrozenAnyMap::get) or [`insert`](FrozenAnyMap::insert) stays
+/// valid while more types are added. That suits interners and caches, which want to return
+/// borrows while still growing. With `&mut self`, it can be changed freely again.
+///
+/// It isn’t `Sync`; for that, see [`OnceAnyMap`](crate::sync::OnceAnyMap). It converts to and
+/// from a `Map` with `From` and [`into_inner`](FrozenAnyMap::into_inner), for other changes.
+///
+/// ```rust
+/// use anymap::frozen::FrozenAnyMap;
+///
+/// let cache = FrozenAnyMap::<dyn core::any::Any>::new();
+/// let name = cache.insert(String::from("anymap"));
+/// let number = cache.get_or_insert_with(|| 42i32);
+/// assert_eq!((name.as_str(), *number), ("anymap", 42));
+/// assert_eq!(cache.len(), 2);
+/// ```
+pub struct FrozenAnyMap<A: ?Sized + Downcast = dyn Any> {
+    // The values are owned, as by `Box<A>`, but kept as raw pointers: moving a `Box` around, as
+    // the table does when it grows, would assert unique access to its contents, which would
+    // invalidate the references handed out. A raw pointer asserts nothing.
+    raw: UnsafeCell<HashMap<TypeId, NonNull<A>, BuildHasherDefault<TypeIdHasher>>>,
+    _marker: PhantomData<Box<A>>,
+}
+
+// SAFETY: it owns its values as a `Map<A>` would, so it may be sent where a `Box<A>` may. It
+// isn’t `Sync`, since it changes through `&self`.
+unsafe impl<A: ?Sized + Downcast + Send> Send for FrozenAnyMap<A> { }
+
+impl<A: ?Sized + Downcast> Drop for FrozenAnyMap<A> {
+    fn drop(&mut self) {
+        for (_, value) in self.raw.get_mut().drain() {
+            // SAFETY: each pointer came from `Box::leak`, and is dropped only here.
+            drop(unsafe { Box::from_raw(value.as_ptr()) });
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> Default for FrozenAnyMap<A> {
+    #[inline]
+    fn default() -> FrozenAnyMap<A> {
+        FrozenAnyMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for FrozenAnyMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> FrozenAnyMap<A> {
+        let raw = map.into_raw().into_iter()
+            .map(|(type_id, value)| (type_id, NonNull::from(Box::leak(value))))
+            .collect();
+        FrozenAnyMap {
+            raw: UnsafeCell::new(raw),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Not showing the values, since their `Debug` could reach this map and insert into it mid-walk.
+impl<A: ?Sized + Downcast> fmt::Debug for FrozenAnyMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrozenAnyMap").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> FrozenAnyMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> FrozenAnyMap<A> {
+        FrozenAnyMap::from(Map::new())
+    }
+
+    #[inline]
+    fn raw(&self) -> &HashMap<TypeId, NonNull<A>, BuildHasherDefault<TypeIdHasher>> {
+        // SAFETY: the table is only mutated through `&self` inside `insert_new`, which hands out
+        // no references into it and calls no user code while it holds its `&mut`.
+        unsafe { &*self.raw.get() }
+    }
+
+    /// Inserts `value`, which the caller has checked is not yet present, returning a reference to
+    /// it where it now lives.
+    #[inline]
+    fn insert_new<T: IntoBox<A>>(&self, value: T) -> &T {
+        let value = NonNull::from(Box::leak(value.into_box()));
+        // SAFETY: see `raw`. The `&mut` covers just the table, not the values, so references to
+        // them are unaffected by the table growing.
+        let _ = unsafe { &mut *self.raw.get() }.insert(TypeId::of::<T>(), value);
+        // SAFETY: the value is a `T`, and lives until it’s removed through `&mut self`.
+        unsafe { &*(value.as_ptr() as *const T) }
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw().len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw().is_empty()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.raw().contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        self.raw().get(&TypeId::of::<T>())
+            // SAFETY: the value stored under `T`’s `TypeId` is a `T`.
+            .map(|value| unsafe { &*(value.as_ptr() as *const T) })
+    }
+
+    /// Stores `value` for the type `T` if the collection has no value of that type yet, and
+    /// returns a reference to the value stored, which is the existing one if there was one.
+    ///
+    /// Unlike [`Map::insert`], it never replaces a value, since references to it may be out there.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&self, value: T) -> &T {
+        match self.get::<T>() {
+            Some(existing) => existing,
+            None => self.insert_new(value),
+        }
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, inserting the
+    /// result of `f` first if there was none.
+    ///
+    /// `f` may itself use the map; if it stores a `T`, that one is kept and `f`’s result dropped.
+    #[inline]
+    pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&self, f: F) -> &T {
+        match self.get::<T>() {
+            Some(existing) => existing,
+            None => self.insert(f()),
+        }
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// if it exists.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+        self.raw.get_mut().get_mut(&TypeId::of::<T>())
+            // SAFETY: as for `get`, and `&mut self` means no other references are out there.
+            .map(|value| unsafe { &mut *(value.as_ptr() as *mut T) })
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+        self.raw.get_mut().remove(&TypeId::of::<T>())
+            // SAFETY: as for `get_mut`; the pointer came from `Box::leak`, and is now gone.
+            .map(|value| *unsafe { Box::from_raw(value.as_ptr() as *mut T) })
+    }
+
+    /// Converts this into a `Map`, for any other changes.
+    #[inline]
+    pub fn into_inner(mut self) -> Map<A> {
+        let raw = self.raw.get_mut().drain()
+            // SAFETY: as for `Drop`, which now finds nothing left.
+            .map(|(type_id, value)| (type_id, unsafe { Box::from_raw(value.as_ptr()) }))
+            .collect();
+        // SAFETY: each value is still under its own type’s `TypeId`.
+        unsafe { Map::from_raw(raw) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_frozen_any_map() {
+        let map = FrozenAnyMap::<dyn Any>::new();
+        let a = map.insert(A(1));
+        assert_eq!(map.insert(A(2)), &A(1));
+        let b = map.get_or_insert_with(|| {
+            // Growing the map while `a` is borrowed mustn’t move it.
+            let _ = (map.insert(0u8), map.insert(0u16), map.insert(0u32), map.insert(0u64));
+            let _ = (map.insert(0i8), map.insert(0i16), map.insert(0i32), map.insert(0i64));
+            let _ = (map.insert(0f32), map.insert(0f64), map.insert('0'), map.insert(false));
+            let _ = map.insert(B(3));
+            B(4)
+        });
+        assert_eq!((a, b), (&A(1), &B(3)));
+        assert!(map.contains::<u32>());
+        assert_eq!(format!("{:?}", map), "FrozenAnyMap { len: 14, .. }");
+        let mut map = map;
+        map.get_mut::<A>().unwrap().0 = 5;
+        assert_eq!(map.remove::<A>(), Some(A(5)));
+        assert_eq!(map.into_inner().len(), 13);
+    }
+}
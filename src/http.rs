@@ -0,0 +1,236 @@
+//! Moving values between maps and the [`Extensions`] of [http](https://docs.rs/http) requests
+//! and responses.
+//!
+//! `Extensions` is a map of types much like `Map<dyn Any + Send + Sync>`, but it can’t be
+//! iterated: there’s no finding out what’s in one but by asking for each type in turn. So moving
+//! its values into a map, or a map’s values into it, takes a list of the types to move, an
+//! [`ExtensionTypes`]. Types not on the list stay where they are.
+//!
+//! For the same reason there’s no `From` conversion either way. Not into a map, since an
+//! `Extensions` can’t be emptied without the list; and not out of one, since
+//! `Extensions::insert` keys a value by its static type, which a map’s `Box<dyn Any + Send +
+//! Sync>` doesn’t have: it would go in under the type of the box, where no one would find it.
+//!
+//! To look a value up in both without moving anything, there’s [`ExtensionsView`].
+//!
+//! ```rust
+//! use anymap::Map;
+//! use anymap::http::{ExtensionTypes, ExtensionsView};
+//! use core::any::Any;
+//!
+//! #[derive(Clone, Debug, PartialEq)]
+//! struct UserId(u64);
+//!
+//! let mut types = ExtensionTypes::new();
+//! types.register::<UserId>();
+//!
+//! let mut request = http::Request::new(());
+//! request.extensions_mut().insert(UserId(7));
+//! request.extensions_mut().insert(5u8);
+//!
+//! let mut map: Map<dyn Any + Send + Sync> =
+//!     Map::from_extensions(request.extensions_mut(), &types);
+//! assert_eq!(map.get::<UserId>(), Some(&UserId(7)));
+//! assert_eq!(request.extensions().get::<u8>(), Some(&5));
+//!
+//! map.insert(String::from("local"));
+//! let view = ExtensionsView::new(&map, request.extensions());
+//! assert_eq!(view.get::<String>().map(|s| &**s), Some("local"));
+//! assert_eq!(view.get::<u8>(), Some(&5));
+//!
+//! map.move_to_extensions(request.extensions_mut(), &types);
+//! assert_eq!(request.extensions().get::<UserId>(), Some(&UserId(7)));
+//! assert!(!map.contains::<UserId>());
+//! ```
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use std::collections::BTreeMap;
+
+use ::http::Extensions;
+
+use crate::Map;
+
+/// The map type that values are moved to and from `Extensions`, which holds the same values.
+type SendSyncMap = Map<dyn Any + Send + Sync>;
+
+/// Functions moving a value of one type each way.
+#[derive(Clone, Copy)]
+struct Transfer {
+    type_name: fn() -> &'static str,
+    to_map: fn(&mut Extensions, &mut SendSyncMap),
+    to_extensions: fn(&mut SendSyncMap, &mut Extensions),
+}
+
+/// The types to move between maps and [`Extensions`]; see the [module](self) docs.
+#[derive(Clone, Default)]
+pub struct ExtensionTypes {
+    transfers: BTreeMap<TypeId, Transfer>,
+}
+
+impl ExtensionTypes {
+    /// Create an empty list of types.
+    #[inline]
+    pub fn new() -> ExtensionTypes {
+        ExtensionTypes::default()
+    }
+
+    /// Adds the type `T` to the list, returning false if it was already there.
+    pub fn register<T: Clone + Send + Sync + 'static>(&mut self) -> bool {
+        self.transfers.insert(TypeId::of::<T>(), Transfer {
+            type_name: core::any::type_name::<T>,
+            to_map: |extensions, map| {
+                if let Some(value) = extensions.remove::<T>() {
+                    let _ = map.insert(value);
+                }
+            },
+            to_extensions: |map, extensions| {
+                if let Some(value) = map.remove::<T>() {
+                    let _ = extensions.insert(value);
+                }
+            },
+        }).is_none()
+    }
+
+    /// Returns true if the type `T` is on the list.
+    #[inline]
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.transfers.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the number of types on the list.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Returns true if the list is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+}
+
+impl fmt::Debug for ExtensionTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.transfers.values().map(|t| (t.type_name)())).finish()
+    }
+}
+
+impl Map<dyn Any + Send + Sync> {
+    /// Creates a collection of the values of the types in `types` that `extensions` has,
+    /// removing them from `extensions`.
+    #[inline]
+    pub fn from_extensions(
+        extensions: &mut Extensions,
+        types: &ExtensionTypes,
+    ) -> Map<dyn Any + Send + Sync> {
+        let mut map = Map::new();
+        map.take_from_extensions(extensions, types);
+        map
+    }
+
+    /// Moves the values of the types in `types` that `extensions` has into the collection,
+    /// replacing any it already has of those types.
+    pub fn take_from_extensions(&mut self, extensions: &mut Extensions, types: &ExtensionTypes) {
+        for transfer in types.transfers.values() {
+            (transfer.to_map)(extensions, self);
+        }
+    }
+
+    /// Moves the values of the types in `types` from the collection into `extensions`,
+    /// replacing any it already has of those types.
+    pub fn move_to_extensions(&mut self, extensions: &mut Extensions, types: &ExtensionTypes) {
+        for transfer in types.transfers.values() {
+            (transfer.to_extensions)(self, extensions);
+        }
+    }
+}
+
+/// A map and an [`Extensions`] borrowed together, for looking values up in one and then the
+/// other without moving them.
+#[derive(Clone, Copy, Debug)]
+pub struct ExtensionsView<'a> {
+    map: &'a Map<dyn Any + Send + Sync>,
+    extensions: &'a Extensions,
+}
+
+impl<'a> ExtensionsView<'a> {
+    /// Borrows `map` and `extensions` together.
+    #[inline]
+    pub fn new(
+        map: &'a Map<dyn Any + Send + Sync>,
+        extensions: &'a Extensions,
+    ) -> ExtensionsView<'a> {
+        ExtensionsView { map, extensions }
+    }
+
+    /// Returns a reference to the map’s value of type `T` if it has one, or else to the
+    /// extensions’, if they have one.
+    #[inline]
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&'a T> {
+        self.map.get::<T>().or_else(|| self.extensions.get::<T>())
+    }
+
+    /// Returns true if either the map or the extensions have a value of type `T`.
+    #[inline]
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// Returns the map.
+    #[inline]
+    pub fn map(&self) -> &'a Map<dyn Any + Send + Sync> {
+        self.map
+    }
+
+    /// Returns the extensions.
+    #[inline]
+    pub fn extensions(&self) -> &'a Extensions {
+        self.extensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_extensions() {
+        let mut types = ExtensionTypes::new();
+        assert!(types.register::<A>());
+        assert!(types.register::<B>());
+        assert!(!types.register::<A>());
+        assert!(types.contains::<B>() && !types.contains::<u8>());
+        assert_eq!(types.len(), 2);
+
+        let mut extensions = Extensions::new();
+        let _ = extensions.insert(A(1));
+        let _ = extensions.insert(1u8);
+        let mut map = Map::from_extensions(&mut extensions, &types);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(), Some(&A(1)));
+        assert_eq!(extensions.len(), 1);
+
+        let _ = map.insert(B(2));
+        let _ = map.insert(2u8);
+        let _ = extensions.insert(B(3));
+        {
+            let view = ExtensionsView::new(&map, &extensions);
+            assert_eq!(view.get(), Some(&B(2)));
+            assert_eq!(view.get(), Some(&2u8));
+            assert!(view.contains::<A>() && !view.contains::<u16>());
+        }
+        map.take_from_extensions(&mut extensions, &types);
+        assert_eq!(map.get(), Some(&B(3)));
+
+        map.move_to_extensions(&mut extensions, &types);
+        assert_eq!(map.len(), 1);
+        assert_eq!(extensions.get(), Some(&A(1)));
+        assert_eq!(extensions.get(), Some(&B(3)));
+        assert_eq!(extensions.get(), Some(&1u8));
+    }
+}
@@ -0,0 +1,249 @@
+//! Registering types for maps once, anywhere in the program, with
+//! [inventory](https://docs.rs/inventory).
+//!
+//! Building a [`Registry`] by hand means knowing every type that might be in a map, which an
+//! application made of many crates seldom does. Instead, each crate can declare its own types
+//! where it defines them, with [`register_anymap_type!`](crate::register_anymap_type), and
+//! everything declared across the whole program is collected at startup, to be picked up by
+//! [`Registry::from_inventory`] (stable names and deserialization),
+//! [`Map::register_inventory_casts`](crate::Map::register_inventory_casts) (trait casters) and
+//! [`type_name_of`] (names for debugging).
+//!
+//! Registrations are for maps of a particular `A`, `dyn SerializeAny` unless another is given.
+//! They can be collected for `dyn SerializeAny` and `dyn Any`, each alone, `+ Send` or
+//! `+ Send + Sync`. Trait casters can only be registered for the `dyn Any` ones, since a map of
+//! `dyn SerializeAny` can’t hold the [`TraitCasts`](crate::TraitCasts) they’re recorded in.
+//!
+//! ```rust
+//! use anymap::Map;
+//! use anymap::serde::{Registry, SerializeAny};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+//! struct Score(u32);
+//! anymap::register_anymap_type!(Score, "score");
+//!
+//! trait Describe {
+//!     fn describe(&self) -> String;
+//! }
+//! anymap::impl_cast!(Describe);
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Level(u8);
+//! impl Describe for Level {
+//!     fn describe(&self) -> String { format!("level {}", self.0) }
+//! }
+//! anymap::register_anymap_type!(Level as dyn core::any::Any, "level", casts = [dyn Describe]);
+//!
+//! fn main() {
+//!     let registry = Registry::from_inventory();
+//!     let mut map: Map<dyn SerializeAny> = Map::new();
+//!     map.insert(Score(3));
+//!     let json = serde_json::to_string(&map.serialize_with(&registry)).unwrap();
+//!     assert_eq!(json, r#"{"score":3}"#);
+//!
+//!     let mut map: Map = Map::new();
+//!     map.register_inventory_casts();
+//!     map.insert(Level(2));
+//!     let described = map.iter_as::<dyn Describe>().map(|d| d.describe()).collect::<Vec<_>>();
+//!     assert_eq!(described, ["level 2"]);
+//!
+//!     let type_id = core::any::TypeId::of::<Score>();
+//!     let name = anymap::inventory::type_name_of::<dyn SerializeAny>(&type_id);
+//!     assert!(name.unwrap().ends_with("Score"));
+//! }
+//! ```
+
+use core::any::{Any, TypeId};
+use core::fmt;
+
+use ::inventory::Collect;
+
+use crate::any::Downcast;
+use crate::serde::{Registry, SerializeAny};
+use crate::Map;
+
+/// A type declared for maps of `A` by [`register_anymap_type!`](crate::register_anymap_type).
+pub struct Registration<A: ?Sized + Downcast + 'static> {
+    name: &'static str,
+    type_id: fn() -> TypeId,
+    type_name: fn() -> &'static str,
+    register: fn(&mut Registry<A>),
+    register_casts: fn(&mut Map<A>),
+}
+
+impl<A: ?Sized + Downcast + 'static> Registration<A> {
+    #[doc(hidden)]
+    pub const fn __new(
+        name: &'static str,
+        type_id: fn() -> TypeId,
+        type_name: fn() -> &'static str,
+        register: fn(&mut Registry<A>),
+        register_casts: fn(&mut Map<A>),
+    ) -> Registration<A> {
+        Registration { name, type_id, type_name, register, register_casts }
+    }
+
+    /// Returns the type’s stable name.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the type’s `TypeId`.
+    #[inline]
+    pub fn type_id(&self) -> TypeId {
+        (self.type_id)()
+    }
+
+    /// Returns the type’s name, as given by `core::any::type_name`.
+    #[inline]
+    pub fn type_name(&self) -> &'static str {
+        (self.type_name)()
+    }
+}
+
+impl<A: ?Sized + Downcast + 'static> fmt::Debug for Registration<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Registration")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name())
+            .finish()
+    }
+}
+
+::inventory::collect!(Registration<dyn SerializeAny>);
+::inventory::collect!(Registration<dyn SerializeAny + Send>);
+::inventory::collect!(Registration<dyn SerializeAny + Send + Sync>);
+::inventory::collect!(Registration<dyn Any>);
+::inventory::collect!(Registration<dyn Any + Send>);
+::inventory::collect!(Registration<dyn Any + Send + Sync>);
+
+/// Returns an iterator over all the types registered for maps of `A`, across the program, in
+/// arbitrary order.
+#[inline]
+pub fn registrations<A>() -> impl Iterator<Item = &'static Registration<A>>
+where
+    A: ?Sized + Downcast + 'static,
+    Registration<A>: Collect,
+{
+    ::inventory::iter::<Registration<A>>.into_iter()
+}
+
+/// Returns the name, as given by `core::any::type_name`, of the type with the given `TypeId`,
+/// if it was registered for maps of `A`, for showing a type that’s only known by its `TypeId`.
+pub fn type_name_of<A>(type_id: &TypeId) -> Option<&'static str>
+where
+    A: ?Sized + Downcast + 'static,
+    Registration<A>: Collect,
+{
+    registrations::<A>()
+        .find(|&registration| Registration::type_id(registration) == *type_id)
+        .map(Registration::type_name)
+}
+
+impl<A: ?Sized + Downcast + 'static> Registry<A> {
+    /// Creates a registry of all the types registered for maps of `A` with
+    /// [`register_anymap_type!`](crate::register_anymap_type), across the program.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same stable name was registered for two types.
+    pub fn from_inventory() -> Registry<A>
+    where
+        Registration<A>: Collect,
+    {
+        let mut registry = Registry::new();
+        for registration in registrations::<A>() {
+            (registration.register)(&mut registry);
+        }
+        registry
+    }
+}
+
+impl<A: ?Sized + Downcast + 'static> Map<A> {
+    /// Records all the trait casters registered for maps of `A` with
+    /// [`register_anymap_type!`](crate::register_anymap_type), across the program, as
+    /// [`register_cast`](Map::register_cast) does.
+    pub fn register_inventory_casts(&mut self)
+    where
+        Registration<A>: Collect,
+    {
+        for registration in registrations::<A>() {
+            (registration.register_casts)(self);
+        }
+    }
+}
+
+/// Registers a type for maps, to be collected at startup wherever in the program it’s declared;
+/// see the [`inventory`](crate::inventory) module.
+///
+/// `register_anymap_type!(Score, "score")` gives `Score` the stable name `"score"`, for
+/// serializing and deserializing maps of `dyn SerializeAny`. To register it for maps of another
+/// `A`, say `register_anymap_type!(Score as dyn Any + Send, "score")`; and to register trait
+/// casters for it too, add `casts = [dyn Describe, dyn Display]`. The type must implement
+/// `DeserializeOwned`.
+///
+/// Like `inventory::submit!`, it must be used outside of any function.
+#[macro_export]
+macro_rules! register_anymap_type {
+    ($t:ty as $a:ty, $name:expr $(, casts = [$($cast:ty),* $(,)?])? $(,)?) => {
+        $crate::__inventory::submit! {
+            $crate::inventory::Registration::<$a>::__new(
+                $name,
+                ::core::any::TypeId::of::<$t>,
+                ::core::any::type_name::<$t>,
+                |registry| registry.register_deserialize::<$t>($name),
+                |_map| { $($(let _ = _map.register_cast::<$cast, $t>();)*)? },
+            )
+        }
+    };
+    ($t:ty, $name:expr $(, casts = [$($cast:ty),* $(,)?])? $(,)?) => {
+        $crate::register_anymap_type!(
+            $t as dyn $crate::serde::SerializeAny,
+            $name
+            $(, casts = [$($cast),*])?
+        );
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, ::serde::Deserialize)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq, ::serde::Deserialize)] struct B(i32);
+
+    trait Value {
+        fn value(&self) -> i32;
+    }
+    crate::impl_cast!(Value);
+    impl Value for A { fn value(&self) -> i32 { self.0 } }
+    impl Value for B { fn value(&self) -> i32 { self.0 } }
+
+    crate::register_anymap_type!(A as dyn Any + Send, "a", casts = [dyn Value, dyn Value + Send]);
+    crate::register_anymap_type!(B as dyn Any + Send, "b");
+
+    #[test]
+    fn test_inventory() {
+        let mut names = registrations::<dyn Any + Send>()
+            .map(Registration::name)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(registrations::<dyn Any>().count(), 0);
+        let name = type_name_of::<dyn Any + Send>(&TypeId::of::<B>());
+        assert_eq!(name, Some(core::any::type_name::<B>()));
+        assert_eq!(type_name_of::<dyn Any>(&TypeId::of::<B>()), None);
+
+        let registry = Registry::<dyn Any + Send>::from_inventory();
+        assert_eq!(registry.type_id_of("a"), Some(TypeId::of::<A>()));
+        assert!(registry.can_deserialize(&TypeId::of::<B>()));
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"a":1,"b":2}"#);
+        let mut map = Map::<dyn Any + Send>::deserialize(&mut deserializer, &registry).unwrap();
+        assert_eq!(map.get(), Some(&B(2)));
+
+        map.register_inventory_casts();
+        assert_eq!(map.iter_as::<dyn Value>().map(Value::value).collect::<Vec<_>>(), [1]);
+        assert_eq!(map.iter_as::<dyn Value + Send>().count(), 1);
+    }
+}
@@ -0,0 +1,192 @@
+//! Marker types keying values of some other type, as in the old `typemap` crate, or telling
+//! apart values of the same type.
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+/// A marker type that keys a value of type `Value` in a map, rather than the value keying
+/// itself by its own type; see [`Map::get_key`](crate::Map::get_key).
+///
+/// This lets several keys have values of the same type, without a newtype for each:
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use anymap::{AnyMap, Key};
+///
+/// struct Width;
+/// impl Key for Width { type Value = u32; }
+/// struct Height;
+/// impl Key for Height { type Value = u32; }
+///
+/// let mut map = AnyMap::new();
+/// map.insert_key::<Width>(800);
+/// map.insert_key::<Height>(600);
+/// assert_eq!(map.get_key::<Width>(), Some(&800));
+/// assert_eq!(map.get_key::<Height>(), Some(&600));
+/// assert_eq!(map.get::<u32>(), None);
+/// ```
+pub trait Key: 'static {
+    /// The type of the value the key is for.
+    type Value: 'static;
+}
+
+/// The value for a [`Key`] `K`, as it’s stored in a map: under the type `KeyValue<K>`, one for
+/// each key.
+///
+/// It implements `Clone`, `Debug`, `PartialEq`, `Eq`, `Hash` and `Default` when `K::Value` does,
+/// whether `K` does or not, so it can go in a `Map<dyn CloneAny>` and the like.
+#[repr(transparent)]
+pub struct KeyValue<K: Key>(pub K::Value);
+
+impl<K: Key> Deref for KeyValue<K> {
+    type Target = K::Value;
+
+    #[inline]
+    fn deref(&self) -> &K::Value {
+        &self.0
+    }
+}
+
+impl<K: Key> DerefMut for KeyValue<K> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut K::Value {
+        &mut self.0
+    }
+}
+
+impl<K: Key> Clone for KeyValue<K> where K::Value: Clone {
+    #[inline]
+    fn clone(&self) -> KeyValue<K> {
+        KeyValue(self.0.clone())
+    }
+}
+
+impl<K: Key> fmt::Debug for KeyValue<K> where K::Value: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("KeyValue").field(&self.0).finish()
+    }
+}
+
+impl<K: Key> PartialEq for KeyValue<K> where K::Value: PartialEq {
+    #[inline]
+    fn eq(&self, other: &KeyValue<K>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: Key> Eq for KeyValue<K> where K::Value: Eq { }
+
+impl<K: Key> Hash for KeyValue<K> where K::Value: Hash {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<K: Key> Default for KeyValue<K> where K::Value: Default {
+    #[inline]
+    fn default() -> KeyValue<K> {
+        KeyValue(K::Value::default())
+    }
+}
+
+/// A value of type `T` told apart from other `T`s by the marker type `M`, so that a map can
+/// hold one of each: `Tagged<Pool, Primary>` and `Tagged<Pool, Replica>` are different types.
+/// See [`Map::get_tagged`](crate::Map::get_tagged).
+///
+/// Like [`KeyValue`], it implements `Clone`, `Debug` and so on when `T` does, whatever `M` is, and
+/// is `Send` and `Sync` when `T` is.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use anymap::{AnyMap, Tagged};
+///
+/// struct Pool { url: &'static str }
+/// enum Primary {}
+/// enum Replica {}
+///
+/// let mut map = AnyMap::new();
+/// map.insert_tagged::<Primary, _>(Pool { url: "db1" });
+/// map.insert(Tagged::<_, Replica>::new(Pool { url: "db2" }));
+/// assert_eq!(map.get_tagged::<Pool, Primary>().unwrap().url, "db1");
+/// assert_eq!(map.get::<Tagged<Pool, Replica>>().unwrap().url, "db2");
+/// ```
+#[repr(transparent)]
+pub struct Tagged<T, M: ?Sized>(pub T, PhantomData<fn() -> M>);
+
+impl<T, M: ?Sized> Tagged<T, M> {
+    /// Tags `value` with `M`.
+    #[inline]
+    pub const fn new(value: T) -> Tagged<T, M> {
+        Tagged(value, PhantomData)
+    }
+
+    /// Returns the value, untagged.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, M: ?Sized> Deref for Tagged<T, M> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T, M: ?Sized> DerefMut for Tagged<T, M> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T, M: ?Sized> From<T> for Tagged<T, M> {
+    #[inline]
+    fn from(value: T) -> Tagged<T, M> {
+        Tagged::new(value)
+    }
+}
+
+impl<T: Clone, M: ?Sized> Clone for Tagged<T, M> {
+    #[inline]
+    fn clone(&self) -> Tagged<T, M> {
+        Tagged::new(self.0.clone())
+    }
+}
+
+impl<T: Copy, M: ?Sized> Copy for Tagged<T, M> { }
+
+impl<T: fmt::Debug, M: ?Sized> fmt::Debug for Tagged<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Tagged").field(&self.0).finish()
+    }
+}
+
+impl<T: PartialEq, M: ?Sized> PartialEq for Tagged<T, M> {
+    #[inline]
+    fn eq(&self, other: &Tagged<T, M>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Eq, M: ?Sized> Eq for Tagged<T, M> { }
+
+impl<T: Hash, M: ?Sized> Hash for Tagged<T, M> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<T: Default, M: ?Sized> Default for Tagged<T, M> {
+    #[inline]
+    fn default() -> Tagged<T, M> {
+        Tagged::new(T::default())
+    }
+}
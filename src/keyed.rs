@@ -0,0 +1,209 @@
+//! A map of values keyed by their type and a runtime key together.
+
+use core::any::TypeId;
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasherDefault, Hash};
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::any::{Downcast, IntoBox};
+use crate::TypeIdHasher;
+
+/// A collection containing any number of values of each type, each under a key of type `K`:
+/// values are keyed by `(TypeId, K)`.
+///
+/// Where [`Map`](crate::Map) holds one value of each type, this holds one for each pair of type
+/// and key, so values of the same type can sit side by side under different keys, such as
+/// database pools by name, while those of different types never collide, even under the same
+/// key.
+///
+/// ```rust
+/// use anymap::keyed::KeyedMap;
+/// use core::any::Any;
+///
+/// struct Pool { url: &'static str }
+///
+/// let mut pools = KeyedMap::<dyn Any, &str>::new();
+/// pools.insert_keyed("primary", Pool { url: "db1" });
+/// pools.insert_keyed("replica", Pool { url: "db2" });
+/// pools.insert_keyed("primary", 5u32);
+/// assert_eq!(pools.get_keyed::<Pool, _>("replica").map(|pool| pool.url), Some("db2"));
+/// assert_eq!(pools.get_keyed::<u32, _>("primary"), Some(&5));
+/// assert_eq!(pools.len_of::<Pool>(), 2);
+/// ```
+pub struct KeyedMap<A: ?Sized + Downcast, K> {
+    raw: HashMap<TypeId, HashMap<K, Box<A>>, BuildHasherDefault<TypeIdHasher>>,
+}
+
+impl<A: ?Sized + Downcast, K> Default for KeyedMap<A, K> {
+    #[inline]
+    fn default() -> KeyedMap<A, K> {
+        KeyedMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast, K: Clone> Clone for KeyedMap<A, K> where Box<A>: Clone {
+    #[inline]
+    fn clone(&self) -> KeyedMap<A, K> {
+        KeyedMap { raw: self.raw.clone() }
+    }
+}
+
+impl<A: ?Sized + Downcast, K> fmt::Debug for KeyedMap<A, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyedMap").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast, K> KeyedMap<A, K> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> KeyedMap<A, K> {
+        KeyedMap { raw: HashMap::default() }
+    }
+
+    /// Returns the number of values in the collection, of all types.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw.values().map(HashMap::len).sum()
+    }
+
+    /// Returns true if there are no values in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Removes all values from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.raw.clear()
+    }
+
+    /// Returns the number of values of type `T` in the collection.
+    #[inline]
+    pub fn len_of<T: IntoBox<A>>(&self) -> usize {
+        self.raw.get(&TypeId::of::<T>()).map_or(0, HashMap::len)
+    }
+
+    /// Returns an iterator over the keys and values of type `T`, in arbitrary order.
+    #[inline]
+    pub fn iter_of<T: IntoBox<A>>(&self) -> impl Iterator<Item = (&K, &T)> {
+        self.raw.get(&TypeId::of::<T>()).into_iter().flatten()
+            .map(|(key, value)| (key, unsafe { value.downcast_ref_unchecked::<T>() }))
+    }
+
+    /// Removes all the values of type `T`, returning them with their keys in arbitrary order.
+    #[inline]
+    pub fn remove_all<T: IntoBox<A>>(&mut self) -> impl Iterator<Item = (K, T)> {
+        self.raw.remove(&TypeId::of::<T>()).into_iter().flatten()
+            .map(|(key, value)| (key, *unsafe { value.downcast_unchecked::<T>() }))
+    }
+}
+
+impl<A: ?Sized + Downcast, K: Hash + Eq> KeyedMap<A, K> {
+    /// Sets the value of type `T` under `key`. If the collection already had a value of type
+    /// `T` under `key`, that value is returned. Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert_keyed<T: IntoBox<A>>(&mut self, key: K, value: T) -> Option<T> {
+        self.raw.entry(TypeId::of::<T>()).or_default()
+            .insert(key, value.into_box())
+            .map(|any| *unsafe { any.downcast_unchecked::<T>() })
+    }
+
+    /// Returns a reference to the value of type `T` under `key`, if it exists.
+    #[inline]
+    pub fn get_keyed<T: IntoBox<A>, Q>(&self, key: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.raw.get(&TypeId::of::<T>())?.get(key)
+            .map(|any| unsafe { any.downcast_ref_unchecked::<T>() })
+    }
+
+    /// Returns a mutable reference to the value of type `T` under `key`, if it exists.
+    #[inline]
+    pub fn get_keyed_mut<T: IntoBox<A>, Q>(&mut self, key: &Q) -> Option<&mut T>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.raw.get_mut(&TypeId::of::<T>())?.get_mut(key)
+            .map(|any| unsafe { any.downcast_mut_unchecked::<T>() })
+    }
+
+    /// Returns true if the collection contains a value of type `T` under `key`.
+    #[inline]
+    pub fn contains_keyed<T: IntoBox<A>, Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.raw.get(&TypeId::of::<T>()).is_some_and(|values| values.contains_key(key))
+    }
+
+    /// Removes the value of type `T` under `key`, returning it if there was one or `None` if
+    /// there was not.
+    pub fn remove_keyed<T: IntoBox<A>, Q>(&mut self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let values = self.raw.get_mut(&TypeId::of::<T>())?;
+        let value = values.remove(key)?;
+        if values.is_empty() {
+            let _ = self.raw.remove(&TypeId::of::<T>());
+        }
+        Some(*unsafe { value.downcast_unchecked::<T>() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::any::Any;
+
+    use super::*;
+    use crate::CloneAny;
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, string::String, vec::Vec};
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_keyed_map() {
+        let mut map = KeyedMap::<dyn CloneAny, String>::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert_keyed("x".into(), A(1)), None);
+        assert_eq!(map.insert_keyed("y".into(), A(2)), None);
+        assert_eq!(map.insert_keyed("x".into(), B(3)), None);
+        assert_eq!(map.insert_keyed("x".into(), A(10)), Some(A(1)));
+        assert_eq!((map.len(), map.len_of::<A>(), map.len_of::<u8>()), (3, 2, 0));
+        assert_eq!(map.get_keyed::<A, _>("x"), Some(&A(10)));
+        assert_eq!(map.get_keyed::<B, _>("x"), Some(&B(3)));
+        assert_eq!(map.get_keyed::<B, _>("y"), None);
+        map.get_keyed_mut::<A, _>("y").unwrap().0 = 20;
+        let mut a = map.iter_of::<A>().map(|(k, v)| (k.as_str(), v.0)).collect::<Vec<_>>();
+        a.sort_unstable();
+        assert_eq!(a, [("x", 10), ("y", 20)]);
+
+        let clone = map.clone();
+        assert_eq!(map.remove_keyed::<B, _>("x"), Some(B(3)));
+        assert_eq!(map.remove_keyed::<B, _>("x"), None);
+        assert!(!map.contains_keyed::<B, _>("x") && clone.contains_keyed::<B, _>("x"));
+        assert_eq!(map.remove_all::<A>().count(), 2);
+        assert!(map.is_empty());
+        assert_eq!(format!("{:?}", clone), "KeyedMap { len: 3, .. }");
+
+        let mut map = KeyedMap::<dyn Any, u8>::default();
+        let _ = map.insert_keyed(1, A(1));
+        map.clear();
+        assert_eq!(map.get_keyed::<A, _>(&1), None);
+    }
+}
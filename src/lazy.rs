@@ -0,0 +1,106 @@
+//! A value made on first use, for storing in a map.
+
+use core::cell::{Cell, OnceCell};
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+
+/// A value that isn’t made until it’s first used, as stored by
+/// [`Map::insert_lazy`](crate::Map::insert_lazy).
+///
+/// This is `core::cell::LazyCell` with the function boxed, so that every `Lazy<T>` is the same
+/// type, whatever function makes it: a map can hold one for each `T`, under the type
+/// `Lazy<T>`, and [`Map::get_lazy`](crate::Map::get_lazy) makes the value the first time it’s
+/// asked for, even through a shared reference. The function must be `Send`, so that a `Lazy<T>`
+/// can go in a `Map<dyn Any + Send>` if `T` is `Send`; it can’t be `Sync`, nor `Clone`.
+///
+/// If the function panics, the `Lazy` is poisoned, and using it again panics too.
+///
+/// ```rust
+/// use anymap::Lazy;
+///
+/// let lazy = Lazy::new(|| 6 * 7);
+/// assert_eq!(Lazy::get(&lazy), None);
+/// assert_eq!(*lazy, 42);
+/// assert_eq!(Lazy::get(&lazy), Some(&42));
+/// ```
+pub struct Lazy<T> {
+    value: OnceCell<T>,
+    init: Cell<Option<Box<dyn FnOnce() -> T + Send>>>,
+}
+
+impl<T> Lazy<T> {
+    /// Creates a value to be made by `f` when it’s first used.
+    #[inline]
+    pub fn new<F: FnOnce() -> T + Send + 'static>(f: F) -> Lazy<T> {
+        Lazy {
+            value: OnceCell::new(),
+            init: Cell::new(Some(Box::new(f))),
+        }
+    }
+
+    /// Creates a value that has already been made.
+    #[inline]
+    pub fn from_value(value: T) -> Lazy<T> {
+        Lazy {
+            value: OnceCell::from(value),
+            init: Cell::new(None),
+        }
+    }
+
+    /// Makes the value if it hasn’t been made yet, and returns a reference to it. This is an
+    /// associated function, lest it get in the way of `T`’s methods.
+    #[inline]
+    pub fn force(this: &Lazy<T>) -> &T {
+        this.value.get_or_init(|| match this.init.take() {
+            Some(init) => init(),
+            None => panic!("Lazy instance has previously been poisoned"),
+        })
+    }
+
+    /// Makes the value if it hasn’t been made yet, and returns a mutable reference to it.
+    #[inline]
+    pub fn force_mut(this: &mut Lazy<T>) -> &mut T {
+        let _ = Lazy::force(this);
+        this.value.get_mut().expect("forced just now")
+    }
+
+    /// Returns a reference to the value if it has been made, without making it.
+    #[inline]
+    pub fn get(this: &Lazy<T>) -> Option<&T> {
+        this.value.get()
+    }
+
+    /// Makes the value if it hasn’t been made yet, and returns it.
+    #[inline]
+    pub fn into_inner(mut this: Lazy<T>) -> T {
+        let _ = Lazy::force_mut(&mut this);
+        this.value.take().expect("forced just now")
+    }
+}
+
+impl<T> Deref for Lazy<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+impl<T> DerefMut for Lazy<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        Lazy::force_mut(self)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Lazy::get(self) {
+            Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+            None => f.write_str("Lazy(<unmade>)"),
+        }
+    }
+}
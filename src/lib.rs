@@ -2,10 +2,15 @@
 //!
 //! Your starting point is [`Map`]. It has an example.
 //!
+//! `Map` comes with each of the implementations below. Every other kind of map lives in a module
+//! of its own: those wrapping a `Map`, such as `tracked` and `scoped`, work with either
+//! implementation’s, and the rest, such as `tiny` or `sync`, are named in the list of features
+//! they need.
+//!
 //! # Cargo features
 //!
 //! This crate has two independent features, each of which provides an implementation providing
-//! types `Map`, `AnyMap`, `OccupiedEntry`, `VacantEntry`, `Entry` and `RawMap`:
+//! types `Map`, `AnyMap`, `OccupiedEntry`, `VacantEntry`, `Entry` and `RawMap`, among others:
 //!
 #![cfg_attr(
     feature = "std",
@@ -16,7 +21,11 @@
     doc = " - **std** (default, *disabled* in this build):"
 )]
 //!   an implementation using `std::collections::hash_map`, placed in the crate root
-//!   (e.g. `anymap::AnyMap`).
+//!   (e.g. `anymap::AnyMap`), the `sync` and `concurrent` modules of maps for sharing between
+//!   threads, the `local` module of thread-local values, the `events` module, with a map that
+//!   reports its changes over channels, the `cow` module, with a map with cheap snapshots, the
+//!   `di` module, a dependency injection container, the `frozen` module, with a map that can
+//!   grow through `&self`, and `static_map::Global`, a process-wide map.
 //!
 #![cfg_attr(
     feature = "hashbrown",
@@ -28,38 +37,194 @@
 )]
 //!   an implementation using `alloc` and `hashbrown::hash_map`, placed in a module `hashbrown`
 //!   (e.g. `anymap::hashbrown::AnyMap`).
+//!
+//! Either of them also brings the `arc`, `cell`, `keyed`, `multi` and `weak` modules, of maps
+//! holding `Arc`s, `RefCell`s, values under runtime keys, many values of each type, and `Weak`s.
+//! These use std’s hash map if they can, and hashbrown’s otherwise.
+//!
+//! There are also some other features:
+//!
+//! - **alloc** (implied by both of those): the `tiny` module, with a `Vec`-backed map for when
+//!   you know there’ll only ever be a few types. Without it, this crate doesn’t need an allocator
+//!   at all, leaving you the `static_cap` module, with a map storing a fixed number of small
+//!   values inline. It also brings the `persistent` module, with a map whose `insert` and
+//!   `remove` return new versions sharing structure with the old, and [`Lazy`], a value made on
+//!   first use.
+//!
+//! - **derive**: `#[derive(AnyMapBundle)]`, implementing [`Bundle`] for structs, and
+//!   `#[derive(FromAnyMap)]`, implementing `TryFrom<&Map>` for structs.
+//!
+//! - **async** (implies std): the `async_map` module, with `AsyncMap`, a map behind an async
+//!   read-write lock for sharing between tasks.
+//!
+//! - **arc-swap** (implies std): `sync::SnapshotMap`, a map for reading without locks,
+//!   swapping in whole new versions on writes, with [arc-swap](https://docs.rs/arc-swap).
+//!
+//! - **rayon** (implies alloc): `Map::{par_iter, par_iter_mut}`, iterating over the values in
+//!   parallel with [rayon](https://docs.rs/rayon), and `FromParallelIterator` and
+//!   `ParallelExtend` for building a `Map` from a parallel iterator of boxes.
+//!
+//! - **type-names**: record the name of each type stored in a `Map`, for diagnostics, as
+//!   `Map::{type_name_of, type_names}`. This costs a second table, so it’s off by default.
+//!
+//! - **serde** (implies alloc): the `serde` module, for serializing a `Map<dyn SerializeAny>`
+//!   with [serde](https://docs.rs/serde), keyed by stable names given to the types.
+//!
+//! - **postcard** (implies serde): `Map::{to_postcard, from_postcard}`, for serializing a map
+//!   compactly with [postcard](https://docs.rs/postcard), even without `std`.
+//!
+//! - **serde_json** (implies serde): `Map::{to_json_value, from_json_value}`, for a map as a
+//!   JSON object keyed by its types’ stable names, which people can read and edit.
+//!
+//! - **inventory** (implies serde and std): the `inventory` module, for registering types for
+//!   serialization and trait casting wherever they’re defined, with
+//!   [inventory](https://docs.rs/inventory).
+//!
+//! - **compat** (implies std): the `compat` module, the API of anymap 0.12 and anymap2 where
+//!   it differs from this one, with its raw map whose inserting methods are unsafe, for
+//!   migrating from them gradually.
+//!
+//! - **http** (implies std): the `http` module, for moving values between a
+//!   `Map<dyn Any + Send + Sync>` and the `Extensions` of [http](https://docs.rs/http) requests
+//!   and responses.
+//!
+//! - **reflect** (implies std): the `reflect` module, making `Map<dyn Reflect>` work with
+//!   [bevy_reflect](https://docs.rs/bevy_reflect), with values reachable by reflection path.
 
 #![warn(missing_docs, unused_results)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use core::convert::TryInto;
+use core::fmt;
 use core::hash::Hasher;
 
-#[cfg(not(feature = "std"))]
+#[cfg(all(feature = "alloc", not(feature = "std")))]
 extern crate alloc;
 
-pub use crate::any::{CloneAny, Downcast};
+pub use crate::any::{
+    CloneAny, CloneDebugAny, ClonePartialEqAny, CoerceFrom, DebugAny, Downcast, HashAny,
+    PartialEqAny,
+};
+pub use crate::tuple::{Bundle, CloneSubset, Fetch, FetchMut};
+pub use crate::key::{Key, KeyValue, Tagged};
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub use crate::cast::{CastFrom, TraitCasts};
+#[cfg(feature = "alloc")]
+pub use crate::lazy::Lazy;
+#[cfg(feature = "alloc")]
+pub use crate::provider::DefaultFactory;
+pub use crate::stable::StableKey;
+#[cfg(feature = "derive")]
+pub use anymap_derive::{AnyMapBundle, FromAnyMap};
 
+#[macro_use]
 mod any;
+#[macro_use]
+mod tuple;
+mod key;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_use]
+mod cast;
+#[cfg(feature = "alloc")]
+mod lazy;
+#[macro_use]
+pub mod stable;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub mod patch;
+#[cfg(feature = "reflect")]
+pub mod reflect;
+#[cfg(all(feature = "serde", any(feature = "std", feature = "hashbrown")))]
+pub mod serde;
+#[cfg(feature = "inventory")]
+pub mod inventory;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "alloc")]
+pub mod persistent;
+#[cfg(feature = "alloc")]
+mod provider;
+#[cfg(feature = "alloc")]
+pub mod tiny;
+pub mod static_cap;
+#[macro_use]
+pub mod static_map;
+#[cfg(feature = "async")]
+pub mod async_map;
+#[cfg(feature = "std")]
+pub mod concurrent;
+#[cfg(feature = "std")]
+pub mod cow;
+#[cfg(feature = "std")]
+pub mod di;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_use]
+pub mod builder;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_use]
+pub mod bounded;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_use]
+pub mod scoped;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_use]
+pub mod chain;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_use]
+pub mod tracked;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub mod arc;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub mod cell;
+#[cfg(feature = "std")]
+pub mod frozen;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub mod keyed;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub mod multi;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub mod weak;
+#[cfg(feature = "std")]
+pub mod local;
+#[cfg(feature = "std")]
+pub mod sync;
 
 #[cfg(any(feature = "std", feature = "hashbrown"))]
 macro_rules! everything {
     ($example_init:literal, $($parent:ident)::+ $(, $entry_generics:ty)?) => {
         use core::any::{Any, TypeId};
-        use core::hash::BuildHasherDefault;
+        use core::hash::{BuildHasher, BuildHasherDefault, Hash};
         use core::marker::PhantomData;
+        use core::pin::Pin;
 
         #[cfg(not(feature = "std"))]
         use alloc::boxed::Box;
+        #[cfg(not(feature = "std"))]
+        use alloc::vec::Vec;
 
         use ::$($parent)::+::hash_map::{self, HashMap};
+        use ::$($parent)::+::TryReserveError;
 
         use crate::any::{IntoBox};
 
+        // Values are always boxed, even small ones. Storing them inline (smallbox-style) has been
+        // considered, but `Box<A>` is part of the public API here—`as_raw`, `into_raw`,
+        // `insert_boxed`, `insert_dyn` and friends all hand out or take the boxes themselves—so
+        // it’d mean a different map type rather than a change to this one. (Zero-sized values
+        // don’t allocate anyway, and the pointer chase is rarely what dominates a `get`.) Keeping
+        // all the values in one arena, with the table holding offsets, runs into the same wall.
+        // For building and dropping many maps, reusing them with `clear` at least keeps the table.
+        //
+        // The same goes for switching to a linear scan over a flat array while the map is small:
+        // `RawMap` is public, so the backing can’t change underneath it. Nor is there much hashing
+        // to avoid, since `TypeIdHasher` just passes the `TypeId`’s bits through.
+
         /// Raw access to the underlying `HashMap`.
         ///
         /// This alias is provided for convenience because of the ugly third generic parameter.
-        pub type RawMap<A> = HashMap<TypeId, Box<A>, BuildHasherDefault<TypeIdHasher>>;
+        pub type RawMap<A, S = BuildHasherDefault<TypeIdHasher>> = HashMap<TypeId, Box<A>, S>;
 
         /// A collection containing zero or one values for any given type and allowing convenient,
         /// type-safe access to those values.
@@ -69,18 +234,34 @@ macro_rules! everything {
         ///
         /// - If you want the entire map to be cloneable, use `CloneAny` instead of `Any`; with
         ///   that, you can only add types that implement `Clone` to the map.
+        /// - If you want to be able to see the values in the map’s `Debug` output, use
+        ///   `DebugAny`, which likewise only accepts types that implement `Debug`.
+        /// - If you want to be able to compare whole maps with `==`, use `PartialEqAny`, which
+        ///   only accepts types that implement `PartialEq`.
+        /// - If you want to be able to hash whole maps, use `HashAny`, which only accepts types
+        ///   that implement `Hash`.
+        /// - If you want both cloning and debugging, use `CloneDebugAny`.
+        /// - If you want both cloning and equality, as [`diff`](Map::diff) does, use
+        ///   `ClonePartialEqAny`.
         /// - You can add on `+ Send` or `+ Send + Sync` (e.g. `Map<dyn Any + Send>`) to add those
         ///   auto traits.
         ///
-        /// Cumulatively, there are thus six forms of map:
+        /// The second type parameter `S` is the hasher, [`TypeIdHasher`] by default, which just
+        /// passes the `TypeId` through; you can use a different one with
+        /// [`with_hasher`](Map::with_hasher) if you have reason to.
+        ///
+        /// Cumulatively, there are thus three forms of map for each bound:
         ///
         /// - <code>[Map]&lt;dyn [core::any::Any]&gt;</code>,
         ///   also spelled [`AnyMap`] for convenience.
         /// - <code>[Map]&lt;dyn [core::any::Any] + Send&gt;</code>
         /// - <code>[Map]&lt;dyn [core::any::Any] + Send + Sync&gt;</code>
-        /// - <code>[Map]&lt;dyn [CloneAny]&gt;</code>
-        /// - <code>[Map]&lt;dyn [CloneAny] + Send&gt;</code>
-        /// - <code>[Map]&lt;dyn [CloneAny] + Send + Sync&gt;</code>
+        ///
+        /// and likewise for [`CloneAny`], [`DebugAny`], [`PartialEqAny`], [`HashAny`],
+        /// [`CloneDebugAny`] and [`ClonePartialEqAny`].
+        ///
+        /// A map converts with `From` into one that drops `Send` or `Sync`, or that goes from
+        /// `CloneAny` to `Any`, keeping the same boxes.
         ///
         /// ## Example
         ///
@@ -108,17 +289,90 @@ macro_rules! everything {
         /// ```
         ///
         /// Values containing non-static references are not permitted.
-        #[derive(Debug)]
-        pub struct Map<A: ?Sized + Downcast = dyn Any> {
-            raw: RawMap<A>,
+        ///
+        /// Each value is boxed, but boxing a zero-sized value doesn’t allocate, so marker types
+        /// used as flags cost nothing beyond their entry in the table.
+        pub struct Map<A: ?Sized + Downcast = dyn Any, S = BuildHasherDefault<TypeIdHasher>> {
+            raw: RawMap<A, S>,
+            // Kept to the types present: whatever removes a value from `raw` forgets its name too.
+            #[cfg(feature = "type-names")]
+            names: NameTable,
         }
 
+        #[cfg(feature = "type-names")]
+        type NameTable = HashMap<TypeId, &'static str, BuildHasherDefault<TypeIdHasher>>;
+
         // #[derive(Clone)] would want A to implement Clone, but in reality only Box<A> can.
-        impl<A: ?Sized + Downcast> Clone for Map<A> where Box<A>: Clone {
+        impl<A: ?Sized + Downcast, S: Clone> Clone for Map<A, S> where Box<A>: Clone {
             #[inline]
-            fn clone(&self) -> Map<A> {
+            fn clone(&self) -> Map<A, S> {
                 Map {
                     raw: self.raw.clone(),
+                    #[cfg(feature = "type-names")]
+                    names: self.names.clone(),
+                }
+            }
+        }
+
+        /// Shows the number of values and, for each of them, the name of its type (with the
+        /// `type-names` feature, falling back to its `TypeId`) and whatever `A`’s `Debug` shows;
+        /// for `dyn Any` that’s nothing in particular, but a `Debug`-bounded `A` shows the value.
+        impl<A: ?Sized + Downcast + fmt::Debug, S> fmt::Debug for Map<A, S> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                struct Key(TypeId, Option<&'static str>);
+                impl fmt::Debug for Key {
+                    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        match self.1 {
+                            Some(name) => f.write_str(name),
+                            None => self.0.fmt(f),
+                        }
+                    }
+                }
+
+                struct Entries<'a, A: ?Sized + Downcast, S>(&'a Map<A, S>);
+                impl<A: ?Sized + Downcast + fmt::Debug, S> fmt::Debug for Entries<'_, A, S> {
+                    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        let map = self.0;
+                        f.debug_map()
+                            .entries(map.raw.iter().map(|(&type_id, value)| {
+                                #[cfg(feature = "type-names")]
+                                let name = map.names.get(&type_id).copied();
+                                #[cfg(not(feature = "type-names"))]
+                                let name = None;
+                                (Key(type_id, name), &**value)
+                            }))
+                            .finish()
+                    }
+                }
+
+                f.debug_struct("Map")
+                    .field("len", &self.raw.len())
+                    .field("entries", &Entries(self))
+                    .finish()
+            }
+        }
+
+        // #[derive(PartialEq)] would want S: PartialEq, and would compare the type names.
+        impl<A: ?Sized + Downcast + PartialEq, S: BuildHasher> PartialEq for Map<A, S> {
+            #[inline]
+            fn eq(&self, other: &Map<A, S>) -> bool {
+                self.raw.len() == other.raw.len() && self.raw.iter().all(|(type_id, value)| {
+                    other.raw.get(type_id).is_some_and(|other_value| **value == **other_value)
+                })
+            }
+        }
+
+        /// Entries are hashed in `TypeId` order, so equal maps hash the same regardless of the order
+        /// they were built in. `TypeId`s are only stable within one build of a program, though,
+        /// so these hashes shouldn’t be persisted.
+        impl<A: ?Sized + Downcast + Hash, S> Hash for Map<A, S> {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                let mut entries = self.raw.iter().collect::<Vec<_>>();
+                entries.sort_unstable_by_key(|&(type_id, _)| *type_id);
+                state.write_usize(entries.len());
+                for (type_id, value) in entries {
+                    type_id.hash(state);
+                    (**value).hash(state);
                 }
             }
         }
@@ -130,10 +384,10 @@ macro_rules! everything {
         /// value. It’s a bit sad, really. Ah well, I guess this approach will do.
         pub type AnyMap = Map<dyn Any>;
 
-        impl<A: ?Sized + Downcast> Default for Map<A> {
+        impl<A: ?Sized + Downcast, S: Default> Default for Map<A, S> {
             #[inline]
-            fn default() -> Map<A> {
-                Map::new()
+            fn default() -> Map<A, S> {
+                Map::with_hasher(S::default())
             }
         }
 
@@ -143,6 +397,8 @@ macro_rules! everything {
             pub fn new() -> Map<A> {
                 Map {
                     raw: RawMap::with_hasher(Default::default()),
+                    #[cfg(feature = "type-names")]
+                    names: Default::default(),
                 }
             }
 
@@ -151,9 +407,83 @@ macro_rules! everything {
             pub fn with_capacity(capacity: usize) -> Map<A> {
                 Map {
                     raw: RawMap::with_capacity_and_hasher(capacity, Default::default()),
+                    #[cfg(feature = "type-names")]
+                    names: NameTable::with_capacity_and_hasher(capacity, Default::default()),
+                }
+            }
+
+            /// Creates a builder for a collection, for setting it up in a chain of method calls.
+            #[inline]
+            pub fn builder() -> crate::builder::Builder<Map<A>> {
+                crate::builder::Builder {
+                    map: Map::new(),
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S> Map<A, S> {
+            /// Creates an empty collection which will use the given hasher.
+            #[inline]
+            pub fn with_hasher(hasher: S) -> Map<A, S> {
+                Map {
+                    raw: RawMap::with_hasher(hasher),
+                    #[cfg(feature = "type-names")]
+                    names: Default::default(),
+                }
+            }
+
+            /// Creates an empty collection with the given initial capacity, which will use the
+            /// given hasher.
+            #[inline]
+            pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Map<A, S> {
+                Map {
+                    raw: RawMap::with_capacity_and_hasher(capacity, hasher),
+                    #[cfg(feature = "type-names")]
+                    names: NameTable::with_capacity_and_hasher(capacity, Default::default()),
                 }
             }
 
+            /// Returns a reference to the collection’s hasher.
+            #[inline]
+            pub fn hasher(&self) -> &S {
+                self.raw.hasher()
+            }
+        }
+
+        #[cfg(feature = "rayon")]
+        impl<A: ?Sized + Downcast, S> Map<A, S> {
+            /// Returns a parallel iterator over references to the values in the collection, as
+            /// `&A`: the [`values`](Map::values) of [rayon](https://docs.rs/rayon).
+            #[inline]
+            pub fn par_iter<'a>(&'a self) -> impl rayon::iter::ParallelIterator<Item = &'a A>
+            where
+                A: Sync,
+                &'a RawMap<A, S>: rayon::iter::IntoParallelIterator<Item = (&'a TypeId, &'a Box<A>)>,
+            {
+                rayon::iter::ParallelIterator::map(
+                    rayon::iter::IntoParallelIterator::into_par_iter(&self.raw),
+                    |(_, value)| &**value,
+                )
+            }
+
+            /// Returns a parallel iterator over mutable references to the values in the
+            /// collection, as `&mut A`: the [`values_mut`](Map::values_mut) of
+            /// [rayon](https://docs.rs/rayon).
+            #[inline]
+            pub fn par_iter_mut<'a>(&'a mut self) -> impl rayon::iter::ParallelIterator<Item = &'a mut A>
+            where
+                A: Send,
+                &'a mut RawMap<A, S>:
+                    rayon::iter::IntoParallelIterator<Item = (&'a TypeId, &'a mut Box<A>)>,
+            {
+                rayon::iter::ParallelIterator::map(
+                    rayon::iter::IntoParallelIterator::into_par_iter(&mut self.raw),
+                    |(_, value)| &mut **value,
+                )
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> Map<A, S> {
             /// Returns the number of elements the collection can hold without reallocating.
             #[inline]
             pub fn capacity(&self) -> usize {
@@ -169,7 +499,9 @@ macro_rules! everything {
             /// Panics if the new allocation size overflows `usize`.
             #[inline]
             pub fn reserve(&mut self, additional: usize) {
-                self.raw.reserve(additional)
+                self.raw.reserve(additional);
+                #[cfg(feature = "type-names")]
+                self.names.reserve(additional);
             }
 
             /// Shrinks the capacity of the collection as much as possible. It will drop
@@ -177,12 +509,87 @@ macro_rules! everything {
             /// and possibly leaving some space in accordance with the resize policy.
             #[inline]
             pub fn shrink_to_fit(&mut self) {
-                self.raw.shrink_to_fit()
+                self.raw.shrink_to_fit();
+                #[cfg(feature = "type-names")]
+                self.names.shrink_to_fit();
+            }
+
+            /// Tries to reserve capacity for at least `additional` more elements to be inserted
+            /// in the collection. The collection may reserve more space to avoid
+            /// frequent reallocations.
+            ///
+            /// # Errors
+            ///
+            /// If the capacity overflows, or the allocator reports a failure, then an error
+            /// is returned.
+            #[inline]
+            pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+                #[cfg(feature = "type-names")]
+                self.names.try_reserve(additional)?;
+                self.raw.try_reserve(additional)
+            }
+
+            /// Shrinks the capacity of the collection with a lower limit. It will drop
+            /// down no lower than the supplied limit while maintaining the internal rules
+            /// and possibly leaving some space in accordance with the resize policy.
+            ///
+            /// If the current capacity is less than the lower limit, this is a no-op.
+            #[inline]
+            pub fn shrink_to(&mut self, min_capacity: usize) {
+                self.raw.shrink_to(min_capacity);
+                #[cfg(feature = "type-names")]
+                self.names.shrink_to(min_capacity);
+            }
+
+            /// Returns an estimate of the heap memory used by the collection, in bytes.
+            ///
+            /// This is the hash table (as laid out by hashbrown, which also underlies std’s
+            /// `HashMap`) plus each value’s box, as broken down by
+            /// [`entry_layouts`](Map::entry_layouts). It doesn’t include anything the values own
+            /// themselves, such as a `Vec`’s buffer, or any of the allocator’s overhead.
+            pub fn heap_bytes(&self) -> usize {
+                let capacity = self.raw.capacity();
+                let table = if capacity == 0 {
+                    0
+                } else {
+                    // hashbrown allocates a power-of-two number of buckets, at most 7/8 full, with
+                    // a control byte for each bucket plus a group’s worth more (≤ 16).
+                    let buckets = if capacity < 8 {
+                        if capacity < 4 { 4 } else { 8 }
+                    } else {
+                        (capacity * 8 / 7).next_power_of_two()
+                    };
+                    buckets * (core::mem::size_of::<(TypeId, Box<A>)>() + 1) + 16
+                };
+                table + self.entry_layouts().map(|(_, layout)| layout.size()).sum::<usize>()
+            }
+
+            /// Returns an iterator over references to the values in the collection, as `&A`, in
+            /// arbitrary order.
+            #[inline]
+            pub fn values(&self) -> Values<'_, A> {
+                Values {
+                    iter: self.raw.values(),
+                }
+            }
+
+            /// Returns an iterator over mutable references to the values in the collection, as
+            /// `&mut A`, in arbitrary order.
+            #[inline]
+            pub fn values_mut(&mut self) -> ValuesMut<'_, A> {
+                ValuesMut {
+                    iter: self.raw.values_mut(),
+                }
             }
 
-            // Additional stable methods (as of 1.60.0-nightly) that could be added:
-            // try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>    (1.57.0)
-            // shrink_to(&mut self, min_capacity: usize)                                   (1.56.0)
+            /// Returns an iterator over the `TypeId` of each value in the collection and the
+            /// `Layout` of its box, in arbitrary order.
+            #[inline]
+            pub fn entry_layouts(&self) -> EntryLayouts<'_, A> {
+                EntryLayouts {
+                    iter: self.raw.iter(),
+                }
+            }
 
             /// Returns the number of items in the collection.
             #[inline]
@@ -199,7 +606,9 @@ macro_rules! everything {
             /// Removes all items from the collection. Keeps the allocated memory for reuse.
             #[inline]
             pub fn clear(&mut self) {
-                self.raw.clear()
+                self.raw.clear();
+                #[cfg(feature = "type-names")]
+                self.names.clear();
             }
 
             /// Returns a reference to the value stored in the collection for the type `T`,
@@ -210,376 +619,3359 @@ macro_rules! everything {
                     .map(|any| unsafe { any.downcast_ref_unchecked::<T>() })
             }
 
-            /// Returns a mutable reference to the value stored in the collection for the type `T`,
-            /// if it exists.
+            /// Returns references to the values stored in the collection for each of the types in
+            /// the tuple `T`, for those that exist.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert(42i32);
+            /// data.insert("str");
+            /// assert_eq!(data.get_all::<(i32, &str, bool)>(), (Some(&42), Some(&"str"), None));
+            /// ```
             #[inline]
-            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
-                self.raw.get_mut(&TypeId::of::<T>())
-                    .map(|any| unsafe { any.downcast_mut_unchecked::<T>() })
+            pub fn get_all<'a, T: Fetch<'a, Self>>(&'a self) -> T::Refs {
+                T::fetch(self)
             }
 
-            /// Sets the value stored in the collection for the type `T`.
-            /// If the collection already had a value of type `T`, that value is returned.
-            /// Otherwise, `None` is returned.
+            /// Returns mutable references to the values stored in the collection for each of the
+            /// types in the tuple `T`, if they all exist.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert(42i32);
+            /// data.insert(String::from("str"));
+            /// if let Some((i, s)) = data.get_many_mut::<(i32, String)>() {
+            ///     s.push_str(&i.to_string());
+            ///     *i += 1;
+            /// }
+            /// assert_eq!(data.get_all::<(i32, String)>(), (Some(&43), Some(&"str42".into())));
+            /// assert!(data.get_many_mut::<(i32, bool)>().is_none());
+            /// ```
+            ///
+            /// # Panics
+            ///
+            /// Panics if any type appears more than once in the tuple, as that would make for
+            /// aliasing mutable references.
             #[inline]
-            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
-                self.raw.insert(TypeId::of::<T>(), value.into_box())
-                    .map(|any| unsafe { *any.downcast_unchecked::<T>() })
+            pub fn get_many_mut<'a, T: FetchMut<'a, Self>>(&'a mut self) -> Option<T::Muts> {
+                T::fetch_mut(self)
             }
 
-            // rustc 1.60.0-nightly has another method try_insert that would be nice when stable.
+            /// Returns a reference to the value stored in the collection for the unsized type `T`,
+            /// if it exists. See [`insert_unsized`][Map::insert_unsized] for details.
+            #[inline]
+            pub fn get_unsized<T: ?Sized + 'static>(&self) -> Option<&T>
+            where
+                Box<T>: IntoBox<A>,
+            {
+                self.get::<Box<T>>().map(|value| &**value)
+            }
 
-            /// Removes the `T` value from the collection,
-            /// returning it if there was one or `None` if there was not.
+            /// Returns a mutable reference to the value stored in the collection for the unsized
+            /// type `T`, if it exists. See [`insert_unsized`][Map::insert_unsized] for details.
             #[inline]
-            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
-                self.raw.remove(&TypeId::of::<T>())
-                    .map(|any| *unsafe { any.downcast_unchecked::<T>() })
+            pub fn get_unsized_mut<T: ?Sized + 'static>(&mut self) -> Option<&mut T>
+            where
+                Box<T>: IntoBox<A>,
+            {
+                self.get_mut::<Box<T>>().map(|value| &mut **value)
             }
 
-            /// Returns true if the collection contains a value of type `T`.
+            /// Returns a pinned reference to the pinned value stored in the collection for the type
+            /// `T`, if it exists. See [`insert_pinned`][Map::insert_pinned] for details.
             #[inline]
-            pub fn contains<T: IntoBox<A>>(&self) -> bool {
-                self.raw.contains_key(&TypeId::of::<T>())
+            pub fn get_pinned<T: 'static>(&self) -> Option<Pin<&T>>
+            where
+                Pin<Box<T>>: IntoBox<A>,
+            {
+                self.get::<Pin<Box<T>>>().map(|value| value.as_ref())
             }
 
-            /// Gets the entry for the given type in the collection for in-place manipulation
+            /// Returns a pinned mutable reference to the pinned value stored in the collection for
+            /// the type `T`, if it exists. See [`insert_pinned`][Map::insert_pinned] for details.
             #[inline]
-            pub fn entry<T: IntoBox<A>>(&mut self) -> Entry<A, T> {
-                match self.raw.entry(TypeId::of::<T>()) {
-                    hash_map::Entry::Occupied(e) => Entry::Occupied(OccupiedEntry {
-                        inner: e,
-                        type_: PhantomData,
-                    }),
-                    hash_map::Entry::Vacant(e) => Entry::Vacant(VacantEntry {
-                        inner: e,
-                        type_: PhantomData,
-                    }),
-                }
+            pub fn get_pinned_mut<T: 'static>(&mut self) -> Option<Pin<&mut T>>
+            where
+                Pin<Box<T>>: IntoBox<A>,
+            {
+                self.get_mut::<Pin<Box<T>>>().map(|value| value.as_mut())
             }
 
-            /// Get access to the raw hash map that backs this.
-            ///
-            /// This will seldom be useful, but it’s conceivable that you could wish to iterate
-            /// over all the items in the collection, and this lets you do that.
+            /// Returns a clone of the value stored in the collection for the type `T`,
+            /// if it exists.
             #[inline]
-            pub fn as_raw(&self) -> &RawMap<A> {
-                &self.raw
+            pub fn get_cloned<T: IntoBox<A> + Clone>(&self) -> Option<T> {
+                self.get::<T>().cloned()
             }
 
-            /// Get mutable access to the raw hash map that backs this.
-            ///
-            /// This will seldom be useful, but it’s conceivable that you could wish to iterate
-            /// over all the items in the collection mutably, or drain or something, or *possibly*
-            /// even batch insert, and this lets you do that.
-            ///
-            /// # Safety
-            ///
-            /// If you insert any values to the raw map, the key (a `TypeId`) must match the
-            /// value’s type, or *undefined behaviour* will occur when you access those values.
-            ///
-            /// (*Removing* entries is perfectly safe.)
+            /// Returns a copy of the value stored in the collection for the type `T`,
+            /// if it exists.
             #[inline]
-            pub unsafe fn as_raw_mut(&mut self) -> &mut RawMap<A> {
-                &mut self.raw
+            pub fn get_copied<T: IntoBox<A> + Copy>(&self) -> Option<T> {
+                self.get::<T>().copied()
             }
 
-            /// Convert this into the raw hash map that backs this.
-            ///
-            /// This will seldom be useful, but it’s conceivable that you could wish to consume all
-            /// the items in the collection and do *something* with some or all of them, and this
-            /// lets you do that, without the `unsafe` that `.as_raw_mut().drain()` would require.
+            /// Returns a reference to the value stored in the collection for the type `T`, or an
+            /// error naming the type if there is none.
             #[inline]
-            pub fn into_raw(self) -> RawMap<A> {
-                self.raw
+            pub fn try_get<T: IntoBox<A>>(&self) -> Result<&T, crate::MissingError> {
+                self.get::<T>().ok_or_else(crate::MissingError::new::<T>)
             }
 
-            /// Construct a map from a collection of raw values.
-            ///
-            /// You know what? I can’t immediately think of any legitimate use for this, especially
-            /// because of the requirement of the `BuildHasherDefault<TypeIdHasher>` generic in the
-            /// map.
-            ///
-            /// Perhaps this will be most practical as `unsafe { Map::from_raw(iter.collect()) }`,
-            /// `iter` being an iterator over `(TypeId, Box<A>)` pairs. Eh, this method provides
-            /// symmetry with `into_raw`, so I don’t care if literally no one ever uses it. I’m not
-            /// even going to write a test for it, it’s so trivial.
+            /// Returns a reference to the value stored in the collection for the type `T`.
             ///
-            /// # Safety
+            /// # Panics
             ///
-            /// For all entries in the raw map, the key (a `TypeId`) must match the value’s type,
-            /// or *undefined behaviour* will occur when you access that entry.
+            /// Panics if there is none, with a message giving `context` and the name of the type,
+            /// which is rather more useful in logs than unwrapping the `None` from `get`.
             #[inline]
-            pub unsafe fn from_raw(raw: RawMap<A>) -> Map<A> {
-                Self { raw }
+            #[track_caller]
+            pub fn expect<T: IntoBox<A>>(&self, context: &str) -> &T {
+                match self.get::<T>() {
+                    Some(value) => value,
+                    None => panic!("{}: {}", context, crate::MissingError::new::<T>()),
+                }
             }
-        }
 
-        impl<A: ?Sized + Downcast> Extend<Box<A>> for Map<A> {
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// or an error naming the type if there is none.
             #[inline]
-            fn extend<T: IntoIterator<Item = Box<A>>>(&mut self, iter: T) {
-                for item in iter {
-                    let _ = self.raw.insert(Downcast::type_id(&*item), item);
-                }
+            pub fn try_get_mut<T: IntoBox<A>>(&mut self) -> Result<&mut T, crate::MissingError> {
+                self.get_mut::<T>().ok_or_else(crate::MissingError::new::<T>)
             }
-        }
-
-        /// A view into a single occupied location in an `Map`.
-        pub struct OccupiedEntry<'a, A: ?Sized + Downcast, V: 'a> {
-            inner: hash_map::OccupiedEntry<'a, TypeId, Box<A>, $($entry_generics)?>,
-            type_: PhantomData<V>,
-        }
-
-        /// A view into a single empty location in an `Map`.
-        pub struct VacantEntry<'a, A: ?Sized + Downcast, V: 'a> {
-            inner: hash_map::VacantEntry<'a, TypeId, Box<A>, $($entry_generics)?>,
-            type_: PhantomData<V>,
-        }
-
-        /// A view into a single location in an `Map`, which may be vacant or occupied.
-        pub enum Entry<'a, A: ?Sized + Downcast, V: 'a> {
-            /// An occupied Entry
-            Occupied(OccupiedEntry<'a, A, V>),
-            /// A vacant Entry
-            Vacant(VacantEntry<'a, A, V>),
-        }
 
-        impl<'a, A: ?Sized + Downcast, V: IntoBox<A>> Entry<'a, A, V> {
-            /// Ensures a value is in the entry by inserting the default if empty, and returns
-            /// a mutable reference to the value in the entry.
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// if it exists.
             #[inline]
-            pub fn or_insert(self, default: V) -> &'a mut V {
-                match self {
-                    Entry::Occupied(inner) => inner.into_mut(),
-                    Entry::Vacant(inner) => inner.insert(default),
-                }
+            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+                self.raw.get_mut(&TypeId::of::<T>())
+                    .map(|any| unsafe { any.downcast_mut_unchecked::<T>() })
             }
 
-            /// Ensures a value is in the entry by inserting the result of the default function if
-            /// empty, and returns a mutable reference to the value in the entry.
+            /// Calls `f` with a mutable reference to the value stored in the collection for the
+            /// type `T`, if it exists, returning whether it did.
             #[inline]
-            pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
-                match self {
-                    Entry::Occupied(inner) => inner.into_mut(),
-                    Entry::Vacant(inner) => inner.insert(default()),
+            pub fn update<T: IntoBox<A>, F: FnOnce(&mut T)>(&mut self, f: F) -> bool {
+                match self.get_mut() {
+                    Some(value) => {
+                        f(value);
+                        true
+                    },
+                    None => false,
                 }
             }
 
-            /// Ensures a value is in the entry by inserting the default value if empty,
-            /// and returns a mutable reference to the value in the entry.
+            /// Sets the value stored in the collection for the type `T`.
+            /// If the collection already had a value of type `T`, that value is returned.
+            /// Otherwise, `None` is returned.
             #[inline]
-            pub fn or_default(self) -> &'a mut V where V: Default {
-                match self {
-                    Entry::Occupied(inner) => inner.into_mut(),
-                    Entry::Vacant(inner) => inner.insert(Default::default()),
-                }
+            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+                self.record_type_name::<T>();
+                self.raw.insert(TypeId::of::<T>(), value.into_box())
+                    .map(|any| unsafe { *any.downcast_unchecked::<T>() })
             }
 
-            /// Provides in-place mutable access to an occupied entry before any potential inserts
-            /// into the map.
+            /// Sets the value stored in the collection for the type `T` until the returned guard
+            /// is dropped, when whatever was there before (or nothing) is put back. Meanwhile, the
+            /// map is used through the guard.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert(1i32);
+            /// {
+            ///     let data = data.insert_scoped(2i32);
+            ///     assert_eq!(data.get::<i32>(), Some(&2));
+            /// }
+            /// assert_eq!(data.get::<i32>(), Some(&1));
+            /// ```
             #[inline]
-            pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
-                match self {
-                    Entry::Occupied(mut inner) => {
-                        f(inner.get_mut());
-                        Entry::Occupied(inner)
+            pub fn insert_scoped<T: IntoBox<A>>(
+                &mut self,
+                value: T,
+            ) -> crate::scoped::ScopedGuard<'_, T, Self> {
+                let previous = self.insert(value);
+                crate::scoped::ScopedGuard {
+                    map: self,
+                    previous,
+                    restore: |map, previous| {
+                        let _ = match previous {
+                            Some(previous) => map.insert(previous),
+                            None => map.remove::<T>(),
+                        };
                     },
-                    Entry::Vacant(inner) => Entry::Vacant(inner),
                 }
             }
 
-            // Additional stable methods (as of 1.60.0-nightly) that could be added:
-            // insert_entry(self, value: V) -> OccupiedEntry<'a, K, V>                     (1.59.0)
-        }
+            /// Stores `f` to make the value for the type `T` when it’s first asked for with
+            /// [`get_lazy`](Map::get_lazy) or [`get_lazy_mut`](Map::get_lazy_mut), so that an
+            /// expensive value that’s never used is never made. If the collection already had a
+            /// lazy value of type `T`, made or not, that is returned. Otherwise, `None` is
+            /// returned.
+            ///
+            /// It’s stored as a [`Lazy<T>`](crate::Lazy), which is a type of its own, so the other
+            /// methods, like `get::<T>`, `contains::<T>` and `entry::<T>`, don’t see it; they see
+            /// it as a `Lazy<T>`.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert_lazy(|| vec![0u8; 1 << 20]);
+            /// assert!(data.get::<Vec<u8>>().is_none());
+            /// assert_eq!(data.get_lazy::<Vec<u8>>().unwrap().len(), 1 << 20);
+            /// ```
+            #[inline]
+            pub fn insert_lazy<T: 'static, F: FnOnce() -> T + Send + 'static>(
+                &mut self,
+                f: F,
+            ) -> Option<crate::Lazy<T>>
+            where
+                crate::Lazy<T>: IntoBox<A>,
+            {
+                self.insert(crate::Lazy::new(f))
+            }
 
-        impl<'a, A: ?Sized + Downcast, V: IntoBox<A>> OccupiedEntry<'a, A, V> {
-            /// Gets a reference to the value in the entry
+            /// Returns a reference to the lazy value for the type `T`, first making it if this
+            /// is the first time it’s been asked for, if there is one.
             #[inline]
-            pub fn get(&self) -> &V {
-                unsafe { self.inner.get().downcast_ref_unchecked() }
+            pub fn get_lazy<T: 'static>(&self) -> Option<&T> where crate::Lazy<T>: IntoBox<A> {
+                self.get::<crate::Lazy<T>>().map(crate::Lazy::force)
             }
 
-            /// Gets a mutable reference to the value in the entry
+            /// Returns a mutable reference to the lazy value for the type `T`, first making it if
+            /// this is the first time it’s been asked for, if there is one.
             #[inline]
-            pub fn get_mut(&mut self) -> &mut V {
-                unsafe { self.inner.get_mut().downcast_mut_unchecked() }
+            pub fn get_lazy_mut<T: 'static>(&mut self) -> Option<&mut T>
+            where
+                crate::Lazy<T>: IntoBox<A>,
+            {
+                self.get_mut::<crate::Lazy<T>>().map(crate::Lazy::force_mut)
             }
 
-            /// Converts the OccupiedEntry into a mutable reference to the value in the entry
-            /// with a lifetime bound to the collection itself
+            /// Sets the value for the [`Key`](crate::Key) `K`. If the collection already had a
+            /// value for `K`, that value is returned. Otherwise, `None` is returned.
+            ///
+            /// It’s stored as a [`KeyValue<K>`](crate::KeyValue), so the other methods, like
+            /// `get::<K::Value>`, don’t see it; they see it as a `KeyValue<K>`.
             #[inline]
-            pub fn into_mut(self) -> &'a mut V {
-                unsafe { self.inner.into_mut().downcast_mut_unchecked() }
+            pub fn insert_key<K: crate::Key>(&mut self, value: K::Value) -> Option<K::Value>
+            where
+                crate::KeyValue<K>: IntoBox<A>,
+            {
+                self.insert(crate::KeyValue::<K>(value)).map(|previous| previous.0)
             }
 
-            /// Sets the value of the entry, and returns the entry's old value
+            /// Returns a reference to the value for the [`Key`](crate::Key) `K`, if there is one.
             #[inline]
-            pub fn insert(&mut self, value: V) -> V {
-                unsafe { *self.inner.insert(value.into_box()).downcast_unchecked() }
+            pub fn get_key<K: crate::Key>(&self) -> Option<&K::Value>
+            where
+                crate::KeyValue<K>: IntoBox<A>,
+            {
+                self.get::<crate::KeyValue<K>>().map(|value| &value.0)
             }
 
-            /// Takes the value out of the entry, and returns it
+            /// Returns a mutable reference to the value for the [`Key`](crate::Key) `K`, if there
+            /// is one.
             #[inline]
-            pub fn remove(self) -> V {
-                unsafe { *self.inner.remove().downcast_unchecked() }
+            pub fn get_key_mut<K: crate::Key>(&mut self) -> Option<&mut K::Value>
+            where
+                crate::KeyValue<K>: IntoBox<A>,
+            {
+                self.get_mut::<crate::KeyValue<K>>().map(|value| &mut value.0)
             }
-        }
 
-        impl<'a, A: ?Sized + Downcast, V: IntoBox<A>> VacantEntry<'a, A, V> {
-            /// Sets the value of the entry with the VacantEntry's key,
-            /// and returns a mutable reference to it
+            /// Removes the value for the [`Key`](crate::Key) `K`, returning it if there was one
+            /// or `None` if there was not.
             #[inline]
-            pub fn insert(self, value: V) -> &'a mut V {
-                unsafe { self.inner.insert(value.into_box()).downcast_mut_unchecked() }
+            pub fn remove_key<K: crate::Key>(&mut self) -> Option<K::Value>
+            where
+                crate::KeyValue<K>: IntoBox<A>,
+            {
+                self.remove::<crate::KeyValue<K>>().map(|value| value.0)
             }
-        }
 
-        #[cfg(test)]
-        mod tests {
-            use crate::CloneAny;
-            use super::*;
+            /// Returns true if the collection has a value for the [`Key`](crate::Key) `K`.
+            #[inline]
+            pub fn contains_key<K: crate::Key>(&self) -> bool
+            where
+                crate::KeyValue<K>: IntoBox<A>,
+            {
+                self.contains::<crate::KeyValue<K>>()
+            }
 
-            #[derive(Clone, Debug, PartialEq)] struct A(i32);
-            #[derive(Clone, Debug, PartialEq)] struct B(i32);
-            #[derive(Clone, Debug, PartialEq)] struct C(i32);
-            #[derive(Clone, Debug, PartialEq)] struct D(i32);
-            #[derive(Clone, Debug, PartialEq)] struct E(i32);
-            #[derive(Clone, Debug, PartialEq)] struct F(i32);
-            #[derive(Clone, Debug, PartialEq)] struct J(i32);
+            /// Sets the value of type `T` tagged with the marker type `M`, as a
+            /// [`Tagged<T, M>`](crate::Tagged). If the collection already had one, its value is
+            /// returned. Otherwise, `None` is returned.
+            #[inline]
+            pub fn insert_tagged<M: ?Sized + 'static, T>(&mut self, value: T) -> Option<T>
+            where
+                crate::Tagged<T, M>: IntoBox<A>,
+            {
+                self.insert(crate::Tagged::<T, M>::new(value)).map(crate::Tagged::into_inner)
+            }
 
-            macro_rules! test_entry {
-                ($name:ident, $init:ty) => {
-                    #[test]
-                    fn $name() {
-                        let mut map = <$init>::new();
-                        assert_eq!(map.insert(A(10)), None);
-                        assert_eq!(map.insert(B(20)), None);
-                        assert_eq!(map.insert(C(30)), None);
-                        assert_eq!(map.insert(D(40)), None);
-                        assert_eq!(map.insert(E(50)), None);
-                        assert_eq!(map.insert(F(60)), None);
+            /// Returns a reference to the value of type `T` tagged with the marker type `M`, if
+            /// it exists.
+            #[inline]
+            pub fn get_tagged<T, M: ?Sized + 'static>(&self) -> Option<&T>
+            where
+                crate::Tagged<T, M>: IntoBox<A>,
+            {
+                self.get::<crate::Tagged<T, M>>().map(|value| &value.0)
+            }
+
+            /// Returns a mutable reference to the value of type `T` tagged with the marker type
+            /// `M`, if it exists.
+            #[inline]
+            pub fn get_tagged_mut<T, M: ?Sized + 'static>(&mut self) -> Option<&mut T>
+            where
+                crate::Tagged<T, M>: IntoBox<A>,
+            {
+                self.get_mut::<crate::Tagged<T, M>>().map(|value| &mut value.0)
+            }
+
+            /// Removes the value of type `T` tagged with the marker type `M`, returning it if
+            /// there was one or `None` if there was not.
+            #[inline]
+            pub fn remove_tagged<T, M: ?Sized + 'static>(&mut self) -> Option<T>
+            where
+                crate::Tagged<T, M>: IntoBox<A>,
+            {
+                self.remove::<crate::Tagged<T, M>>().map(crate::Tagged::into_inner)
+            }
+
+            /// Sets the values stored in the collection for each of the types in the bundle `B`,
+            /// typically a tuple, returning the values that were there before.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert(1i32);
+            /// assert_eq!(data.insert_bundle((2i32, "str", true)), (Some(1), None, None));
+            /// assert_eq!(data.get_all::<(i32, &str, bool)>(), (Some(&2), Some(&"str"), Some(&true)));
+            /// ```
+            #[inline]
+            pub fn insert_bundle<B: Bundle<Self>>(&mut self, bundle: B) -> B::Displaced {
+                bundle.insert_into(self)
+            }
+
+            /// Sets the value stored in the collection for the type `T` to the boxed `value`,
+            /// which is stored in its existing allocation rather than being moved into a new one.
+            /// If the collection already had a value of type `T`, that value is returned, still
+            /// boxed. Otherwise, `None` is returned.
+            #[inline]
+            pub fn insert_boxed<T: IntoBox<A>>(&mut self, value: Box<T>) -> Option<Box<T>> {
+                self.record_type_name::<T>();
+                self.raw.insert(TypeId::of::<T>(), value.box_into_box())
+                    .map(|any| unsafe { any.downcast_unchecked::<T>() })
+            }
+
+            /// Sets the value stored in the collection for the type of the already-boxed `value`,
+            /// as determined by its own `type_id`, for when the concrete type isn’t known
+            /// statically. If the collection already had a value of that type, that value is
+            /// returned. Otherwise, `None` is returned.
+            ///
+            /// With no static type to go by, the name of the value’s type must be given, as
+            /// `type_name`, for `type_name_of` and the like (with the `type-names` feature); it’s
+            /// typically from `core::any::type_name_of_val`, called before the value was boxed.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// let value = 42i32;
+            /// let type_name = core::any::type_name_of_val(&value);
+            /// data.insert_dyn(Box::new(value), type_name);
+            /// assert_eq!(data.get::<i32>(), Some(&42));
+            /// ```
+            #[inline]
+            pub fn insert_dyn(&mut self, value: Box<A>, type_name: &'static str) -> Option<Box<A>> {
+                let type_id = Downcast::type_id(&*value);
+                #[cfg(feature = "type-names")]
+                let _ = self.names.insert(type_id, type_name);
+                #[cfg(not(feature = "type-names"))]
+                let _ = type_name;
+                self.raw.insert(type_id, value)
+            }
+
+            /// Sets the value stored in the collection for the unsized type `T`, such as `str`,
+            /// `[u8]` or `dyn Trait`. If the collection already had a value of type `T`, that
+            /// value is returned. Otherwise, `None` is returned.
+            ///
+            /// The value is stored as the `Box<T>` itself, so this is the same slot as
+            /// <code>[insert][Map::insert]::&lt;Box&lt;T&gt;&gt;</code> uses, and the other
+            /// `_unsized` methods are likewise shorthands for those on `Box<T>`.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert_unsized::<str>("str".into());
+            /// assert_eq!(data.get_unsized::<str>(), Some("str"));
+            /// data.get_unsized_mut::<str>().unwrap().make_ascii_uppercase();
+            /// assert_eq!(data.remove_unsized::<str>(), Some("STR".into()));
+            /// ```
+            #[inline]
+            pub fn insert_unsized<T: ?Sized + 'static>(&mut self, value: Box<T>) -> Option<Box<T>>
+            where
+                Box<T>: IntoBox<A>,
+            {
+                self.insert(value)
+            }
+
+            /// Sets the pinned value stored in the collection for the type `T`, returning a pinned
+            /// reference to it. Any previous pinned value of type `T` is dropped.
+            ///
+            /// The value is stored as a `Pin<Box<T>>`, so this is the same slot as
+            /// <code>[insert][Map::insert]::&lt;Pin&lt;Box&lt;T&gt;&gt;&gt;</code> uses, and the
+            /// other `_pinned` methods are likewise shorthands for those on `Pin<Box<T>>`. Since
+            /// the only way to get the value out again is in its `Pin<Box<T>>`, its address is
+            /// guaranteed never to change until it is dropped, whatever happens to the map.
+            #[inline]
+            pub fn insert_pinned<T: 'static>(&mut self, value: T) -> Pin<&mut T>
+            where
+                Pin<Box<T>>: IntoBox<A>,
+            {
+                self.entry::<Pin<Box<T>>>().insert_entry(Box::pin(value)).into_mut().as_mut()
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// inserting `value` first if there was none.
+            #[inline]
+            pub fn get_or_insert<T: IntoBox<A>>(&mut self, value: T) -> &mut T {
+                self.entry().or_insert(value)
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// inserting the result of `f` first if there was none.
+            #[inline]
+            pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&mut self, f: F) -> &mut T {
+                self.entry().or_insert_with(f)
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// inserting `T::default()` first if there was none.
+            #[inline]
+            pub fn get_or_insert_default<T: IntoBox<A> + Default>(&mut self) -> &mut T {
+                self.entry().or_default()
+            }
+
+            /// Sets the value stored in the collection for the type `T`, and binds it to the trait
+            /// object type `Tr`, so that [`get_trait::<Tr>`](Map::get_trait) returns it in place
+            /// of any value bound before. If the collection already had a value of type `T`, that
+            /// value is returned. Otherwise, `None` is returned.
+            ///
+            /// `Tr` must be castable from `T`, which [`impl_cast!`](crate::impl_cast) arranges for
+            /// your own traits. How to cast is recorded in the collection as a
+            /// [`TraitCasts<Tr, A>`](crate::TraitCasts).
+            ///
+            /// ```rust
+            /// trait Storage {
+            ///     fn name(&self) -> &'static str;
+            /// }
+            /// anymap::impl_cast!(Storage);
+            ///
+            /// struct Disk;
+            /// impl Storage for Disk {
+            ///     fn name(&self) -> &'static str { "disk" }
+            /// }
+            ///
+            #[doc = $example_init]
+            /// data.bind::<dyn Storage, _>(Disk);
+            /// assert_eq!(data.get_trait::<dyn Storage>().unwrap().name(), "disk");
+            /// assert!(data.contains::<Disk>());
+            /// ```
+            #[inline]
+            pub fn bind<Tr, T>(&mut self, value: T) -> Option<T>
+            where
+                Tr: ?Sized + crate::CastFrom<T> + 'static,
+                T: IntoBox<A>,
+                crate::TraitCasts<Tr, A>: IntoBox<A>,
+            {
+                let casts = self.get_or_insert_with(crate::TraitCasts::<Tr, A>::new);
+                let _ = casts.record::<T>();
+                casts.bind(TypeId::of::<T>());
+                self.insert(value)
+            }
+
+            /// Returns a reference to the value bound to the trait object type `Tr` with
+            /// [`bind`](Map::bind), as a `Tr`, if it exists.
+            #[inline]
+            pub fn get_trait<Tr: ?Sized + 'static>(&self) -> Option<&Tr>
+            where
+                crate::TraitCasts<Tr, A>: IntoBox<A>,
+            {
+                let casts = self.get::<crate::TraitCasts<Tr, A>>()?;
+                let type_id = casts.bound()?;
+                casts.cast_ref(type_id, self.raw.get(&type_id)?)
+            }
+
+            /// Returns a mutable reference to the value bound to the trait object type `Tr` with
+            /// [`bind`](Map::bind), as a `Tr`, if it exists.
+            #[inline]
+            pub fn get_trait_mut<Tr: ?Sized + 'static>(&mut self) -> Option<&mut Tr>
+            where
+                crate::TraitCasts<Tr, A>: IntoBox<A>,
+            {
+                let casts = self.get::<crate::TraitCasts<Tr, A>>()?;
+                let type_id = casts.bound()?;
+                let cast_mut = casts.cast_mut(type_id)?;
+                Some(cast_mut(self.raw.get_mut(&type_id)?))
+            }
+
+            /// Records that values of type `T` can be cast to the trait object type `Tr`, so that
+            /// [`iter_as::<Tr>`](Map::iter_as) yields the collection’s value of type `T`, if any,
+            /// without binding it as [`bind`](Map::bind) does. Returns false if it was already
+            /// recorded.
+            #[inline]
+            pub fn register_cast<Tr, T>(&mut self) -> bool
+            where
+                Tr: ?Sized + crate::CastFrom<T> + 'static,
+                T: IntoBox<A>,
+                crate::TraitCasts<Tr, A>: IntoBox<A>,
+            {
+                let casts = self.get_or_insert_with(crate::TraitCasts::<Tr, A>::new);
+                casts.record::<T>()
+            }
+
+            /// Returns an iterator over the values in the collection that can be cast to the
+            /// trait object type `Tr`, as recorded by [`register_cast`](Map::register_cast) or
+            /// [`bind`](Map::bind), as `Tr`s, in the order their types were recorded.
+            ///
+            /// ```rust
+            /// trait Shutdown {
+            ///     fn shutdown(&self) -> &'static str;
+            /// }
+            /// anymap::impl_cast!(Shutdown);
+            ///
+            /// struct Cache;
+            /// impl Shutdown for Cache {
+            ///     fn shutdown(&self) -> &'static str { "cache flushed" }
+            /// }
+            /// struct Socket;
+            /// impl Shutdown for Socket {
+            ///     fn shutdown(&self) -> &'static str { "socket closed" }
+            /// }
+            ///
+            #[doc = $example_init]
+            /// data.insert(Cache);
+            /// data.insert(Socket);
+            /// data.insert(42i32);
+            /// data.register_cast::<dyn Shutdown, Cache>();
+            /// data.register_cast::<dyn Shutdown, Socket>();
+            /// let done = data.iter_as::<dyn Shutdown>().map(|s| s.shutdown()).collect::<Vec<_>>();
+            /// assert_eq!(done, ["cache flushed", "socket closed"]);
+            /// ```
+            #[inline]
+            pub fn iter_as<Tr: ?Sized + 'static>(&self) -> impl Iterator<Item = &Tr> + '_
+            where
+                crate::TraitCasts<Tr, A>: IntoBox<A>,
+            {
+                let casts = self.get::<crate::TraitCasts<Tr, A>>();
+                casts.into_iter().flat_map(move |casts| {
+                    casts.type_ids().filter_map(move |type_id| {
+                        casts.cast_ref(type_id, self.raw.get(&type_id)?)
+                    })
+                })
+            }
+
+            /// Registers `factory` as the way to make a value of type `T` for
+            /// [`get_or_registered_default`](Map::get_or_registered_default), replacing any
+            /// registered before, which is returned.
+            ///
+            /// The factory is stored in the collection as a
+            /// [`DefaultFactory<T>`](crate::DefaultFactory), so that code which only uses a `T`
+            /// needn’t know how to make one.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.register_default(|| String::from("configured"));
+            /// assert!(!data.contains::<String>());
+            /// data.get_or_registered_default::<String>().unwrap().push('!');
+            /// assert_eq!(data.get::<String>().unwrap(), "configured!");
+            /// assert_eq!(data.get_or_registered_default::<i32>(), None);
+            /// ```
+            #[inline]
+            pub fn register_default<T: 'static, F: Fn() -> T + Send + Sync + 'static>(
+                &mut self,
+                factory: F,
+            ) -> Option<crate::DefaultFactory<T>>
+            where
+                crate::DefaultFactory<T>: IntoBox<A>,
+            {
+                self.insert(crate::DefaultFactory::new(factory))
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// first making one with the factory registered by
+            /// [`register_default`](Map::register_default) if there was none. Returns `None` if
+            /// there was neither value nor factory.
+            #[inline]
+            pub fn get_or_registered_default<T: IntoBox<A>>(&mut self) -> Option<&mut T>
+            where
+                crate::DefaultFactory<T>: IntoBox<A>,
+            {
+                if !self.contains::<T>() {
+                    let value = self.get::<crate::DefaultFactory<T>>()?.make();
+                    return Some(self.get_or_insert(value));
+                }
+                self.get_mut::<T>()
+            }
+
+            /// Tries to set the value stored in the collection for the type `T`, returning a
+            /// mutable reference to it.
+            ///
+            /// If the collection already had a value of type `T`, nothing is updated, and an error
+            /// containing the occupied entry and the value is returned.
+            #[inline]
+            pub fn try_insert<T: IntoBox<A>>(&mut self, value: T)
+                -> Result<&mut T, OccupiedError<'_, A, T, S>>
+            {
+                match self.entry() {
+                    Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
+                    Entry::Vacant(entry) => Ok(entry.insert(value)),
+                }
+            }
+
+            /// Replaces the value stored in the collection for the type `T` with the result of
+            /// `f`, which is given the current value, if any.
+            ///
+            /// If `f` returns `None`, the collection is left without a value of type `T`.
+            #[inline]
+            pub fn replace_with<T: IntoBox<A>, F: FnOnce(Option<T>) -> Option<T>>(&mut self, f: F) {
+                if let Some(value) = f(self.remove::<T>()) {
+                    let _ = self.insert(value);
+                }
+            }
+
+            /// Removes the `T` value from the collection,
+            /// returning it if there was one or `None` if there was not.
+            #[inline]
+            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+                self.remove_raw(&TypeId::of::<T>())
+                    .map(|any| *unsafe { any.downcast_unchecked::<T>() })
+            }
+
+            /// Removes the pinned value for the type `T` from the collection, returning it, still
+            /// pinned, if there was one or `None` if there was not. See
+            /// [`insert_pinned`][Map::insert_pinned] for details.
+            #[inline]
+            pub fn remove_pinned<T: 'static>(&mut self) -> Option<Pin<Box<T>>>
+            where
+                Pin<Box<T>>: IntoBox<A>,
+            {
+                self.remove::<Pin<Box<T>>>()
+            }
+
+            /// Removes the value for the unsized type `T` from the collection, returning it if
+            /// there was one or `None` if there was not. See
+            /// [`insert_unsized`][Map::insert_unsized] for details.
+            #[inline]
+            pub fn remove_unsized<T: ?Sized + 'static>(&mut self) -> Option<Box<T>>
+            where
+                Box<T>: IntoBox<A>,
+            {
+                self.remove::<Box<T>>()
+            }
+
+            /// Removes the `T` value from the collection, returning it in its existing box if there
+            /// was one or `None` if there was not.
+            #[inline]
+            pub fn remove_boxed<T: IntoBox<A>>(&mut self) -> Option<Box<T>> {
+                self.remove_raw(&TypeId::of::<T>())
+                    .map(|any| unsafe { any.downcast_unchecked::<T>() })
+            }
+
+            /// Removes the values stored in the collection for each of the types in the bundle
+            /// `B`, typically a tuple, returning those that there were.
+            ///
+            /// ```rust
+            #[doc = $example_init]
+            /// data.insert_bundle((1i32, "str"));
+            /// assert!(data.contains_all::<(i32, &str)>());
+            /// assert!(!data.contains_all::<(i32, &str, bool)>());
+            /// assert_eq!(data.remove_bundle::<(i32, &str, bool)>(), (Some(1), Some("str"), None));
+            /// assert!(data.is_empty());
+            /// ```
+            #[inline]
+            pub fn remove_bundle<B: Bundle<Self>>(&mut self) -> B::Displaced {
+                B::remove_from(self)
+            }
+
+            /// Returns true if the collection contains a value of type `T`.
+            #[inline]
+            pub fn contains<T: IntoBox<A>>(&self) -> bool {
+                self.raw.contains_key(&TypeId::of::<T>())
+            }
+
+            /// Returns true if the collection contains a value of every one of the types in the
+            /// bundle `B`, typically a tuple.
+            #[inline]
+            pub fn contains_all<B: Bundle<Self>>(&self) -> bool {
+                B::contained_in(self)
+            }
+
+            /// Returns a new collection with clones of just the values of the types in `B`, a
+            /// tuple, that this collection has.
+            ///
+            /// This is only available if the values can be cloned, e.g. for `Map<dyn CloneAny>`.
+            #[inline]
+            pub fn clone_subset<B: CloneSubset<Self>>(&self) -> Self {
+                B::clone_subset(self)
+            }
+
+            /// Returns an iterator over the `TypeId`s of values in this collection that are not
+            /// in `other`, in arbitrary order.
+            ///
+            /// `other` needn’t be the same variety of map, so you can compare, say, a
+            /// `Map<dyn CloneAny>` against an `AnyMap`.
+            #[inline]
+            pub fn difference<'a, B: ?Sized + Downcast, S2: BuildHasher>(
+                &'a self,
+                other: &'a Map<B, S2>,
+            ) -> Difference<'a, A, B, S2>
+            {
+                Difference {
+                    iter: self.raw.keys(),
+                    other: &other.raw,
+                }
+            }
+
+            /// Returns the changes that turn this collection into `other`: clones of the values
+            /// `other` has of types this doesn’t, of those this has of types `other` doesn’t, and
+            /// of both values for each type whose value differs.
+            ///
+            /// This is only available if the values can be both cloned and compared, e.g. for
+            /// `Map<dyn ClonePartialEqAny>`.
+            pub fn diff<S2: BuildHasher>(&self, other: &Map<A, S2>) -> crate::patch::MapPatch<A>
+            where
+                A: PartialEq,
+                Box<A>: Clone,
+            {
+                let mut patch = crate::patch::MapPatch::new();
+                for (type_id, ours) in &self.raw {
+                    match other.raw.get(type_id) {
+                        Some(theirs) if **ours == **theirs => (),
+                        Some(theirs) => patch.changed.push((ours.clone(), theirs.clone())),
+                        None => patch.removed.push(ours.clone()),
+                    }
+                }
+                patch.added.extend(other.raw.iter()
+                    .filter(|&(type_id, _)| !self.raw.contains_key(type_id))
+                    .map(|(_, theirs)| theirs.clone()));
+                patch
+            }
+
+            /// Applies `patch`, as made by [`diff`](Map::diff), adding, removing and replacing
+            /// values as it says.
+            ///
+            /// The patch is applied only if the collection is as it expects: with no value for each
+            /// type it adds, and with the patch’s old value for each type it removes or changes,
+            /// and only if the patch mentions each type just once. Otherwise, nothing is changed,
+            /// and the error lists the conflicts and hands the patch back.
+            pub fn apply(
+                &mut self,
+                patch: crate::patch::MapPatch<A>,
+            ) -> Result<(), crate::patch::PatchError<A>>
+            where
+                A: PartialEq,
+            {
+                use crate::patch::{Conflict, ConflictKind};
+                let mut conflicts = Vec::new();
+                let mut type_ids: Vec<TypeId> = patch.added.iter()
+                    .chain(&patch.removed)
+                    .chain(patch.changed.iter().map(|(old, _)| old))
+                    .map(|value| Downcast::type_id(&**value))
+                    .collect();
+                type_ids.sort_unstable();
+                for pair in type_ids.windows(2) {
+                    // Once for each type, however many times it comes up.
+                    let listed = conflicts.last().map(|conflict: &Conflict| conflict.type_id);
+                    if pair[0] == pair[1] && listed != Some(pair[0]) {
+                        conflicts.push(Conflict { type_id: pair[0], kind: ConflictKind::Duplicate });
+                    }
+                }
+                for value in &patch.added {
+                    let type_id = Downcast::type_id(&**value);
+                    if self.raw.contains_key(&type_id) {
+                        conflicts.push(Conflict { type_id, kind: ConflictKind::Present });
+                    }
+                }
+                let changed = patch.changed.iter().map(|(old, _)| old);
+                for old in patch.removed.iter().chain(changed) {
+                    let type_id = Downcast::type_id(&**old);
+                    let kind = match self.raw.get(&type_id) {
+                        None => ConflictKind::Missing,
+                        Some(current) if **current != **old => ConflictKind::Changed,
+                        Some(_) => continue,
+                    };
+                    conflicts.push(Conflict { type_id, kind });
+                }
+                if !conflicts.is_empty() {
+                    return Err(crate::patch::PatchError { patch, conflicts });
+                }
+                for old in patch.removed {
+                    let _ = self.remove_raw(&Downcast::type_id(&*old));
+                }
+                let changed = patch.changed.into_iter().map(|(_, new)| new);
+                for value in patch.added.into_iter().chain(changed) {
+                    let _ = self.raw.insert(Downcast::type_id(&*value), value);
+                }
+                Ok(())
+            }
+
+            /// Moves all values from `other` into this collection, calling `f` to decide what to
+            /// store for each type present in both.
+            ///
+            /// `f` receives the `TypeId` and both boxes, `self`’s first, and returns the box to
+            /// keep; it may return either of them, or a new value of the same type built from
+            /// them. Types only present in `other` are moved across untouched.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `f` returns a box holding a value of a different type from the one it
+            /// was given, since storing it would break the map’s invariants. If that happens, or
+            /// `f` itself panics, the collection is left with no value of that type, having
+            /// merged the types before it, and the rest of `other` is dropped.
+            pub fn merge_with<S2, F>(&mut self, other: Map<A, S2>, mut f: F)
+            where
+                F: FnMut(TypeId, Box<A>, Box<A>) -> Box<A>,
+            {
+                #[cfg(feature = "type-names")]
+                let names = other.names;
+                for (type_id, theirs) in other.raw {
+                    // Ours comes out along with its name, so that if `f` panics, the type is gone
+                    // altogether rather than leaving its name behind.
+                    #[cfg(feature = "type-names")]
+                    let name = self.names.remove(&type_id)
+                        .or_else(|| names.get(&type_id).copied());
+                    let value = match self.raw.remove(&type_id) {
+                        Some(ours) => {
+                            let merged = f(type_id, ours, theirs);
+                            assert!(
+                                Downcast::type_id(&*merged) == type_id,
+                                "merge_with closure returned a value of the wrong type",
+                            );
+                            merged
+                        },
+                        None => theirs,
+                    };
+                    #[cfg(feature = "type-names")]
+                    if let Some(name) = name {
+                        let _ = self.names.insert(type_id, name);
+                    }
+                    let _ = self.raw.insert(type_id, value);
+                }
+            }
+
+            /// Removes all values for which `f` returns true, returning them in a new collection.
+            ///
+            /// The values are moved across in their existing boxes, without reallocation.
+            pub fn split_off_with<F>(&mut self, mut f: F) -> Map<A, S>
+            where
+                F: FnMut(TypeId, &A) -> bool,
+                S: Clone,
+            {
+                let matching = self.raw.iter()
+                    .filter(|&(&type_id, value)| f(type_id, value))
+                    .map(|(&type_id, _)| type_id)
+                    .collect::<Vec<_>>();
+                let mut other = Map::with_capacity_and_hasher(matching.len(), self.hasher().clone());
+                for type_id in matching {
+                    if let Some(value) = self.raw.remove(&type_id) {
+                        let _ = other.raw.insert(type_id, value);
+                        #[cfg(feature = "type-names")]
+                        if let Some(name) = self.names.remove(&type_id) {
+                            let _ = other.names.insert(type_id, name);
+                        }
+                    }
+                }
+                other
+            }
+
+            #[inline]
+            fn record_type_name<T: 'static>(&mut self) {
+                #[cfg(feature = "type-names")]
+                let _ = self.names.insert(TypeId::of::<T>(), core::any::type_name::<T>());
+            }
+
+            // Removes the value with the given `TypeId` from `raw`, and forgets its type’s name.
+            #[inline]
+            fn remove_raw(&mut self, type_id: &TypeId) -> Option<Box<A>> {
+                #[cfg(feature = "type-names")]
+                let _ = self.names.remove(type_id);
+                self.raw.remove(type_id)
+            }
+
+            /// Returns the name of the type of the value in the collection with the given
+            /// `TypeId`, as given by `core::any::type_name`, if there is such a value and its name
+            /// is known.
+            ///
+            /// Names are recorded whenever a value is added under its static type, or with its
+            /// name by [`insert_dyn`](Map::insert_dyn), but not by [`apply`](Map::apply),
+            /// `Extend`, rayon’s `ParallelExtend` and `FromParallelIterator`, or
+            /// [`from_raw`](Map::from_raw), which only have a `Box<A>` to go by. A value replacing one of the same type keeps its
+            /// name, if that was known.
+            #[cfg(feature = "type-names")]
+            #[inline]
+            pub fn type_name_of(&self, type_id: &TypeId) -> Option<&'static str> {
+                if self.raw.contains_key(type_id) {
+                    self.names.get(type_id).copied()
+                } else {
+                    None
+                }
+            }
+
+            /// Returns an iterator over the `TypeId` of each value in the collection and, if it’s
+            /// known, the name of its type, in arbitrary order. See
+            /// [`type_name_of`](Map::type_name_of) for when names are known.
+            #[cfg(feature = "type-names")]
+            #[inline]
+            pub fn type_names(&self) -> TypeNames<'_, A> {
+                TypeNames {
+                    iter: self.raw.keys(),
+                    names: &self.names,
+                }
+            }
+
+            /// Gets the entry for the given type in the collection for in-place manipulation
+            #[inline]
+            pub fn entry<T: IntoBox<A>>(&mut self) -> Entry<'_, A, T, S> {
+                match self.raw.entry(TypeId::of::<T>()) {
+                    hash_map::Entry::Occupied(e) => Entry::Occupied(OccupiedEntry {
+                        inner: e,
+                        #[cfg(feature = "type-names")]
+                        names: &mut self.names,
+                        type_: PhantomData,
+                    }),
+                    hash_map::Entry::Vacant(e) => Entry::Vacant(VacantEntry {
+                        inner: e,
+                        #[cfg(feature = "type-names")]
+                        names: &mut self.names,
+                        type_: PhantomData,
+                    }),
+                }
+            }
+
+            /// Get access to the raw hash map that backs this.
+            ///
+            /// This will seldom be useful, but it’s conceivable that you could wish to iterate
+            /// over all the items in the collection, and this lets you do that.
+            #[inline]
+            pub fn as_raw(&self) -> &RawMap<A, S> {
+                &self.raw
+            }
+
+            /// Get safe mutable access to the raw hash map that backs this, for anything but
+            /// inserting: iterating mutably, removing, retaining, clearing and draining.
+            #[inline]
+            pub fn raw_mut_view(&mut self) -> RawMutView<'_, A, S> {
+                RawMutView {
+                    raw: &mut self.raw,
+                    #[cfg(feature = "type-names")]
+                    names: &mut self.names,
+                }
+            }
+
+            /// Get mutable access to the raw hash map that backs this.
+            ///
+            /// This is deprecated in favour of [`raw_mut_view`](Map::raw_mut_view), which does
+            /// safely everything but inserting. To insert many values at once, use `Extend`, or
+            /// [`into_raw`](Map::into_raw) and [`try_from_raw`](Map::try_from_raw).
+            ///
+            /// # Safety
+            ///
+            /// If you insert any values to the raw map, the key (a `TypeId`) must match the
+            /// value’s type, or *undefined behaviour* will occur when you access those values.
+            ///
+            /// (*Removing* entries is perfectly safe.)
+            #[deprecated(since = "1.0.0-beta.2", note = "use `raw_mut_view` instead")]
+            #[inline]
+            pub unsafe fn as_raw_mut(&mut self) -> &mut RawMap<A, S> {
+                &mut self.raw
+            }
+
+            /// Convert this into the raw hash map that backs this.
+            ///
+            /// This will seldom be useful, but it’s conceivable that you could wish to consume all
+            /// the items in the collection and do *something* with some or all of them, and this
+            /// lets you do that. (`.raw_mut_view().drain()` does the same but keeps the map.)
+            #[inline]
+            pub fn into_raw(self) -> RawMap<A, S> {
+                self.raw
+            }
+
+            /// Construct a map from a collection of raw values.
+            ///
+            /// You know what? I can’t immediately think of any legitimate use for this.
+            ///
+            /// Perhaps this will be most practical as `unsafe { Map::from_raw(iter.collect()) }`,
+            /// `iter` being an iterator over `(TypeId, Box<A>)` pairs. Eh, this method provides
+            /// symmetry with `into_raw`, so I don’t care if literally no one ever uses it. I’m not
+            /// even going to write a test for it, it’s so trivial.
+            ///
+            /// # Safety
+            ///
+            /// For all entries in the raw map, the key (a `TypeId`) must match the value’s type,
+            /// or *undefined behaviour* will occur when you access that entry.
+            #[inline]
+            pub unsafe fn from_raw(raw: RawMap<A, S>) -> Map<A, S> {
+                Self {
+                    raw,
+                    #[cfg(feature = "type-names")]
+                    names: Default::default(),
+                }
+            }
+
+            /// Construct a map from a raw map, checking that the key of each entry matches its
+            /// value’s type, which makes this the safe counterpart of
+            /// [`from_raw`](Map::from_raw).
+            ///
+            /// If any entry doesn’t match, the raw map is dropped and the first such entry found
+            /// (in arbitrary order) is described in the error.
+            #[inline]
+            pub fn try_from_raw(raw: RawMap<A, S>) -> Result<Map<A, S>, crate::RawMapError> {
+                for (&key, value) in &raw {
+                    let value_type_id = Downcast::type_id(&**value);
+                    if key != value_type_id {
+                        return Err(crate::RawMapError { key, value_type_id });
+                    }
+                }
+                // SAFETY: just checked.
+                Ok(unsafe { Map::from_raw(raw) })
+            }
+        }
+
+        for_each_tuple!(impl_fetch);
+        for_each_tuple!(impl_fetch_mut);
+        for_each_tuple!(impl_bundle);
+        for_each_tuple!(impl_clone_subset);
+        for_each_tuple!(impl_static_map_try_from);
+
+        impl_weaken!(coerce: dyn Any + Send => dyn Any);
+        impl_weaken!(coerce: dyn Any + Send + Sync => dyn Any, dyn Any + Send);
+        impl_weaken!(coerce: dyn crate::CloneAny + Send => dyn crate::CloneAny);
+        impl_weaken!(coerce: dyn crate::CloneAny + Send + Sync
+            => dyn crate::CloneAny, dyn crate::CloneAny + Send);
+        impl_weaken!(into_any: dyn crate::CloneAny => dyn Any);
+        impl_weaken!(into_any: dyn crate::CloneAny + Send => dyn Any, dyn Any + Send);
+        impl_weaken!(into_any: dyn crate::CloneAny + Send + Sync
+            => dyn Any, dyn Any + Send, dyn Any + Send + Sync);
+
+        #[cfg(feature = "serde")]
+        impl<A: ?Sized + Downcast + crate::serde::SerializeToAny, S: BuildHasher> Map<A, S> {
+            /// Returns the values in the collection, ready to serialize as a map from their
+            /// types’ stable names in `registry`; see the [`serde`](crate::serde) module.
+            #[inline]
+            pub fn serialize_with<'a>(
+                &'a self,
+                registry: &'a crate::serde::Registry<A>,
+            ) -> crate::serde::Serializable<'a, A> {
+                let values = self.raw.iter().map(|(&type_id, value)| (type_id, &**value)).collect();
+                crate::serde::Serializable::new(values, registry, false)
+            }
+
+            /// Returns the values in the collection, ready to serialize as a map from their
+            /// types’ tags in `registry`, as eight bytes each, rather than from their names. This
+            /// is more compact, for binary formats.
+            #[inline]
+            pub fn serialize_compact<'a>(
+                &'a self,
+                registry: &'a crate::serde::Registry<A>,
+            ) -> crate::serde::Serializable<'a, A> {
+                let values = self.raw.iter().map(|(&type_id, value)| (type_id, &**value)).collect();
+                crate::serde::Serializable::new(values, registry, true)
+            }
+
+            /// Serializes the collection with [postcard](https://docs.rs/postcard), a compact
+            /// binary format that works without `std`, keyed by its types’ tags in `registry`,
+            /// as by [`serialize_compact`](Map::serialize_compact).
+            #[cfg(feature = "postcard")]
+            #[inline]
+            pub fn to_postcard(
+                &self,
+                registry: &crate::serde::Registry<A>,
+            ) -> Result<Vec<u8>, postcard::Error> {
+                postcard::to_allocvec(&self.serialize_compact(registry))
+            }
+
+            /// Converts the collection to a JSON object from its types’ stable names in
+            /// `registry` to their values, as by [`serialize_with`](Map::serialize_with), for
+            /// showing or editing by hand.
+            #[cfg(feature = "serde_json")]
+            #[inline]
+            pub fn to_json_value(
+                &self,
+                registry: &crate::serde::Registry<A>,
+            ) -> Result<::serde_json::Value, ::serde_json::Error> {
+                ::serde_json::to_value(self.serialize_with(registry))
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<A: ?Sized + Downcast, S: BuildHasher> Map<A, S> {
+            /// Deserializes a collection from a map from stable names to values, as serialized
+            /// by [`serialize_with`](Map::serialize_with), looking the names up in `registry`.
+            ///
+            /// This fails if a name isn’t registered, if its type was registered without a way
+            /// to deserialize it, or if the same name appears twice.
+            #[inline]
+            pub fn deserialize<'de, D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+                registry: &crate::serde::Registry<A>,
+            ) -> Result<Map<A, S>, D::Error>
+            where
+                S: Default,
+            {
+                let values = crate::serde::deserialize_values(deserializer, registry, false)?;
+                let mut map = Map::with_capacity_and_hasher(values.len(), S::default());
+                map.extend(values);
+                Ok(map)
+            }
+
+            /// Deserializes a collection from a map from tags to values, as serialized by
+            /// [`serialize_compact`](Map::serialize_compact), looking the tags up in `registry`.
+            ///
+            /// This fails in the same ways as [`deserialize`](Map::deserialize).
+            #[inline]
+            pub fn deserialize_compact<'de, D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+                registry: &crate::serde::Registry<A>,
+            ) -> Result<Map<A, S>, D::Error>
+            where
+                S: Default,
+            {
+                let values = crate::serde::deserialize_values(deserializer, registry, true)?;
+                let mut map = Map::with_capacity_and_hasher(values.len(), S::default());
+                map.extend(values);
+                Ok(map)
+            }
+
+            /// Deserializes a collection from [postcard](https://docs.rs/postcard), as serialized
+            /// by [`to_postcard`](Map::to_postcard), looking the tags up in `registry`.
+            #[cfg(feature = "postcard")]
+            #[inline]
+            pub fn from_postcard(
+                bytes: &[u8],
+                registry: &crate::serde::Registry<A>,
+            ) -> Result<Map<A, S>, postcard::Error>
+            where
+                S: Default,
+            {
+                Map::deserialize_compact(&mut postcard::Deserializer::from_bytes(bytes), registry)
+            }
+
+            /// Converts a JSON object from stable names to values, as returned by
+            /// [`to_json_value`](Map::to_json_value), back to a collection, looking the names up
+            /// in `registry`.
+            ///
+            /// This fails in the same ways as [`deserialize`](Map::deserialize), and if `value`
+            /// isn’t an object.
+            #[cfg(feature = "serde_json")]
+            #[inline]
+            pub fn from_json_value(
+                value: ::serde_json::Value,
+                registry: &crate::serde::Registry<A>,
+            ) -> Result<Map<A, S>, ::serde_json::Error>
+            where
+                S: Default,
+            {
+                Map::deserialize(value, registry)
+            }
+
+            /// Serializes the collection as a sequence of its values, each tagged with its type
+            /// by `A`’s own `Serialize` implementation, in arbitrary order.
+            ///
+            /// This is for trait objects that serialize with their type, as
+            /// [typetag](https://docs.rs/typetag) makes them: for these there’s no need for a
+            /// [`Registry`](crate::serde::Registry), since every type implementing the trait is
+            /// registered by being annotated. It has the signature serde’s `serialize_with`
+            /// attribute asks for, so that a `Map` field can be serialized this way.
+            ///
+            /// ```rust
+            /// use anymap::Map;
+            ///
+            /// #[typetag::serde]
+            /// trait Component: core::any::Any {}
+            /// anymap::impl_any_bound!(Component);
+            ///
+            /// #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+            /// struct Health(u32);
+            /// #[typetag::serde]
+            /// impl Component for Health {}
+            ///
+            /// #[derive(serde::Serialize, serde::Deserialize)]
+            /// struct Entity {
+            ///     #[serde(
+            ///         serialize_with = "Map::serialize_tagged",
+            ///         deserialize_with = "Map::deserialize_tagged",
+            ///     )]
+            ///     components: Map<dyn Component>,
+            /// }
+            ///
+            /// let mut entity = Entity { components: Map::new() };
+            /// entity.components.insert(Health(10));
+            /// let json = serde_json::to_string(&entity).unwrap();
+            /// assert_eq!(json, r#"{"components":[{"Health":10}]}"#);
+            ///
+            /// let entity: Entity = serde_json::from_str(&json).unwrap();
+            /// assert_eq!(entity.components.get::<Health>(), Some(&Health(10)));
+            /// ```
+            #[inline]
+            pub fn serialize_tagged<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+            where
+                A: ::serde::Serialize,
+                Ser: ::serde::Serializer,
+            {
+                serializer.collect_seq(self.raw.values())
+            }
+
+            /// Deserializes a collection from a sequence of values, each tagged with its type,
+            /// as serialized by [`serialize_tagged`](Map::serialize_tagged), with `Box<A>`’s own
+            /// `Deserialize` implementation.
+            ///
+            /// This fails if two of the values are of the same type.
+            pub fn deserialize_tagged<'de, D>(deserializer: D) -> Result<Map<A, S>, D::Error>
+            where
+                Box<A>: ::serde::Deserialize<'de>,
+                S: Default,
+                D: ::serde::Deserializer<'de>,
+            {
+                let values = <Vec<Box<A>> as ::serde::Deserialize>::deserialize(deserializer)?;
+                let mut map = Map::with_capacity_and_hasher(values.len(), S::default());
+                for value in values {
+                    if map.raw.insert(Downcast::type_id(&*value), value).is_some() {
+                        return Err(::serde::de::Error::custom("duplicate entry of one type"));
+                    }
+                }
+                Ok(map)
+            }
+        }
+
+        #[cfg(feature = "reflect")]
+        impl<S: BuildHasher> Map<dyn bevy_reflect::Reflect, S> {
+            /// Returns a reference to the value of the type with the given `TypeId`, if it
+            /// exists, for inspecting by reflection without knowing the type.
+            #[inline]
+            pub fn get_reflect(&self, type_id: TypeId) -> Option<&dyn bevy_reflect::Reflect> {
+                self.raw.get(&type_id).map(|value| &**value)
+            }
+
+            /// Returns a mutable reference to the value of the type with the given `TypeId`, if
+            /// it exists, for editing by reflection without knowing the type.
+            #[inline]
+            pub fn get_reflect_mut(
+                &mut self,
+                type_id: TypeId,
+            ) -> Option<&mut dyn bevy_reflect::Reflect> {
+                self.raw.get_mut(&type_id).map(|value| &mut **value)
+            }
+
+            /// Returns a reference to the part of the value of the type with the given `TypeId`
+            /// that the reflection path `path` leads to, such as `"size.width"`.
+            #[inline]
+            pub fn reflect_path<'p>(
+                &self,
+                type_id: TypeId,
+                path: &'p str,
+            ) -> Result<&dyn bevy_reflect::PartialReflect, crate::reflect::ReflectError<'p>> {
+                use bevy_reflect::GetPath;
+                self.get_reflect(type_id)
+                    .ok_or(crate::reflect::ReflectError::Missing(type_id))?
+                    .reflect_path(path)
+                    .map_err(crate::reflect::ReflectError::Path)
+            }
+
+            /// Returns a mutable reference to the part of the value of the type with the given
+            /// `TypeId` that the reflection path `path` leads to, such as `"size.width"`.
+            #[inline]
+            pub fn reflect_path_mut<'p>(
+                &mut self,
+                type_id: TypeId,
+                path: &'p str,
+            ) -> Result<&mut dyn bevy_reflect::PartialReflect, crate::reflect::ReflectError<'p>>
+            {
+                use bevy_reflect::GetPath;
+                self.get_reflect_mut(type_id)
+                    .ok_or(crate::reflect::ReflectError::Missing(type_id))?
+                    .reflect_path_mut(path)
+                    .map_err(crate::reflect::ReflectError::Path)
+            }
+
+            /// Applies `value` to the value in the collection of the type it represents, as
+            /// `PartialReflect::try_apply` does, updating only the fields `value` has if it’s a
+            /// dynamic type such as `DynamicStruct`.
+            ///
+            /// This fails if `value` doesn’t say what type it represents, if the collection has
+            /// no value of that type, or if `value` doesn’t fit it. The value may have been
+            /// partly updated in the last case, as with `try_apply`.
+            pub fn try_apply(
+                &mut self,
+                value: &dyn bevy_reflect::PartialReflect,
+            ) -> Result<(), crate::reflect::ReflectError<'static>> {
+                let type_id = value.get_represented_type_info()
+                    .ok_or(crate::reflect::ReflectError::Unrepresented)?
+                    .type_id();
+                self.get_reflect_mut(type_id)
+                    .ok_or(crate::reflect::ReflectError::Missing(type_id))?
+                    .try_apply(value)
+                    .map_err(crate::reflect::ReflectError::Apply)
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> Extend<Box<A>> for Map<A, S> {
+            #[inline]
+            fn extend<T: IntoIterator<Item = Box<A>>>(&mut self, iter: T) {
+                for item in iter {
+                    let _ = self.raw.insert(Downcast::type_id(&*item), item);
+                }
+            }
+        }
+
+        /// As with `Extend`, if several values have the same type, the one latest in the
+        /// iterator’s order wins, however the work was split between threads.
+        #[cfg(feature = "rayon")]
+        impl<A: ?Sized + Downcast + Send, S> rayon::iter::ParallelExtend<Box<A>> for Map<A, S>
+        where
+            RawMap<A, S>: rayon::iter::ParallelExtend<(TypeId, Box<A>)>,
+        {
+            #[inline]
+            fn par_extend<I>(&mut self, par_iter: I)
+            where
+                I: rayon::iter::IntoParallelIterator<Item = Box<A>>,
+            {
+                use rayon::iter::ParallelIterator;
+                self.raw.par_extend(par_iter.into_par_iter().map(|item| {
+                    (Downcast::type_id(&*item), item)
+                }))
+            }
+        }
+
+        /// If several values have the same type, the one latest in the iterator’s order wins.
+        #[cfg(feature = "rayon")]
+        impl<A: ?Sized + Downcast + Send, S: Default> rayon::iter::FromParallelIterator<Box<A>>
+            for Map<A, S>
+        where
+            Map<A, S>: rayon::iter::ParallelExtend<Box<A>>,
+        {
+            #[inline]
+            fn from_par_iter<I>(par_iter: I) -> Map<A, S>
+            where
+                I: rayon::iter::IntoParallelIterator<Item = Box<A>>,
+            {
+                let mut map = Map::with_hasher(S::default());
+                rayon::iter::ParallelExtend::par_extend(&mut map, par_iter);
+                map
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher + Default, L: Bundle<Map<A, S>>>
+            From<crate::static_map::StaticMap<L>> for Map<A, S>
+        {
+            #[inline]
+            fn from(map: crate::static_map::StaticMap<L>) -> Map<A, S> {
+                let mut new = Map::default();
+                let _ = map.into_inner().insert_into(&mut new);
+                new
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher + Default> From<crate::tiny::TinyMap<A>>
+            for Map<A, S>
+        {
+            #[inline]
+            fn from(map: crate::tiny::TinyMap<A>) -> Map<A, S> {
+                Map {
+                    raw: map.into_raw().into_iter().collect(),
+                    #[cfg(feature = "type-names")]
+                    names: Default::default(),
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S> From<Map<A, S>> for crate::tiny::TinyMap<A> {
+            #[inline]
+            fn from(map: Map<A, S>) -> crate::tiny::TinyMap<A> {
+                // SAFETY: the keys came from a map, so they match their values and are distinct.
+                unsafe { crate::tiny::TinyMap::from_raw(map.raw.into_iter().collect()) }
+            }
+        }
+
+        impl_builder!();
+
+        impl_bounded_map!();
+
+        impl_tracked_map!();
+
+        impl_stable_map!();
+
+        impl_chain_map!();
+
+        impl_scoped_map!();
+
+        /// An iterator over the `TypeId`s of values in one map but not another.
+        ///
+        /// This is created by [`Map::difference`].
+        pub struct Difference<
+            'a,
+            A: ?Sized + Downcast,
+            B: ?Sized + Downcast,
+            S = BuildHasherDefault<TypeIdHasher>,
+        > {
+            iter: hash_map::Keys<'a, TypeId, Box<A>>,
+            other: &'a RawMap<B, S>,
+        }
+
+        impl<'a, A: ?Sized + Downcast, B: ?Sized + Downcast, S: BuildHasher> Iterator
+            for Difference<'a, A, B, S>
+        {
+            type Item = TypeId;
+
+            #[inline]
+            fn next(&mut self) -> Option<TypeId> {
+                let other = self.other;
+                self.iter.by_ref().find(|type_id| !other.contains_key(type_id)).copied()
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (0, self.iter.size_hint().1)
+            }
+        }
+
+        /// An iterator over the `TypeId` of each value in a map and the name of its type.
+        ///
+        /// This is created by [`Map::type_names`].
+        #[cfg(feature = "type-names")]
+        pub struct TypeNames<'a, A: ?Sized + Downcast> {
+            iter: hash_map::Keys<'a, TypeId, Box<A>>,
+            names: &'a NameTable,
+        }
+
+        #[cfg(feature = "type-names")]
+        impl<'a, A: ?Sized + Downcast> Iterator for TypeNames<'a, A> {
+            type Item = (TypeId, Option<&'static str>);
+
+            #[inline]
+            fn next(&mut self) -> Option<(TypeId, Option<&'static str>)> {
+                let names = self.names;
+                self.iter.next().map(|&type_id| (type_id, names.get(&type_id).copied()))
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.iter.size_hint()
+            }
+        }
+
+        #[cfg(feature = "type-names")]
+        impl<'a, A: ?Sized + Downcast> ExactSizeIterator for TypeNames<'a, A> { }
+
+        /// Mutable access to the raw hash map that backs a `Map`, without the ability to insert,
+        /// which is what makes it safe.
+        ///
+        /// This is created by [`Map::raw_mut_view`]. The values are handed out as `&mut A` rather
+        /// than `&mut Box<A>`, so that they can’t be swapped for a value of a different type.
+        pub struct RawMutView<'a, A: ?Sized + Downcast, S = BuildHasherDefault<TypeIdHasher>> {
+            raw: &'a mut RawMap<A, S>,
+            #[cfg(feature = "type-names")]
+            names: &'a mut NameTable,
+        }
+
+        impl<'a, A: ?Sized + Downcast, S: BuildHasher> RawMutView<'a, A, S> {
+            /// Returns the raw hash map, to read.
+            #[inline]
+            pub fn as_raw(&self) -> &RawMap<A, S> {
+                self.raw
+            }
+
+            /// Returns the number of items in the collection.
+            #[inline]
+            pub fn len(&self) -> usize {
+                self.raw.len()
+            }
+
+            /// Returns true if there are no items in the collection.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.raw.is_empty()
+            }
+
+            /// Returns a mutable reference to the value with the given `TypeId`, if it exists.
+            #[inline]
+            pub fn get_mut(&mut self, type_id: &TypeId) -> Option<&mut A> {
+                self.raw.get_mut(type_id).map(|value| &mut **value)
+            }
+
+            /// Returns an iterator over the `TypeId` and a mutable reference to each value, in
+            /// arbitrary order.
+            #[inline]
+            pub fn iter_mut(&mut self) -> RawIterMut<'_, A> {
+                RawIterMut {
+                    iter: self.raw.iter_mut(),
+                }
+            }
+
+            /// Removes the value with the given `TypeId`, returning it if there was one.
+            #[inline]
+            pub fn remove(&mut self, type_id: &TypeId) -> Option<Box<A>> {
+                #[cfg(feature = "type-names")]
+                let _ = self.names.remove(type_id);
+                self.raw.remove(type_id)
+            }
+
+            /// Retains only the values for which `f` returns true.
+            #[inline]
+            pub fn retain<F: FnMut(&TypeId, &mut A) -> bool>(&mut self, mut f: F) {
+                #[cfg(feature = "type-names")]
+                let names = &mut *self.names;
+                self.raw.retain(|type_id, value| {
+                    let keep = f(type_id, &mut **value);
+                    #[cfg(feature = "type-names")]
+                    if !keep {
+                        let _ = names.remove(type_id);
+                    }
+                    keep
+                })
+            }
+
+            /// Removes all items from the collection. Keeps the allocated memory for reuse.
+            #[inline]
+            pub fn clear(&mut self) {
+                self.raw.clear();
+                #[cfg(feature = "type-names")]
+                self.names.clear();
+            }
+
+            /// Clears the collection, returning all the values in an iterator. Keeps the allocated
+            /// memory for reuse.
+            #[inline]
+            pub fn drain(&mut self) -> hash_map::Drain<'_, TypeId, Box<A>> {
+                #[cfg(feature = "type-names")]
+                self.names.clear();
+                self.raw.drain()
+            }
+        }
+
+        /// An iterator over the `TypeId` and a mutable reference to each value in a map.
+        ///
+        /// This is created by [`RawMutView::iter_mut`].
+        pub struct RawIterMut<'a, A: ?Sized + Downcast> {
+            iter: hash_map::IterMut<'a, TypeId, Box<A>>,
+        }
+
+        impl<'a, A: ?Sized + Downcast> Iterator for RawIterMut<'a, A> {
+            type Item = (TypeId, &'a mut A);
+
+            #[inline]
+            fn next(&mut self) -> Option<(TypeId, &'a mut A)> {
+                self.iter.next().map(|(&type_id, value)| (type_id, &mut **value))
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.iter.size_hint()
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast> ExactSizeIterator for RawIterMut<'a, A> { }
+
+        /// An iterator over references to the values in a map.
+        ///
+        /// This is created by [`Map::values`].
+        pub struct Values<'a, A: ?Sized + Downcast> {
+            iter: hash_map::Values<'a, TypeId, Box<A>>,
+        }
+
+        impl<'a, A: ?Sized + Downcast> Iterator for Values<'a, A> {
+            type Item = &'a A;
+
+            #[inline]
+            fn next(&mut self) -> Option<&'a A> {
+                self.iter.next().map(|value| &**value)
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.iter.size_hint()
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast> ExactSizeIterator for Values<'a, A> { }
+
+        /// An iterator over mutable references to the values in a map.
+        ///
+        /// This is created by [`Map::values_mut`].
+        pub struct ValuesMut<'a, A: ?Sized + Downcast> {
+            iter: hash_map::ValuesMut<'a, TypeId, Box<A>>,
+        }
+
+        impl<'a, A: ?Sized + Downcast> Iterator for ValuesMut<'a, A> {
+            type Item = &'a mut A;
+
+            #[inline]
+            fn next(&mut self) -> Option<&'a mut A> {
+                self.iter.next().map(|value| &mut **value)
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.iter.size_hint()
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast> ExactSizeIterator for ValuesMut<'a, A> { }
+
+        /// An iterator over the `TypeId` and box `Layout` of each value in a map.
+        ///
+        /// This is created by [`Map::entry_layouts`].
+        pub struct EntryLayouts<'a, A: ?Sized + Downcast> {
+            iter: hash_map::Iter<'a, TypeId, Box<A>>,
+        }
+
+        impl<'a, A: ?Sized + Downcast> Iterator for EntryLayouts<'a, A> {
+            type Item = (TypeId, core::alloc::Layout);
+
+            #[inline]
+            fn next(&mut self) -> Option<(TypeId, core::alloc::Layout)> {
+                self.iter.next()
+                    .map(|(&type_id, value)| (type_id, core::alloc::Layout::for_value(&**value)))
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.iter.size_hint()
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast> ExactSizeIterator for EntryLayouts<'a, A> { }
+
+        /// A view into a single occupied location in an `Map`.
+        pub struct OccupiedEntry<
+            'a,
+            A: ?Sized + Downcast,
+            V: 'a,
+            S: 'a = BuildHasherDefault<TypeIdHasher>,
+        > {
+            inner: hash_map::OccupiedEntry<'a, TypeId, Box<A>, $($entry_generics)?>,
+            #[cfg(feature = "type-names")]
+            names: &'a mut NameTable,
+            type_: PhantomData<(V, &'a mut S)>,
+        }
+
+        /// A view into a single empty location in an `Map`.
+        pub struct VacantEntry<
+            'a,
+            A: ?Sized + Downcast,
+            V: 'a,
+            S: 'a = BuildHasherDefault<TypeIdHasher>,
+        > {
+            inner: hash_map::VacantEntry<'a, TypeId, Box<A>, $($entry_generics)?>,
+            #[cfg(feature = "type-names")]
+            names: &'a mut NameTable,
+            type_: PhantomData<(V, &'a mut S)>,
+        }
+
+        /// A view into a single location in an `Map`, which may be vacant or occupied.
+        pub enum Entry<'a, A: ?Sized + Downcast, V: 'a, S: 'a = BuildHasherDefault<TypeIdHasher>> {
+            /// An occupied Entry
+            Occupied(OccupiedEntry<'a, A, V, S>),
+            /// A vacant Entry
+            Vacant(VacantEntry<'a, A, V, S>),
+        }
+
+        /// The error returned by [`Map::try_insert`] when the collection already had a value of
+        /// the type.
+        ///
+        /// Contains the occupied entry, and the value that was not inserted.
+        pub struct OccupiedError<
+            'a,
+            A: ?Sized + Downcast,
+            V: 'a,
+            S: 'a = BuildHasherDefault<TypeIdHasher>,
+        > {
+            /// The entry in the collection that was already occupied.
+            pub entry: OccupiedEntry<'a, A, V, S>,
+            /// The value which was not inserted, because the entry was already occupied.
+            pub value: V,
+        }
+
+        impl<'a, A: ?Sized + Downcast, V: IntoBox<A> + fmt::Debug, S> fmt::Debug
+            for OccupiedError<'a, A, V, S>
+        {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_struct("OccupiedError")
+                    .field("old_value", self.entry.get())
+                    .field("new_value", &self.value)
+                    .finish()
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast, V: IntoBox<A> + fmt::Debug, S> fmt::Display
+            for OccupiedError<'a, A, V, S>
+        {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "failed to insert {:?}, a value of this type already exists: {:?}",
+                    self.value,
+                    self.entry.get(),
+                )
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl<'a, A: ?Sized + Downcast, V: IntoBox<A> + fmt::Debug, S> std::error::Error
+            for OccupiedError<'a, A, V, S> { }
+
+        impl<'a, A: ?Sized + Downcast, V: IntoBox<A>, S: BuildHasher> Entry<'a, A, V, S> {
+            /// Ensures a value is in the entry by inserting the default if empty, and returns
+            /// a mutable reference to the value in the entry.
+            #[inline]
+            pub fn or_insert(self, default: V) -> &'a mut V {
+                match self {
+                    Entry::Occupied(inner) => inner.into_mut(),
+                    Entry::Vacant(inner) => inner.insert(default),
+                }
+            }
+
+            /// Ensures a value is in the entry by inserting the result of the default function if
+            /// empty, and returns a mutable reference to the value in the entry.
+            #[inline]
+            pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+                match self {
+                    Entry::Occupied(inner) => inner.into_mut(),
+                    Entry::Vacant(inner) => inner.insert(default()),
+                }
+            }
+
+            /// Ensures a value is in the entry by inserting the result of the default function if
+            /// empty and it succeeds, and returns a mutable reference to the value in the entry,
+            /// or the error from the default function.
+            #[inline]
+            pub fn or_try_insert_with<E, F: FnOnce() -> Result<V, E>>(self, default: F)
+                -> Result<&'a mut V, E>
+            {
+                match self {
+                    Entry::Occupied(inner) => Ok(inner.into_mut()),
+                    Entry::Vacant(inner) => Ok(inner.insert(default()?)),
+                }
+            }
+
+            /// Ensures a value is in the entry by inserting, if empty, the result of the default
+            /// function, which is given the entry’s key, and returns a mutable reference to the
+            /// value in the entry.
+            #[inline]
+            pub fn or_insert_with_key<F: FnOnce(&TypeId) -> V>(self, default: F) -> &'a mut V {
+                match self {
+                    Entry::Occupied(inner) => inner.into_mut(),
+                    Entry::Vacant(inner) => {
+                        let value = default(inner.inner.key());
+                        inner.insert(value)
+                    },
+                }
+            }
+
+            /// Ensures a value is in the entry by inserting the default value if empty,
+            /// and returns a mutable reference to the value in the entry.
+            #[inline]
+            pub fn or_default(self) -> &'a mut V where V: Default {
+                match self {
+                    Entry::Occupied(inner) => inner.into_mut(),
+                    Entry::Vacant(inner) => inner.insert(Default::default()),
+                }
+            }
+
+            /// Provides in-place mutable access to an occupied entry before any potential inserts
+            /// into the map.
+            #[inline]
+            pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+                match self {
+                    Entry::Occupied(mut inner) => {
+                        f(inner.get_mut());
+                        Entry::Occupied(inner)
+                    },
+                    Entry::Vacant(inner) => Entry::Vacant(inner),
+                }
+            }
+
+            /// Sets the value of the entry, and returns an `OccupiedEntry`.
+            #[inline]
+            pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, A, V, S> {
+                match self {
+                    Entry::Occupied(mut inner) => {
+                        let _ = inner.insert(value);
+                        inner
+                    },
+                    Entry::Vacant(inner) => inner.insert_entry(value),
+                }
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast, V: IntoBox<A>, S> OccupiedEntry<'a, A, V, S> {
+            /// Gets a reference to the key in the entry, the `TypeId` of `V`
+            #[inline]
+            pub fn key(&self) -> &TypeId {
+                self.inner.key()
+            }
+
+            /// Gets the name of the type of the entry, as given by `core::any::type_name`
+            #[inline]
+            pub fn type_name(&self) -> &'static str {
+                core::any::type_name::<V>()
+            }
+
+            /// Gets a reference to the value in the entry
+            #[inline]
+            pub fn get(&self) -> &V {
+                unsafe { self.inner.get().downcast_ref_unchecked() }
+            }
+
+            /// Gets a mutable reference to the value in the entry
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut V {
+                unsafe { self.inner.get_mut().downcast_mut_unchecked() }
+            }
+
+            /// Converts the OccupiedEntry into a mutable reference to the value in the entry
+            /// with a lifetime bound to the collection itself
+            #[inline]
+            pub fn into_mut(self) -> &'a mut V {
+                unsafe { self.inner.into_mut().downcast_mut_unchecked() }
+            }
+
+            /// Sets the value of the entry, and returns the entry's old value
+            #[inline]
+            pub fn insert(&mut self, value: V) -> V {
+                #[cfg(feature = "type-names")]
+                let _ = self.names.insert(TypeId::of::<V>(), core::any::type_name::<V>());
+                unsafe { *self.inner.insert(value.into_box()).downcast_unchecked() }
+            }
+
+            /// Takes the value out of the entry, and returns it
+            #[inline]
+            pub fn remove(self) -> V {
+                self.remove_entry().1
+            }
+
+            /// Takes the key and value out of the entry, and returns them
+            #[inline]
+            pub fn remove_entry(self) -> (TypeId, V) {
+                #[cfg(feature = "type-names")]
+                let _ = self.names.remove(self.inner.key());
+                let (key, value) = self.inner.remove_entry();
+                (key, unsafe { *value.downcast_unchecked() })
+            }
+        }
+
+        impl<'a, A: ?Sized + Downcast, V: IntoBox<A>, S: BuildHasher> VacantEntry<'a, A, V, S> {
+            /// Gets a reference to the key that would be used when inserting a value through the
+            /// VacantEntry, the `TypeId` of `V`
+            #[inline]
+            pub fn key(&self) -> &TypeId {
+                self.inner.key()
+            }
+
+            /// Gets the name of the type of the entry, as given by `core::any::type_name`
+            #[inline]
+            pub fn type_name(&self) -> &'static str {
+                core::any::type_name::<V>()
+            }
+
+            /// Sets the value of the entry with the VacantEntry's key,
+            /// and returns a mutable reference to it
+            #[inline]
+            pub fn insert(self, value: V) -> &'a mut V {
+                self.insert_entry(value).into_mut()
+            }
+
+            /// Sets the value of the entry with the VacantEntry's key,
+            /// and returns an `OccupiedEntry`
+            #[inline]
+            pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, A, V, S> {
+                #[cfg(feature = "type-names")]
+                let _ = self.names.insert(TypeId::of::<V>(), core::any::type_name::<V>());
+                OccupiedEntry {
+                    inner: InsertEntry::insert_entry(self.inner, value.into_box()),
+                    #[cfg(feature = "type-names")]
+                    names: self.names,
+                    type_: PhantomData,
+                }
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use crate::CloneAny;
+            use super::*;
+            #[cfg(not(feature = "std"))]
+            use alloc::{vec, vec::Vec};
+
+            #[derive(Clone, Debug, PartialEq, Hash)] struct A(i32);
+            #[derive(Clone, Debug, PartialEq, Hash)] struct B(i32);
+            #[derive(Clone, Debug, PartialEq, Hash)] struct C(i32);
+            #[derive(Clone, Debug, PartialEq, Hash)] struct D(i32);
+            #[derive(Clone, Debug, PartialEq, Hash)] struct E(i32);
+            #[derive(Clone, Debug, PartialEq, Hash)] struct F(i32);
+            #[derive(Clone, Debug, PartialEq, Hash)] struct J(i32);
+
+            macro_rules! test_entry {
+                ($name:ident, $init:ty) => {
+                    #[test]
+                    fn $name() {
+                        let mut map = <$init>::new();
+                        assert_eq!(map.insert(A(10)), None);
+                        assert_eq!(map.insert(B(20)), None);
+                        assert_eq!(map.insert(C(30)), None);
+                        assert_eq!(map.insert(D(40)), None);
+                        assert_eq!(map.insert(E(50)), None);
+                        assert_eq!(map.insert(F(60)), None);
+
+                        // Existing key (insert)
+                        match map.entry::<A>() {
+                            Entry::Vacant(_) => unreachable!(),
+                            Entry::Occupied(mut view) => {
+                                assert_eq!(view.get(), &A(10));
+                                assert_eq!(view.insert(A(100)), A(10));
+                            }
+                        }
+                        assert_eq!(map.get::<A>().unwrap(), &A(100));
+                        assert_eq!(map.len(), 6);
+
+
+                        // Existing key (update)
+                        match map.entry::<B>() {
+                            Entry::Vacant(_) => unreachable!(),
+                            Entry::Occupied(mut view) => {
+                                let v = view.get_mut();
+                                let new_v = B(v.0 * 10);
+                                *v = new_v;
+                            }
+                        }
+                        assert_eq!(map.get::<B>().unwrap(), &B(200));
+                        assert_eq!(map.len(), 6);
+
+
+                        // Existing key (remove)
+                        match map.entry::<C>() {
+                            Entry::Vacant(_) => unreachable!(),
+                            Entry::Occupied(view) => {
+                                assert_eq!(view.remove(), C(30));
+                            }
+                        }
+                        assert_eq!(map.get::<C>(), None);
+                        assert_eq!(map.len(), 5);
+
+                        // Existing key (remove_entry)
+                        match map.entry::<E>() {
+                            Entry::Vacant(_) => unreachable!(),
+                            Entry::Occupied(view) => {
+                                assert_eq!(view.key(), &TypeId::of::<E>());
+                                assert_eq!(view.type_name(), core::any::type_name::<E>());
+                                assert_eq!(view.remove_entry(), (TypeId::of::<E>(), E(50)));
+                            }
+                        }
+                        assert_eq!(map.get::<E>(), None);
+                        assert_eq!(map.len(), 4);
+                        match map.entry::<E>() {
+                            Entry::Occupied(_) => unreachable!(),
+                            Entry::Vacant(view) => {
+                                assert_eq!(view.key(), &TypeId::of::<E>());
+                                assert_eq!(view.type_name(), core::any::type_name::<E>());
+                                assert_eq!(*view.insert(E(50)), E(50));
+                            }
+                        }
+                        assert_eq!(map.len(), 5);
+
+
+                        // Inexistent key (insert)
+                        match map.entry::<J>() {
+                            Entry::Occupied(_) => unreachable!(),
+                            Entry::Vacant(view) => {
+                                assert_eq!(*view.insert(J(1000)), J(1000));
+                            }
+                        }
+                        assert_eq!(map.get::<J>().unwrap(), &J(1000));
+                        assert_eq!(map.len(), 6);
+
+                        // Entry.insert_entry on existing and nonexisting keys
+                        let mut view = map.entry::<J>().insert_entry(J(2000));
+                        assert_eq!(view.get(), &J(2000));
+                        view.get_mut().0 += 1;
+                        assert_eq!(view.remove(), J(2001));
+                        let view = map.entry::<J>().insert_entry(J(3000));
+                        assert_eq!(view.into_mut(), &J(3000));
+                        assert_eq!(map.len(), 6);
+
+                        // Entry.or_insert on existing key
+                        map.entry::<B>().or_insert(B(71)).0 += 1;
+                        assert_eq!(map.get::<B>().unwrap(), &B(201));
+                        assert_eq!(map.len(), 6);
+
+                        // Entry.or_insert on nonexisting key
+                        map.entry::<C>().or_insert(C(300)).0 += 1;
+                        assert_eq!(map.get::<C>().unwrap(), &C(301));
+                        assert_eq!(map.len(), 7);
+
+                        // Entry.or_insert_with_key on existing and nonexisting keys
+                        map.entry::<C>().or_insert_with_key(|_| unreachable!()).0 += 1;
+                        assert_eq!(map.get::<C>().unwrap(), &C(302));
+                        assert_eq!(map.remove::<D>(), Some(D(40)));
+                        *map.entry::<D>().or_insert_with_key(|type_id| {
+                            assert_eq!(*type_id, TypeId::of::<D>());
+                            D(400)
+                        }) = D(401);
+                        assert_eq!(map.get::<D>().unwrap(), &D(401));
+                        assert_eq!(map.len(), 7);
+
+                        // Entry.or_try_insert_with on existing and nonexisting keys
+                        assert_eq!(map.entry::<D>().or_try_insert_with(|| Err(())), Ok(&mut D(401)));
+                        assert_eq!(map.remove::<D>(), Some(D(401)));
+                        assert_eq!(map.entry::<D>().or_try_insert_with(|| Err("nope")), Err("nope"));
+                        assert_eq!(map.get::<D>(), None);
+                        assert_eq!(map.entry::<D>().or_try_insert_with(|| Ok::<_, ()>(D(402))), Ok(&mut D(402)));
+                        assert_eq!(map.len(), 7);
+                    }
+                }
+            }
+
+            test_entry!(test_entry_any, AnyMap);
+            test_entry!(test_entry_cloneany, Map<dyn CloneAny>);
+
+            #[test]
+            fn test_default() {
+                let map: AnyMap = Default::default();
+                assert_eq!(map.len(), 0);
+            }
+
+            #[test]
+            fn test_clone() {
+                let mut map: Map<dyn CloneAny> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(D(3));
+                let _ = map.insert(E(4));
+                let _ = map.insert(F(5));
+                let _ = map.insert(J(6));
+                let map2 = map.clone();
+                assert_eq!(map2.len(), 6);
+                assert_eq!(map2.get::<A>(), Some(&A(1)));
+                assert_eq!(map2.get::<B>(), Some(&B(2)));
+                assert_eq!(map2.get::<C>(), None);
+                assert_eq!(map2.get::<D>(), Some(&D(3)));
+                assert_eq!(map2.get::<E>(), Some(&E(4)));
+                assert_eq!(map2.get::<F>(), Some(&F(5)));
+                assert_eq!(map2.get::<J>(), Some(&J(6)));
+            }
+
+            #[test]
+            fn test_varieties() {
+                fn assert_send<T: Send>() { }
+                fn assert_sync<T: Sync>() { }
+                fn assert_clone<T: Clone>() { }
+                fn assert_debug<T: ::core::fmt::Debug>() { }
+                assert_send::<Map<dyn Any + Send>>();
+                assert_send::<Map<dyn Any + Send + Sync>>();
+                assert_sync::<Map<dyn Any + Send + Sync>>();
+                assert_debug::<Map<dyn Any>>();
+                assert_debug::<Map<dyn Any + Send>>();
+                assert_debug::<Map<dyn Any + Send + Sync>>();
+                assert_send::<Map<dyn CloneAny + Send>>();
+                assert_send::<Map<dyn CloneAny + Send + Sync>>();
+                assert_sync::<Map<dyn CloneAny + Send + Sync>>();
+                assert_clone::<Map<dyn CloneAny + Send>>();
+                assert_clone::<Map<dyn CloneAny + Send + Sync>>();
+                assert_clone::<Map<dyn CloneAny + Send + Sync>>();
+                assert_debug::<Map<dyn CloneAny>>();
+                assert_debug::<Map<dyn CloneAny + Send>>();
+                assert_debug::<Map<dyn CloneAny + Send + Sync>>();
+            }
+
+            #[test]
+            fn test_extend() {
+                let mut map = AnyMap::new();
+                map.extend([Box::new(123) as Box<dyn Any>, Box::new(456), Box::new(true)]);
+                assert_eq!(map.get(), Some(&456));
+                assert_eq!(map.get::<bool>(), Some(&true));
+                assert!(map.get::<Box<dyn Any>>().is_none());
+            }
+
+            #[test]
+            fn test_merge_with() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let mut other = AnyMap::new();
+                let _ = other.insert(B(20));
+                let _ = other.insert(C(30));
+                map.merge_with(other, |type_id, mine, theirs| {
+                    assert_eq!(type_id, TypeId::of::<B>());
+                    let mine = mine.downcast::<B>().unwrap();
+                    let theirs = theirs.downcast::<B>().unwrap();
+                    Box::new(B(mine.0 + theirs.0))
+                });
+                assert_eq!(map.len(), 3);
+                assert_eq!(map.get(), Some(&A(1)));
+                assert_eq!(map.get(), Some(&B(22)));
+                assert_eq!(map.get(), Some(&C(30)));
+            }
+
+            #[test]
+            fn test_split_off_with() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                let other = map.split_off_with(|type_id, _| type_id != TypeId::of::<B>());
+                assert_eq!(map.len(), 1);
+                assert_eq!(map.get(), Some(&B(2)));
+                assert_eq!(other.len(), 2);
+                assert_eq!(other.get(), Some(&A(1)));
+                assert_eq!(other.get(), Some(&C(3)));
+            }
+
+            #[test]
+            fn test_difference() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                let mut other: Map<dyn CloneAny> = Map::new();
+                let _ = other.insert(B(2));
+                let _ = other.insert(D(4));
+                let mut difference = map.difference(&other).collect::<Vec<_>>();
+                difference.sort();
+                let mut expected = vec![TypeId::of::<A>(), TypeId::of::<C>()];
+                expected.sort();
+                assert_eq!(difference, expected);
+                assert_eq!(other.difference(&map).collect::<Vec<_>>(), [TypeId::of::<D>()]);
+                assert_eq!(map.difference(&map).next(), None);
+            }
+
+            #[test]
+            fn test_diff() {
+                let mut map: Map<dyn crate::ClonePartialEqAny> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                let mut other = map.clone();
+                let _ = other.insert(B(20));
+                let _ = other.remove::<C>();
+                let _ = other.insert(D(4));
+                let patch = map.diff(&other);
+                assert_eq!(patch.len(), 3);
+                assert_eq!(patch.get_added(), Some(&D(4)));
+                assert_eq!(patch.get_removed(), Some(&C(3)));
+                assert_eq!(patch.get_changed(), Some((&B(2), &B(20))));
+                assert_eq!(patch.get_changed::<A>(), None);
+                let reverse = other.diff(&map);
+                assert_eq!((reverse.get_added(), reverse.get_removed()), (Some(&C(3)), Some(&D(4))));
+                assert!(map.diff(&map.clone()).is_empty());
+            }
+
+            #[test]
+            fn test_apply() {
+                #[cfg(not(feature = "std"))]
+                use alloc::format;
+                use crate::patch::{Conflict, ConflictKind};
+
+                let mut map: Map<dyn crate::ClonePartialEqAny> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let mut other = map.clone();
+                let _ = other.insert(A(10));
+                let _ = other.remove::<B>();
+                let _ = other.insert(C(3));
+                let patch = map.diff(&other);
+
+                let mut replica = map.clone();
+                replica.apply(patch.clone()).unwrap();
+                assert!(replica.diff(&other).is_empty());
+                replica.apply(patch.clone().invert()).unwrap();
+                assert!(replica.diff(&map).is_empty());
+
+                // Nothing is applied if anything conflicts.
+                let _ = replica.insert(A(5));
+                let _ = replica.remove::<B>();
+                let _ = replica.insert(C(3));
+                let error = replica.apply(patch).unwrap_err();
+                let mut conflicts = error.conflicts().to_vec();
+                conflicts.sort_by_key(|conflict| conflict.kind as u8);
+                assert_eq!(conflicts, [
+                    Conflict { type_id: TypeId::of::<C>(), kind: ConflictKind::Present },
+                    Conflict { type_id: TypeId::of::<B>(), kind: ConflictKind::Missing },
+                    Conflict { type_id: TypeId::of::<A>(), kind: ConflictKind::Changed },
+                ]);
+                assert_eq!(replica.get(), Some(&A(5)));
+                assert_eq!(format!("{}", error), "patch conflicts with the map for 3 type(s)");
+                assert_eq!(error.into_patch().len(), 3);
+
+                // Nor if the patch has a type twice, rather than the last of them winning.
+                let mut twice = crate::patch::MapPatch::new();
+                twice.added.push(Box::new(D(4)) as Box<dyn crate::ClonePartialEqAny>);
+                twice.added.push(Box::new(D(40)));
+                let error = replica.apply(twice).unwrap_err();
+                assert_eq!(error.conflicts(), [
+                    Conflict { type_id: TypeId::of::<D>(), kind: ConflictKind::Duplicate },
+                ]);
+                assert!(!replica.contains::<D>());
+            }
+
+            #[test]
+            fn test_chain_map() {
+                let mut parent: Map<dyn CloneAny> = Map::new();
+                let _ = parent.insert(A(1));
+                let _ = parent.insert(B(2));
+                let mut chain = parent.chain();
+                assert_eq!((chain.len(), chain.get()), (2, Some(&A(1))));
+                assert_eq!(chain.get_mut::<A>(), None);
+                assert_eq!(chain.insert(A(10)), None);
+                assert_eq!(chain.insert(C(30)), None);
+                chain.get_mut::<A>().unwrap().0 += 1;
+                assert_eq!((chain.get(), chain.get()), (Some(&A(11)), Some(&B(2))));
+                assert_eq!(chain.get(), Some(&C(30)));
+                assert!(chain.contains::<B>() && !chain.contains::<D>());
+                assert_eq!(chain.len(), 3);
+                let flat = chain.flatten();
+                assert_eq!((flat.len(), flat.get(), flat.get()), (3, Some(&A(11)), Some(&B(2))));
+                assert_eq!(chain.remove::<A>(), Some(A(11)));
+                assert_eq!(chain.get(), Some(&A(1)));
+                assert_eq!(chain.parent().len(), 2);
+                assert_eq!(chain.into_local().len(), 1);
+                assert!(Map::<dyn CloneAny>::new().chain().is_empty());
+            }
+
+            #[test]
+            fn test_scoped_map() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let mut map = map.into_scoped();
+                assert!(!map.pop_scope());
+                map.push_scope();
+                assert_eq!(map.insert(A(2)), None);
+                assert_eq!(map.insert(A(3)), Some(A(2)));
+                assert_eq!(map.insert(B(4)), None);
+                map.push_scope();
+                assert_eq!(map.remove::<A>(), None);
+                assert_eq!(map.insert(A(5)), None);
+                map.get_mut::<B>().unwrap().0 = 40;
+                assert_eq!((map.depth(), map.get(), map.get()), (2, Some(&A(5)), Some(&B(40))));
+                assert_eq!(map.remove::<A>(), Some(A(5)));
+                assert_eq!(map.get(), Some(&A(3)));
+                let _ = map.insert(C(6));
+                assert!(map.pop_scope());
+                assert!(!map.contains::<C>());
+                assert_eq!(map.get(), Some(&B(40)));
+                map.push_scope();
+                let map = map.into_inner();
+                assert_eq!((map.len(), map.get()), (1, Some(&A(1))));
+            }
+
+            #[test]
+            fn test_lazy() {
+                use core::sync::atomic::{AtomicUsize, Ordering};
+                use crate::Lazy;
+
+                static MADE: AtomicUsize = AtomicUsize::new(0);
+                let mut map: Map<dyn Any + Send> = Map::new();
+                assert!(map.insert_lazy(|| {
+                    let _ = MADE.fetch_add(1, Ordering::Relaxed);
+                    A(1)
+                }).is_none());
+                assert_eq!((map.get::<A>(), MADE.load(Ordering::Relaxed)), (None, 0));
+                assert_eq!(map.get_lazy::<A>(), Some(&A(1)));
+                assert_eq!(map.get_lazy::<A>(), Some(&A(1)));
+                assert_eq!(MADE.load(Ordering::Relaxed), 1);
+                map.get_lazy_mut::<A>().unwrap().0 = 2;
+                let previous = map.insert_lazy(|| A(3)).unwrap();
+                assert_eq!(Lazy::into_inner(previous), A(2));
+                assert_eq!(map.get_lazy_mut::<A>(), Some(&mut A(3)));
+                assert_eq!(map.get_lazy::<B>(), None);
+
+                // The lazy slot is a `Lazy<A>` to everything else too, rather than a hidden `A`.
+                assert!(!map.contains::<A>());
+                assert!(map.contains::<Lazy<A>>());
+                assert!(matches!(map.entry::<A>(), Entry::Vacant(_)));
+                assert_eq!(*map.entry::<A>().or_insert(A(4)), A(4));
+                assert_eq!((map.get::<A>(), map.get_lazy::<A>()), (Some(&A(4)), Some(&A(3))));
+                assert_eq!(map.remove::<A>(), Some(A(4)));
+                assert_eq!(map.get_lazy::<A>(), Some(&A(3)));
+
+                let poisoned = Lazy::<A>::new(|| panic!());
+                #[cfg(feature = "std")]
+                {
+                    let poisoned = std::panic::AssertUnwindSafe(&poisoned);
+                    assert!(std::panic::catch_unwind(|| Lazy::force(&poisoned)).is_err());
+                    assert!(std::panic::catch_unwind(|| Lazy::force(&poisoned)).is_err());
+                }
+                assert_eq!(Lazy::get(&poisoned), None);
+            }
+
+            #[test]
+            fn test_key() {
+                use crate::{Key, KeyValue};
+
+                struct First;
+                impl Key for First { type Value = A; }
+                struct Second;
+                impl Key for Second { type Value = A; }
+
+                let mut map: Map<dyn CloneAny + Send + Sync> = Map::new();
+                assert_eq!(map.insert_key::<First>(A(1)), None);
+                assert_eq!(map.insert_key::<Second>(A(2)), None);
+                assert_eq!(map.insert_key::<First>(A(10)), Some(A(1)));
+                assert_eq!((map.len(), map.get::<A>()), (2, None));
+                assert_eq!(map.get_key::<First>(), Some(&A(10)));
+                map.get_key_mut::<Second>().unwrap().0 = 20;
+                assert_eq!(map.get::<KeyValue<Second>>(), Some(&KeyValue(A(20))));
+                assert_eq!(map.clone().remove_key::<Second>(), Some(A(20)));
+                assert!(map.contains_key::<Second>());
+                assert_eq!(map.remove_key::<First>(), Some(A(10)));
+                assert!(!map.contains_key::<First>());
+            }
+
+            #[test]
+            fn test_tagged() {
+                use crate::Tagged;
+
+                enum Primary {}
+                enum Replica {}
+
+                let mut map: Map<dyn CloneAny + Send + Sync> = Map::new();
+                assert_eq!(map.insert_tagged::<Primary, _>(A(1)), None);
+                assert_eq!(map.insert_tagged::<Replica, _>(A(2)), None);
+                assert_eq!(map.insert_tagged::<Primary, _>(A(10)), Some(A(1)));
+                assert_eq!((map.len(), map.get::<A>()), (2, None));
+                assert_eq!(map.get_tagged::<A, Primary>(), Some(&A(10)));
+                map.get_tagged_mut::<A, Replica>().unwrap().0 = 20;
+                let replica = map.get::<Tagged<A, Replica>>().unwrap();
+                assert_eq!((replica.0 .0, replica.clone()), (20, Tagged::new(A(20))));
+                assert_eq!(map.remove_tagged::<A, Replica>(), Some(A(20)));
+                assert_eq!(map.get_tagged::<A, Replica>(), None);
+            }
+
+            #[test]
+            fn test_register_default() {
+                let mut map: Map<dyn CloneAny + Send + Sync> = Map::new();
+                assert!(map.register_default(|| A(1)).is_none());
+                assert!(map.register_default(|| A(2)).is_some());
+                assert_eq!(map.len(), 1);
+                assert_eq!(map.get_or_registered_default::<A>(), Some(&mut A(2)));
+                map.get_or_registered_default::<A>().unwrap().0 = 3;
+                let clone = map.clone();
+                assert_eq!(clone.get(), Some(&A(3)));
+                assert_eq!(map.remove::<A>(), Some(A(3)));
+                assert_eq!(map.get_or_registered_default::<A>(), Some(&mut A(2)));
+                assert_eq!(map.get_or_registered_default::<B>(), None);
+            }
+
+            #[test]
+            fn test_bind() {
+                trait Value {
+                    fn value(&self) -> i32;
+                    fn set(&mut self, value: i32);
+                }
+                crate::impl_cast!(Value);
+                impl Value for A {
+                    fn value(&self) -> i32 { self.0 }
+                    fn set(&mut self, value: i32) { self.0 = value }
+                }
+                impl Value for B {
+                    fn value(&self) -> i32 { self.0 }
+                    fn set(&mut self, value: i32) { self.0 = value }
+                }
+
+                let mut map: Map<dyn CloneAny + Send + Sync> = Map::new();
+                assert!(map.get_trait::<dyn Value>().is_none());
+                assert_eq!(map.bind::<dyn Value + Send, _>(A(1)), None);
+                assert!(map.get_trait::<dyn Value>().is_none());
+                assert_eq!(map.get_trait::<dyn Value + Send>().unwrap().value(), 1);
+                assert_eq!(map.bind::<dyn Value, _>(B(2)), None);
+                map.get_trait_mut::<dyn Value>().unwrap().set(20);
+                assert_eq!(map.get(), Some(&B(20)));
+                let clone = map.clone();
+                assert_eq!(clone.get_trait::<dyn Value>().unwrap().value(), 20);
+                assert_eq!(map.bind::<dyn Value, _>(A(3)), Some(A(1)));
+                assert_eq!(map.get_trait::<dyn Value>().unwrap().value(), 3);
+                let _ = map.remove::<A>();
+                assert!(map.get_trait::<dyn Value>().is_none());
+                type Casts = crate::TraitCasts<dyn Value, dyn CloneAny + Send + Sync>;
+                assert_eq!(map.get::<Casts>().unwrap().type_ids().count(), 2);
+            }
+
+            #[test]
+            fn test_iter_as() {
+                #[cfg(not(feature = "std"))] use alloc::vec::Vec;
+
+                trait Value {
+                    fn value(&self) -> i32;
+                }
+                crate::impl_cast!(Value);
+                impl Value for A { fn value(&self) -> i32 { self.0 } }
+                impl Value for B { fn value(&self) -> i32 { self.0 } }
+                impl Value for C { fn value(&self) -> i32 { self.0 } }
+
+                let mut map: AnyMap = AnyMap::new();
+                assert_eq!(map.iter_as::<dyn Value>().count(), 0);
+                let _ = map.insert(A(1));
+                let _ = map.insert(C(3));
+                assert!(map.register_cast::<dyn Value, A>());
+                assert!(map.register_cast::<dyn Value, B>());
+                assert!(!map.register_cast::<dyn Value, A>());
+                let _ = map.bind::<dyn Value, _>(C(30));
+                let values = map.iter_as::<dyn Value>().map(|v| v.value()).collect::<Vec<_>>();
+                assert_eq!(values, [1, 30]);
+                let _ = map.insert(B(2));
+                let _ = map.remove::<A>();
+                let values = map.iter_as::<dyn Value>().map(|v| v.value()).collect::<Vec<_>>();
+                assert_eq!(values, [2, 30]);
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn test_serialize_with() {
+                #[cfg(not(feature = "std"))] use alloc::{format, string::String};
+                use crate::serde::{Registry, SerializeAny};
+
+                #[derive(::serde::Serialize)]
+                struct Point { x: i32, y: i32 }
+
+                let mut registry = Registry::new();
+                registry.register::<Point>("point");
+                registry.register::<String>("name");
+                assert_eq!(registry.name_of(&TypeId::of::<String>()), Some("name"));
+                assert_eq!(registry.name_of(&TypeId::of::<i32>()), None);
+
+                let mut map: Map<dyn SerializeAny + Send + Sync> = Map::new();
+                assert_eq!(serde_json::to_string(&map.serialize_with(&registry)).unwrap(), "{}");
+                let _ = map.insert(Point { x: 1, y: 2 });
+                let _ = map.insert(String::from("origin"));
+                assert_eq!(
+                    serde_json::to_string(&map.serialize_with(&registry)).unwrap(),
+                    r#"{"name":"origin","point":{"x":1,"y":2}}"#,
+                );
+                let value = map.values().next().unwrap();
+                assert!(serde_json::to_string(value).is_ok());
+
+                let _ = map.insert(4i32);
+                let error = serde_json::to_string(&map.serialize_with(&registry)).unwrap_err();
+                assert!(format!("{}", error).contains("no stable name registered"));
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn test_deserialize() {
+                #[cfg(not(feature = "std"))] use alloc::{format, string::String};
+                use crate::serde::{Registry, SerializeAny};
+
+                #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+                struct Point { x: i32, y: i32 }
+
+                let mut registry = Registry::new();
+                registry.register_deserialize::<Point>("point");
+                registry.register_with("name", |deserializer| {
+                    let name = erased_serde::deserialize::<String>(deserializer)?;
+                    Ok(name.to_uppercase())
+                });
+                registry.register::<u8>("byte");
+                assert!(registry.can_deserialize(&TypeId::of::<Point>()));
+                assert!(!registry.can_deserialize(&TypeId::of::<u8>()));
+                assert_eq!(registry.type_id_of("name"), Some(TypeId::of::<String>()));
+                registry.register_deserialize::<String>("text");
+                assert_eq!(registry.type_id_of("name"), None);
+                assert_eq!(registry.len(), 3);
+
+                let json = r#"{"point":{"x":1,"y":2},"text":"hi"}"#;
+                let mut deserializer = serde_json::Deserializer::from_str(json);
+                let map: Map<dyn SerializeAny> = Map::deserialize(&mut deserializer, &registry)
+                    .unwrap();
+                assert_eq!(map.len(), 2);
+                assert_eq!(map.get(), Some(&Point { x: 1, y: 2 }));
+                assert_eq!(map.get::<String>().map(|s| &**s), Some("hi"));
+                let round_trip = serde_json::to_string(&map.serialize_with(&registry)).unwrap();
+                assert_eq!(round_trip, json);
+
+                for (json, message) in [
+                    (r#"{"nope":1}"#, "unknown stable type name \"nope\""),
+                    (r#"{"byte":1}"#, "no way to deserialize \"byte\" registered"),
+                    (r#"{"text":"a","text":"b"}"#, "duplicate entry \"text\""),
+                    (r#"{"point":{"x":1}}"#, "missing field `y`"),
+                ] {
+                    let mut deserializer = serde_json::Deserializer::from_str(json);
+                    let error = Map::<dyn SerializeAny>::deserialize(&mut deserializer, &registry)
+                        .err().unwrap();
+                    assert!(format!("{}", error).starts_with(message), "{}", error);
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn test_serialize_tagged() {
+                #[cfg(not(feature = "std"))] use alloc::{format, vec::Vec};
+
+                #[typetag::serde]
+                trait Shape: Any {
+                    fn area(&self) -> i32;
+                }
+                crate::impl_any_bound!(Shape);
+
+                #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+                struct Square(i32);
+                #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+                struct Rect { w: i32, h: i32 }
+                #[typetag::serde]
+                impl Shape for Square {
+                    fn area(&self) -> i32 { self.0 * self.0 }
+                }
+                #[typetag::serde]
+                impl Shape for Rect {
+                    fn area(&self) -> i32 { self.w * self.h }
+                }
+
+                let mut map: Map<dyn Shape> = Map::new();
+                let _ = map.insert(Square(2));
+                let _ = map.insert(Rect { w: 2, h: 3 });
+                let mut json = Vec::new();
+                map.serialize_tagged(&mut serde_json::Serializer::new(&mut json)).unwrap();
+                let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+                assert!(value.as_array().unwrap().contains(&serde_json::json!({ "Square": 2 })));
+
+                let mut deserializer = serde_json::Deserializer::from_slice(&json);
+                let back: Map<dyn Shape> = Map::deserialize_tagged(&mut deserializer).unwrap();
+                assert_eq!(back.get(), Some(&Rect { w: 2, h: 3 }));
+                assert_eq!(back.values().map(|shape| shape.area()).sum::<i32>(), 10);
+
+                let json = r#"[{"Square":1},{"Square":2}]"#;
+                let mut deserializer = serde_json::Deserializer::from_str(json);
+                let error = Map::<dyn Shape>::deserialize_tagged(&mut deserializer).err().unwrap();
+                assert!(format!("{}", error).starts_with("duplicate entry"), "{}", error);
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn test_versioned() {
+                #[cfg(not(feature = "std"))] use alloc::{format, string::String};
+                use ::serde::Deserialize;
+                use crate::serde::{Registry, SerializeAny};
+
+                #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+                struct Size { w: u32, h: u32 }
+
+                let mut registry = Registry::new();
+                registry.register_versioned::<Size, _>("size", 2, |version, deserializer| {
+                    match version {
+                        0 => u32::deserialize(deserializer).map(|w| Size { w, h: w }),
+                        _ => <(u32, u32)>::deserialize(deserializer).map(|(w, h)| Size { w, h }),
+                    }
+                });
+                registry.register_deserialize::<String>("name");
+                assert_eq!(registry.version_of(&TypeId::of::<Size>()), Some(2));
+                assert_eq!(registry.version_of(&TypeId::of::<String>()), Some(0));
+                let tag = registry.tag_of(&TypeId::of::<Size>()).unwrap();
+                assert_eq!(tag, crate::StableKey::from_name("size@2"));
+                for name in ["size", "size@1", "size@2"] {
+                    let tag = crate::StableKey::from_name(name);
+                    assert_eq!(registry.type_id_of_tag(tag), Some(TypeId::of::<Size>()));
+                }
+
+                for (json, size) in [
+                    (r#"{"size":3}"#, Size { w: 3, h: 3 }),
+                    (r#"{"size@1":[3,4]}"#, Size { w: 3, h: 4 }),
+                    (r#"{"size@2":{"w":5,"h":6}}"#, Size { w: 5, h: 6 }),
+                ] {
+                    let mut deserializer = serde_json::Deserializer::from_str(json);
+                    let map = Map::<dyn SerializeAny>::deserialize(&mut deserializer, &registry)
+                        .unwrap();
+                    assert_eq!(map.get(), Some(&size));
+                }
+
+                let mut map: Map<dyn SerializeAny> = Map::new();
+                let _ = map.insert(Size { w: 1, h: 2 });
+                let _ = map.insert(String::from("box"));
+                assert_eq!(
+                    serde_json::to_string(&map.serialize_with(&registry)).unwrap(),
+                    r#"{"name":"box","size@2":{"w":1,"h":2}}"#,
+                );
+
+                for (json, message) in [
+                    (r#"{"size@3":{"w":1,"h":2}}"#, "version 3 of \"size\" is newer"),
+                    (r#"{"name@1":"x"}"#, "version 1 of \"name\" is newer"),
+                    (r#"{"size":1,"size@2":{"w":1,"h":2}}"#, "duplicate entry \"size\""),
+                    (r#"{"size@x":1}"#, "unknown stable type name \"size@x\""),
+                ] {
+                    let mut deserializer = serde_json::Deserializer::from_str(json);
+                    let error = Map::<dyn SerializeAny>::deserialize(&mut deserializer, &registry)
+                        .err().unwrap();
+                    assert!(format!("{}", error).starts_with(message), "{}", error);
+                }
+
+                registry.register_deserialize::<Size>("size");
+                assert_eq!(registry.type_id_of_tag(crate::StableKey::from_name("size@1")), None);
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            #[should_panic(expected = "registered for two types")]
+            fn test_registry_duplicate_name() {
+                let mut registry: crate::serde::Registry = crate::serde::Registry::new();
+                registry.register::<A>("a");
+                registry.register::<A>("a");
+                registry.register::<B>("a");
+            }
+
+            #[cfg(feature = "postcard")]
+            #[test]
+            fn test_postcard() {
+                #[cfg(not(feature = "std"))] use alloc::string::String;
+                use crate::serde::{Registry, SerializeAny};
+
+                #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+                struct Point { x: i32, y: i32 }
+
+                let mut registry = Registry::new();
+                registry.register_deserialize::<Point>("point");
+                registry.register_deserialize::<String>("name");
+                let tag = registry.tag_of(&TypeId::of::<Point>()).unwrap();
+                assert_eq!(tag, crate::StableKey::from_name("point"));
+                assert_eq!(registry.type_id_of_tag(tag), Some(TypeId::of::<Point>()));
+                assert_eq!(registry.tag_of(&TypeId::of::<u8>()), None);
+
+                let mut map: Map<dyn SerializeAny + Send> = Map::new();
+                let _ = map.insert(Point { x: 1, y: -1 });
+                let _ = map.insert(String::from("origin"));
+                let bytes = map.to_postcard(&registry).unwrap();
+                // A length, then an eight-byte tag before each value.
+                assert_eq!(bytes.len(), 1 + 8 + 7 + 8 + 2);
+                let back: Map<dyn SerializeAny + Send> = Map::from_postcard(&bytes, &registry)
+                    .unwrap();
+                assert_eq!(back.get(), Some(&Point { x: 1, y: -1 }));
+                assert_eq!(back.get::<String>().map(|s| &**s), Some("origin"));
+
+                registry.register_deserialize::<String>("text");
+                assert!(Map::<dyn SerializeAny + Send>::from_postcard(&bytes, &registry).is_err());
+            }
+
+            #[cfg(feature = "serde_json")]
+            #[test]
+            fn test_json_value() {
+                #[cfg(not(feature = "std"))] use alloc::{format, string::String};
+                use crate::serde::{Registry, SerializeAny};
+                use serde_json::json;
+
+                let mut registry = Registry::new();
+                registry.register_deserialize::<String>("name");
+                registry.register_deserialize::<u32>("width");
+
+                let mut map: Map<dyn SerializeAny> = Map::new();
+                let _ = map.insert(String::from("main"));
+                let _ = map.insert(800u32);
+                let mut value = map.to_json_value(&registry).unwrap();
+                assert_eq!(value, json!({ "name": "main", "width": 800 }));
+
+                value["width"] = json!(1024);
+                let map: Map<dyn SerializeAny> = Map::from_json_value(value, &registry).unwrap();
+                assert_eq!(map.get::<u32>(), Some(&1024));
+                assert_eq!(map.get::<String>().map(|s| &**s), Some("main"));
+
+                let error = Map::<dyn SerializeAny>::from_json_value(json!([1]), &registry)
+                    .err().unwrap();
+                assert!(format!("{}", error).starts_with("invalid type"), "{}", error);
+                let _ = map.to_json_value(&Registry::new()).unwrap_err();
+            }
+
+            #[cfg(feature = "reflect")]
+            #[test]
+            fn test_reflect() {
+                use bevy_reflect::{DynamicStruct, PartialReflect, Reflect, Typed};
+
+                #[derive(Reflect, Debug, PartialEq)]
+                struct Size { width: u32, height: u32 }
+
+                let mut map: Map<dyn Reflect> = Map::new();
+                let _ = map.insert(Size { width: 1, height: 2 });
+                let _ = map.insert(3i32);
+                let type_id = TypeId::of::<Size>();
+                assert_eq!(map.values().filter(|value| value.is::<Size>()).count(), 1);
+                assert!(map.get_reflect(type_id).unwrap().is::<Size>());
+                let height = map.reflect_path(type_id, "height").unwrap();
+                assert_eq!(height.try_downcast_ref::<u32>(), Some(&2));
+                *map.reflect_path_mut(type_id, "width").unwrap()
+                    .try_downcast_mut::<u32>().unwrap() = 10;
+                assert!(matches!(
+                    map.reflect_path(type_id, "depth"),
+                    Err(crate::reflect::ReflectError::Path(_)),
+                ));
+                assert!(matches!(
+                    map.reflect_path(TypeId::of::<u8>(), "0"),
+                    Err(crate::reflect::ReflectError::Missing(_)),
+                ));
+
+                let mut update = DynamicStruct::default();
+                update.insert("height", 20u32);
+                assert!(matches!(
+                    map.try_apply(&update),
+                    Err(crate::reflect::ReflectError::Unrepresented),
+                ));
+                update.set_represented_type(Some(Size::type_info()));
+                map.try_apply(&update).unwrap();
+                assert_eq!(map.get(), Some(&Size { width: 10, height: 20 }));
+                assert!(matches!(
+                    map.try_apply(&4u8 as &dyn PartialReflect),
+                    Err(crate::reflect::ReflectError::Missing(_)),
+                ));
+                map.try_apply(&5i32 as &dyn PartialReflect).unwrap();
+                assert_eq!(map.get::<i32>(), Some(&5));
+            }
+
+            #[test]
+            fn test_get_or_insert() {
+                let mut map = AnyMap::new();
+                map.get_or_insert(A(1)).0 += 1;
+                assert_eq!(map.get_or_insert(A(10)), &mut A(2));
+                assert_eq!(map.get_or_insert_with(|| B(20)), &mut B(20));
+                assert_eq!(map.get_or_insert_with::<B, _>(|| unreachable!()), &mut B(20));
+                assert_eq!(map.len(), 2);
+                *map.get_or_insert_default::<i32>() += 3;
+                assert_eq!(map.get_or_insert_default::<i32>(), &mut 3);
+                assert_eq!(map.len(), 3);
+            }
+
+            #[test]
+            fn test_try_insert() {
+                let mut map = AnyMap::new();
+                assert_eq!(map.try_insert(A(1)).unwrap(), &mut A(1));
+                let mut error = map.try_insert(A(2)).unwrap_err();
+                assert_eq!(error.value, A(2));
+                assert_eq!(error.entry.get(), &A(1));
+                error.entry.get_mut().0 = 3;
+                assert_eq!(map.get(), Some(&A(3)));
+            }
+
+            #[test]
+            fn test_replace_with() {
+                let mut map = AnyMap::new();
+                map.replace_with(|a: Option<A>| {
+                    assert_eq!(a, None);
+                    Some(A(1))
+                });
+                map.replace_with(|a: Option<A>| a.map(|a| A(a.0 + 1)));
+                assert_eq!(map.get(), Some(&A(2)));
+                map.replace_with(|_: Option<A>| None);
+                assert_eq!(map.get::<A>(), None);
+                assert_eq!(map.len(), 0);
+            }
+
+            #[test]
+            fn test_update() {
+                let mut map = AnyMap::new();
+                assert!(!map.update(|_: &mut A| unreachable!()));
+                let _ = map.insert(A(1));
+                assert!(map.update(|a: &mut A| a.0 += 1));
+                assert_eq!(map.get(), Some(&A(2)));
+            }
+
+            #[test]
+            fn test_get_cloned_copied() {
+                let mut map = AnyMap::new();
+                assert_eq!(map.get_cloned::<A>(), None);
+                assert_eq!(map.get_copied::<u8>(), None);
+                let _ = map.insert(A(1));
+                let _ = map.insert(2u8);
+                assert_eq!(map.get_cloned(), Some(A(1)));
+                assert_eq!(map.get_copied(), Some(2u8));
+            }
+
+            #[test]
+            #[should_panic(expected = "duplicate types")]
+            fn test_get_many_mut_duplicate() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.get_many_mut::<(A, B, A)>();
+            }
+
+            #[test]
+            fn test_builder() {
+                let map = Map::<dyn CloneAny>::builder()
+                    .with(A(1))
+                    .with(B(2))
+                    .with(A(3))
+                    .with_default::<i32>()
+                    .build();
+                assert_eq!(map.len(), 3);
+                assert_eq!(map.get_all::<(A, B, i32)>(), (Some(&A(3)), Some(&B(2)), Some(&0)));
+            }
+
+            #[test]
+            fn test_boxed() {
+                let mut map = AnyMap::new();
+                let boxed = Box::new(A(1));
+                let ptr: *const A = &*boxed;
+                assert_eq!(map.insert_boxed(boxed), None);
+                assert!(core::ptr::eq(map.get::<A>().unwrap(), ptr));
+                assert_eq!(map.insert_boxed(Box::new(A(2))), Some(Box::new(A(1))));
+                let _ = map.insert(B(3));
+                assert_eq!(map.remove_boxed::<B>(), Some(Box::new(B(3))));
+                assert_eq!(map.remove_boxed::<B>(), None);
+                assert_eq!(map.len(), 1);
+            }
+
+            #[test]
+            fn test_insert_dyn() {
+                let mut map: Map<dyn Any + Send> = Map::new();
+                let value: Box<dyn Any + Send> = Box::new(A(1));
+                assert!(map.insert_dyn(value, "A").is_none());
+                assert_eq!(map.get(), Some(&A(1)));
+                let old = map.insert_dyn(Box::new(A(2)), "A").unwrap();
+                assert_eq!(old.downcast_ref(), Some(&A(1)));
+                assert_eq!(map.get(), Some(&A(2)));
+                assert_eq!(map.len(), 1);
+            }
+
+            #[test]
+            fn test_unsized() {
+                trait Shape {
+                    fn area(&self) -> i32;
+                }
+                impl Shape for A {
+                    fn area(&self) -> i32 { self.0 * self.0 }
+                }
+                let mut map = AnyMap::new();
+                assert!(map.insert_unsized::<dyn Shape>(Box::new(A(3))).is_none());
+                assert!(map.insert_unsized::<[i32]>(vec![1, 2].into_boxed_slice()).is_none());
+                assert_eq!(map.get_unsized::<dyn Shape>().map(Shape::area), Some(9));
+                assert_eq!(map.get_unsized::<[i32]>(), Some(&[1, 2][..]));
+                assert_eq!(map.get_unsized::<[u8]>(), None);
+                assert!(map.contains::<Box<[i32]>>());
+                map.get_unsized_mut::<[i32]>().unwrap()[1] = 3;
+                assert_eq!(map.remove_unsized::<[i32]>().as_deref(), Some(&[1, 3][..]));
+                assert_eq!(map.len(), 1);
+            }
+
+            #[test]
+            fn test_tiny_map_conversions() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let mut tiny = crate::tiny::TinyMap::from(map);
+                assert_eq!(tiny.len(), 2);
+                assert_eq!(tiny.get::<A>(), Some(&A(1)));
+                let _ = tiny.insert(C(3));
+                let map = AnyMap::from(tiny);
+                assert_eq!(map.len(), 3);
+                assert_eq!(map.get::<B>(), Some(&B(2)));
+                assert_eq!(map.get::<C>(), Some(&C(3)));
+            }
+
+            #[test]
+            fn test_static_map_conversions() {
+                use core::convert::TryFrom;
+                let map = AnyMap::from(crate::static_map::StaticMap::new((A(1), B(2))));
+                assert_eq!(map.len(), 2);
+                assert_eq!(map.get::<B>(), Some(&B(2)));
+                let mut static_map = crate::static_map::StaticMap::<(B, A)>::try_from(map).unwrap();
+                assert_eq!(static_map.get::<A, _>(), &A(1));
+                static_map.get_mut::<B, _>().0 = 20;
+                let mut map = AnyMap::from(static_map);
+                let _ = map.remove::<A>();
+                assert_eq!(
+                    crate::static_map::StaticMap::<(B, A)>::try_from(map).unwrap_err().type_name(),
+                    core::any::type_name::<A>(),
+                );
+                let map = AnyMap::from(crate::static_map::StaticMap::new((A(1), B(2))));
+                assert_eq!(
+                    crate::static_map::StaticMap::<(A, B, A)>::try_from(map).unwrap_err().type_name(),
+                    core::any::type_name::<A>(),
+                );
+            }
+
+            #[test]
+            fn test_with_hasher() {
+                // A deliberately terrible hasher, to show that it’s really being used.
+                #[derive(Default)]
+                struct ConstantHasher;
+                impl core::hash::Hasher for ConstantHasher {
+                    fn write(&mut self, _: &[u8]) { }
+                    fn finish(&self) -> u64 { 0 }
+                }
+                type ConstantState = BuildHasherDefault<ConstantHasher>;
+
+                let mut map: Map<dyn CloneAny, ConstantState> =
+                    Map::with_capacity_and_hasher(4, ConstantState::default());
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                map.entry::<C>().or_insert(C(3)).0 += 1;
+                assert_eq!(map.get::<A>(), Some(&A(1)));
+                assert_eq!(map.get_all::<(B, C)>(), (Some(&B(2)), Some(&C(4))));
+                let cloned = map.clone();
+                let other = map.split_off_with(|type_id, _| type_id == TypeId::of::<A>());
+                assert_eq!(other.get::<A>(), Some(&A(1)));
+                assert_eq!(map.len(), 2);
+                assert_eq!(cloned.len(), 3);
+                assert_eq!(cloned.difference(&map).collect::<Vec<_>>(), [TypeId::of::<A>()]);
+            }
+
+            #[test]
+            fn test_try_reserve() {
+                let mut map = AnyMap::new();
+                assert!(map.try_reserve(10).is_ok());
+                assert!(map.capacity() >= 10);
+                assert!(map.try_reserve(usize::MAX).is_err());
+            }
+
+            #[test]
+            fn test_shrink_to() {
+                let mut map = AnyMap::with_capacity(100);
+                let _ = map.insert(A(1));
+                map.shrink_to(10);
+                assert!(map.capacity() >= 10 && map.capacity() < 100);
+                map.shrink_to(0);
+                assert!(map.capacity() >= 1);
+                assert_eq!(map.get::<A>(), Some(&A(1)));
+            }
+
+            #[test]
+            fn test_bounded_map() {
+                let mut map = crate::bounded::BoundedMap::<Map>::new(2);
+                assert_eq!(map.limit(), 2);
+                assert_eq!(map.insert(A(1)), Ok(None));
+                assert_eq!(map.insert(B(2)), Ok(None));
+                assert!(map.is_full());
+                assert_eq!(map.insert(C(3)), Err(C(3)));
+                assert_eq!(map.insert(A(10)), Ok(Some(A(1))));
+                map.get_mut::<B>().unwrap().0 = 20;
+                assert_eq!(map.remove::<B>(), Some(B(20)));
+                assert_eq!(map.insert(C(3)), Ok(None));
+                assert_eq!(map.get::<C>(), Some(&C(3)));
+                let map = map.into_inner();
+                assert_eq!(map.len(), 2);
+                assert!(map.into_bounded(1).is_err());
+            }
+
+            #[test]
+            fn test_stable_map() {
+                use crate::StableKey;
+
+                let mut map = Map::<dyn CloneAny>::new();
+                let _ = map.insert(A(1));
+                let mut map = map.into_stable();
+                assert_eq!(map.key_of(&TypeId::of::<A>()), None);
+                assert_eq!(map.insert(A(10)), Some(A(1)));
+                assert_eq!(map.key_of(&TypeId::of::<A>()), Some(StableKey::of::<A>()));
+                assert_eq!(map.insert_named("b", B(2)), None);
+                assert_eq!(map.type_id_of(StableKey::from_name("b")), Some(TypeId::of::<B>()));
+                assert_eq!(map.insert(B(20)), Some(B(2)));
+                assert_eq!(map.key_of(&TypeId::of::<B>()), Some(StableKey::from_name("b")));
+                let _ = map.insert_named("a", A(11));
+                assert!(map.get_by_key(StableKey::of::<A>()).is_none());
+                assert!(map.get_by_key(StableKey::from_name("a")).is_some());
+                assert_eq!(map.clone().iter_keyed().count(), 2);
+
+                let b = map.remove_by_key(StableKey::from_name("b")).unwrap();
+                assert_eq!(Downcast::type_id(&*b), TypeId::of::<B>());
+                assert!(!map.contains::<B>());
+                assert_eq!(map.remove::<A>(), Some(A(11)));
+                assert_eq!(map.type_id_of(StableKey::from_name("a")), None);
+                assert_eq!(map.iter_keyed().count(), 0);
+            }
+
+            #[test]
+            #[should_panic(expected = "is already used by another type")]
+            fn test_stable_map_conflict() {
+                let mut map = crate::stable::StableMap::<Map>::new();
+                let _ = map.insert_named("x", A(1));
+                let _ = map.insert_named("x", B(2));
+            }
+
+            #[test]
+            fn test_tracked_map() {
+                let mut map = Map::<dyn Any>::new();
+                let _ = map.insert(A(1));
+                let mut map = map.into_tracked();
+                assert_eq!((map.tick(), map.changed_tick::<A>()), (1, Some(1)));
+                let start = map.tick();
+                assert_eq!(map.insert(B(2)), None);
+                let _ = map.get_or_insert_with(|| C(3));
+                assert_eq!(map.iter_changed(start).count(), 2);
+                let seen = map.tick();
+                assert!(map.get_mut::<D>().is_none());
+                assert_eq!(map.tick(), seen);
+                map.get_mut::<A>().unwrap().0 = 10;
+                assert!(map.changed_since::<A>(seen));
+                assert!(!map.changed_since::<B>(seen));
+                assert!(map.changed_since::<B>(start));
+                let changed = map.iter_changed(seen).collect::<Vec<_>>();
+                assert_eq!(changed.len(), 1);
+                assert_eq!(changed[0].downcast_ref::<A>(), Some(&A(10)));
+                assert_eq!(map.remove::<A>(), Some(A(10)));
+                assert!(!map.changed_since::<A>(0));
+                assert_eq!(map.len(), 2);
+                map.clear();
+                assert_eq!(map.iter_changed(0).count(), 0);
+                assert!(map.into_inner().is_empty());
+            }
+
+            #[test]
+            fn test_heap_bytes() {
+                let mut map = AnyMap::new();
+                assert_eq!(map.heap_bytes(), 0);
+                let _ = map.insert(());
+                let table_only = map.heap_bytes();
+                assert!(table_only > 0);
+                let _ = map.insert([0u8; 1000]);
+                assert!(map.heap_bytes() >= table_only + 1000);
+                let mut layouts = map.entry_layouts().collect::<Vec<_>>();
+                layouts.sort_by_key(|&(_, layout)| layout.size());
+                assert_eq!(layouts[0], (TypeId::of::<()>(), core::alloc::Layout::new::<()>()));
+                assert_eq!(
+                    layouts[1],
+                    (TypeId::of::<[u8; 1000]>(), core::alloc::Layout::new::<[u8; 1000]>()),
+                );
+            }
+
+            #[cfg(feature = "type-names")]
+            #[test]
+            fn test_type_names() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.entry::<B>().or_insert(B(2));
+                let _ = map.insert_dyn(Box::new(C(3)), "C");
+                let _ = map.entry::<D>();
+                map.extend(Some(Box::new(E(5)) as Box<dyn Any>));
+                assert_eq!(map.type_name_of(&TypeId::of::<A>()), Some(core::any::type_name::<A>()));
+                assert_eq!(map.type_name_of(&TypeId::of::<C>()), Some("C"));
+                assert_eq!(map.type_name_of(&TypeId::of::<D>()), None);
+                assert_eq!(map.type_name_of(&TypeId::of::<E>()), None);
+                let mut names = map.type_names().map(|(_, name)| name).collect::<Vec<_>>();
+                names.sort();
+                assert_eq!(names, [
+                    None,
+                    Some("C"),
+                    Some(core::any::type_name::<A>()),
+                    Some(core::any::type_name::<B>()),
+                ]);
+                let other = map.split_off_with(|type_id, _| type_id == TypeId::of::<B>());
+                assert_eq!(other.type_name_of(&TypeId::of::<B>()), Some(core::any::type_name::<B>()));
+                let _ = map.remove::<A>();
+                assert_eq!(map.type_name_of(&TypeId::of::<A>()), None);
+                map.merge_with(other, |_, ours, _| ours);
+                assert_eq!(map.type_name_of(&TypeId::of::<B>()), Some(core::any::type_name::<B>()));
+            }
+
+            #[cfg(feature = "type-names")]
+            #[test]
+            fn test_type_names_forgotten() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                let _ = map.insert(D(4));
+                let _ = map.entry::<E>();
+                assert_eq!(map.names.len(), 4);
+                let _ = map.remove::<A>();
+                if let Entry::Occupied(e) = map.entry::<B>() {
+                    let _ = e.remove();
+                }
+                assert_eq!(map.names.len(), 2);
+                let mut view = map.raw_mut_view();
+                let _ = view.remove(&TypeId::of::<C>());
+                view.retain(|_, _| false);
+                assert_eq!(map.names.len(), 0);
+                let _ = map.insert(A(1));
+                map.clear();
+                assert_eq!(map.names.len(), 0);
+            }
+
+            #[test]
+            fn test_debug() {
+                #[cfg(not(feature = "std"))]
+                use alloc::format;
+                let mut map: Map<dyn CloneAny> = Map::new();
+                assert_eq!(format!("{:?}", map), "Map { len: 0, entries: {} }");
+                let _ = map.insert(A(1));
+                let debug = format!("{:?}", map);
+                assert!(debug.starts_with("Map { len: 1, entries: {"), "{}", debug);
+                assert!(debug.ends_with(": dyn CloneAny} }"), "{}", debug);
+                #[cfg(feature = "type-names")]
+                assert!(debug.contains(core::any::type_name::<A>()), "{}", debug);
+            }
+
+            #[test]
+            fn test_debug_any() {
+                #[cfg(not(feature = "std"))]
+                use alloc::format;
+                let mut map: Map<dyn crate::DebugAny + Send + Sync> = Map::new();
+                let _ = map.insert(A(1));
+                let debug = format!("{:?}", map);
+                assert!(debug.ends_with(": A(1)} }"), "{}", debug);
+                let pretty = format!("{:#?}", map);
+                assert!(pretty.contains(": A(\n"), "{}", pretty);
+            }
 
-                        // Existing key (insert)
-                        match map.entry::<A>() {
-                            Entry::Vacant(_) => unreachable!(),
-                            Entry::Occupied(mut view) => {
-                                assert_eq!(view.get(), &A(10));
-                                assert_eq!(view.insert(A(100)), A(10));
-                            }
-                        }
-                        assert_eq!(map.get::<A>().unwrap(), &A(100));
-                        assert_eq!(map.len(), 6);
+            #[test]
+            fn test_clone_debug_any() {
+                #[cfg(not(feature = "std"))]
+                use alloc::format;
+                let mut map: Map<dyn crate::CloneDebugAny + Send> = Map::new();
+                let _ = map.insert(A(1));
+                let cloned = map.clone();
+                let _ = map.insert(A(2));
+                assert_eq!(cloned.get::<A>(), Some(&A(1)));
+                let debug = format!("{:?}", cloned);
+                assert!(debug.ends_with(": A(1)} }"), "{}", debug);
+            }
 
+            #[test]
+            fn test_partial_eq_any() {
+                let mut a: Map<dyn crate::PartialEqAny> = Map::new();
+                let _ = a.insert(A(1));
+                let _ = a.insert(B(2));
+                let mut b: Map<dyn crate::PartialEqAny> = Map::new();
+                let _ = b.insert(B(2));
+                assert!(a != b);
+                let _ = b.insert(A(1));
+                assert!(a == b);
+                let _ = b.insert(A(10));
+                assert!(a != b);
+                let _ = b.remove::<A>();
+                let _ = b.insert(C(1));
+                assert!(a != b);
+            }
 
-                        // Existing key (update)
-                        match map.entry::<B>() {
-                            Entry::Vacant(_) => unreachable!(),
-                            Entry::Occupied(mut view) => {
-                                let v = view.get_mut();
-                                let new_v = B(v.0 * 10);
-                                *v = new_v;
-                            }
+            #[test]
+            fn test_hash_any() {
+                // FNV-1a, since TypeIdHasher only takes TypeIds and there’s no std hasher here.
+                struct Fnv(u64);
+                impl Hasher for Fnv {
+                    fn write(&mut self, bytes: &[u8]) {
+                        for &byte in bytes {
+                            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3);
                         }
-                        assert_eq!(map.get::<B>().unwrap(), &B(200));
-                        assert_eq!(map.len(), 6);
+                    }
+                    fn finish(&self) -> u64 {
+                        self.0
+                    }
+                }
+                fn hash<T: Hash>(value: &T) -> u64 {
+                    let mut state = Fnv(0xcbf2_9ce4_8422_2325);
+                    value.hash(&mut state);
+                    state.finish()
+                }
+                let mut a: Map<dyn crate::HashAny> = Map::new();
+                let _ = a.insert(A(1));
+                let _ = a.insert(B(2));
+                let mut b: Map<dyn crate::HashAny> = Map::new();
+                let _ = b.insert(B(2));
+                let _ = b.insert(A(1));
+                assert_eq!(hash(&a), hash(&b));
+                let _ = b.insert(A(10));
+                assert_ne!(hash(&a), hash(&b));
+            }
 
+            #[test]
+            fn test_weaken() {
+                let mut map: Map<dyn crate::CloneAny + Send + Sync> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let map: Map<dyn crate::CloneAny> = map.into();
+                assert_eq!(map.get::<A>(), Some(&A(1)));
+                let map: Map<dyn Any> = map.into();
+                assert_eq!(map.get::<B>(), Some(&B(2)));
+                assert_eq!(map.len(), 2);
 
-                        // Existing key (remove)
-                        match map.entry::<C>() {
-                            Entry::Vacant(_) => unreachable!(),
-                            Entry::Occupied(view) => {
-                                assert_eq!(view.remove(), C(30));
-                            }
-                        }
-                        assert_eq!(map.get::<C>(), None);
-                        assert_eq!(map.len(), 5);
+                let mut map: Map<dyn crate::CloneAny + Send> = Map::new();
+                let _ = map.insert(A(1));
+                let map: Map<dyn Any + Send> = map.into();
+                let map: Map<dyn Any> = map.into();
+                assert_eq!(map.get::<A>(), Some(&A(1)));
+            }
+
+            #[test]
+            fn test_clone_subset() {
+                let mut map: Map<dyn crate::CloneAny> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                let subset = map.clone_subset::<(A, C, D)>();
+                assert_eq!(subset.len(), 2);
+                assert_eq!(subset.get::<A>(), Some(&A(1)));
+                assert_eq!(subset.get::<B>(), None);
+                assert_eq!(subset.get::<C>(), Some(&C(3)));
+                assert_eq!(map.len(), 3);
+            }
 
+            #[test]
+            fn test_try_from_raw() {
+                let mut raw: RawMap<dyn Any> = RawMap::default();
+                let _ = raw.insert(TypeId::of::<A>(), Box::new(A(1)));
+                let map = Map::try_from_raw(raw).unwrap();
+                assert_eq!(map.get::<A>(), Some(&A(1)));
+                let mut raw = map.into_raw();
+                let _ = raw.insert(TypeId::of::<B>(), Box::new(C(2)));
+                let error = Map::try_from_raw(raw).unwrap_err();
+                assert_eq!(error.key(), TypeId::of::<B>());
+                assert_eq!(error.value_type_id(), TypeId::of::<C>());
+            }
 
-                        // Inexistent key (insert)
-                        match map.entry::<J>() {
-                            Entry::Occupied(_) => unreachable!(),
-                            Entry::Vacant(view) => {
-                                assert_eq!(*view.insert(J(1000)), J(1000));
-                            }
+            #[cfg(feature = "rayon")]
+            #[test]
+            fn test_par_iter() {
+                use rayon::iter::ParallelIterator;
+
+                let mut map: Map<dyn Any + Send + Sync> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                assert_eq!(map.par_iter().count(), 3);
+                map.par_iter_mut().for_each(|value| {
+                    if let Some(a) = value.downcast_mut::<A>() {
+                        a.0 += 10;
+                    }
+                });
+                assert_eq!(map.get::<A>(), Some(&A(11)));
+                assert_eq!(map.par_iter().filter(|value| value.is::<C>()).count(), 1);
+            }
+
+            #[cfg(feature = "rayon")]
+            #[test]
+            fn test_from_par_iter() {
+                use rayon::iter::{IntoParallelIterator, ParallelExtend, ParallelIterator};
+
+                let mut map: Map<dyn Any + Send> = (0..3).into_par_iter()
+                    .map(|i| -> Box<dyn Any + Send> {
+                        match i {
+                            0 => Box::new(A(0)),
+                            1 => Box::new(B(1)),
+                            _ => Box::new(C(2)),
                         }
-                        assert_eq!(map.get::<J>().unwrap(), &J(1000));
-                        assert_eq!(map.len(), 6);
+                    })
+                    .collect();
+                assert_eq!(map.len(), 3);
+                assert_eq!(map.get::<B>(), Some(&B(1)));
+                map.par_extend(vec![Box::new(B(10)) as Box<dyn Any + Send>, Box::new(D(3))]);
+                assert_eq!(map.len(), 4);
+                assert_eq!(map.get::<B>(), Some(&B(10)));
+                assert_eq!(map.get::<D>(), Some(&D(3)));
 
-                        // Entry.or_insert on existing key
-                        map.entry::<B>().or_insert(B(71)).0 += 1;
-                        assert_eq!(map.get::<B>().unwrap(), &B(201));
-                        assert_eq!(map.len(), 6);
+                // Values of the same type are told apart by position, not by which thread won.
+                let map: Map<dyn Any + Send> = (0..1000).into_par_iter()
+                    .map(|i| Box::new(A(i)) as Box<dyn Any + Send>)
+                    .collect();
+                assert_eq!(map.get::<A>(), Some(&A(999)));
+                #[cfg(feature = "type-names")]
+                assert_eq!(map.type_name_of(&TypeId::of::<A>()), None);
+            }
 
-                        // Entry.or_insert on nonexisting key
-                        map.entry::<C>().or_insert(C(300)).0 += 1;
-                        assert_eq!(map.get::<C>().unwrap(), &C(301));
-                        assert_eq!(map.len(), 7);
+            #[test]
+            fn test_insert_scoped() {
+                let mut map: Map = Map::new();
+                let _ = map.insert(A(1));
+                {
+                    let mut guard = map.insert_scoped(A(2));
+                    assert_eq!(crate::scoped::ScopedGuard::previous(&guard), Some(&A(1)));
+                    {
+                        let guard = guard.insert_scoped(B(3));
+                        assert_eq!(guard.get::<A>(), Some(&A(2)));
+                        assert_eq!(guard.get::<B>(), Some(&B(3)));
                     }
+                    assert!(!guard.contains::<B>());
+                    guard.get_mut::<A>().unwrap().0 = 20;
                 }
+                assert_eq!(map.get::<A>(), Some(&A(1)));
+                assert_eq!(map.len(), 1);
             }
 
-            test_entry!(test_entry_any, AnyMap);
-            test_entry!(test_entry_cloneany, Map<dyn CloneAny>);
+            #[test]
+            fn test_raw_mut_view() {
+                let mut map: Map<dyn crate::CloneDebugAny> = Map::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let _ = map.insert(C(3));
+                let mut view = map.raw_mut_view();
+                assert_eq!(view.iter_mut().len(), 3);
+                view.retain(|&type_id, _| type_id != TypeId::of::<A>());
+                assert!(view.remove(&TypeId::of::<B>()).is_some());
+                assert!(view.get_mut(&TypeId::of::<C>()).is_some());
+                assert_eq!(view.len(), 1);
+                assert_eq!(view.drain().count(), 1);
+                assert!(view.is_empty());
+                assert!(map.is_empty());
+            }
 
             #[test]
-            fn test_default() {
-                let map: AnyMap = Default::default();
-                assert_eq!(map.len(), 0);
+            fn test_try_get() {
+                #[cfg(not(feature = "std"))]
+                use alloc::string::ToString;
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                assert_eq!(map.try_get::<A>(), Ok(&A(1)));
+                map.try_get_mut::<A>().unwrap().0 = 2;
+                assert_eq!(map.get::<A>(), Some(&A(2)));
+                let error = map.try_get::<B>().unwrap_err();
+                assert!(error.type_name().ends_with("::B"));
+                assert!(error.to_string().contains("::B`"));
+                assert!(map.try_get_mut::<B>().is_err());
             }
 
             #[test]
-            fn test_clone() {
-                let mut map: Map<dyn CloneAny> = Map::new();
+            fn test_expect() {
+                let mut map = AnyMap::new();
                 let _ = map.insert(A(1));
-                let _ = map.insert(B(2));
-                let _ = map.insert(D(3));
-                let _ = map.insert(E(4));
-                let _ = map.insert(F(5));
-                let _ = map.insert(J(6));
-                let map2 = map.clone();
-                assert_eq!(map2.len(), 6);
-                assert_eq!(map2.get::<A>(), Some(&A(1)));
-                assert_eq!(map2.get::<B>(), Some(&B(2)));
-                assert_eq!(map2.get::<C>(), None);
-                assert_eq!(map2.get::<D>(), Some(&D(3)));
-                assert_eq!(map2.get::<E>(), Some(&E(4)));
-                assert_eq!(map2.get::<F>(), Some(&F(5)));
-                assert_eq!(map2.get::<J>(), Some(&J(6)));
+                assert_eq!(map.expect::<A>("A"), &A(1));
             }
 
             #[test]
-            fn test_varieties() {
-                fn assert_send<T: Send>() { }
-                fn assert_sync<T: Sync>() { }
-                fn assert_clone<T: Clone>() { }
-                fn assert_debug<T: ::core::fmt::Debug>() { }
-                assert_send::<Map<dyn Any + Send>>();
-                assert_send::<Map<dyn Any + Send + Sync>>();
-                assert_sync::<Map<dyn Any + Send + Sync>>();
-                assert_debug::<Map<dyn Any>>();
-                assert_debug::<Map<dyn Any + Send>>();
-                assert_debug::<Map<dyn Any + Send + Sync>>();
-                assert_send::<Map<dyn CloneAny + Send>>();
-                assert_send::<Map<dyn CloneAny + Send + Sync>>();
-                assert_sync::<Map<dyn CloneAny + Send + Sync>>();
-                assert_clone::<Map<dyn CloneAny + Send>>();
-                assert_clone::<Map<dyn CloneAny + Send + Sync>>();
-                assert_clone::<Map<dyn CloneAny + Send + Sync>>();
-                assert_debug::<Map<dyn CloneAny>>();
-                assert_debug::<Map<dyn CloneAny + Send>>();
-                assert_debug::<Map<dyn CloneAny + Send + Sync>>();
+            #[should_panic(expected = "configuring: missing value of type `")]
+            fn test_expect_missing() {
+                let _ = AnyMap::new().expect::<A>("configuring");
             }
 
             #[test]
-            fn test_extend() {
+            fn test_pinned() {
+                use core::marker::PhantomPinned;
+                struct SelfAware {
+                    address: usize,
+                    _pin: PhantomPinned,
+                }
+                impl SelfAware {
+                    fn check(&self) -> bool {
+                        self.address == self as *const SelfAware as usize
+                    }
+                }
                 let mut map = AnyMap::new();
-                // (vec![] for 1.36.0 compatibility; more recently, you should use [] instead.)
-                #[cfg(not(feature = "std"))]
-                use alloc::vec;
-                map.extend(vec![Box::new(123) as Box<dyn Any>, Box::new(456), Box::new(true)]);
-                assert_eq!(map.get(), Some(&456));
-                assert_eq!(map.get::<bool>(), Some(&true));
-                assert!(map.get::<Box<dyn Any>>().is_none());
+                let mut pinned = map.insert_pinned(SelfAware { address: 0, _pin: PhantomPinned });
+                let address = &*pinned as *const SelfAware as usize;
+                unsafe { pinned.as_mut().get_unchecked_mut().address = address };
+                map.reserve(100);
+                let _ = map.insert(A(1));
+                assert!(map.get_pinned::<SelfAware>().unwrap().check());
+                assert!(map.get_pinned_mut::<SelfAware>().unwrap().check());
+                assert!(map.remove_pinned::<SelfAware>().unwrap().check());
+                assert!(map.get_pinned::<SelfAware>().is_none());
+            }
+
+            #[test]
+            #[should_panic(expected = "wrong type")]
+            fn test_merge_with_wrong_type() {
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let mut other = AnyMap::new();
+                let _ = other.insert(A(2));
+                map.merge_with(other, |_, _, _| Box::new(B(0)));
+            }
+
+            #[test]
+            #[cfg(feature = "std")]
+            fn test_merge_with_panic() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let mut map = AnyMap::new();
+                let _ = map.insert(A(1));
+                let _ = map.insert(B(2));
+                let mut other = AnyMap::new();
+                let _ = other.insert(B(20));
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    map.merge_with(other, |_, _, _| panic!("can’t merge"));
+                }));
+                assert!(result.is_err());
+                // B is gone, name and all, but the map is otherwise intact.
+                assert_eq!(map.len(), 1);
+                assert!(map.contains::<A>());
+                assert!(!map.contains::<B>());
+                #[cfg(feature = "type-names")]
+                assert_eq!(map.names.len(), 1);
             }
         }
     };
@@ -594,40 +3986,271 @@ everything!("let mut data = anymap::AnyMap::new();", std::collections);
 /// This depends on the `hashbrown` Cargo feature being enabled.
 pub mod hashbrown {
     #[cfg(doc)]
-    use crate::any::CloneAny;
-    use crate::TypeIdHasher;
-
-    everything!(
-        "let mut data = anymap::hashbrown::AnyMap::new();",
-        hashbrown,
-        BuildHasherDefault<TypeIdHasher>
-    );
+    use crate::any::{
+        CloneAny, CloneDebugAny, ClonePartialEqAny, DebugAny, HashAny, PartialEqAny,
+    };
+    use core::fmt;
+    use core::hash::Hasher;
+    use crate::any::Downcast;
+    use crate::{Bundle, CloneSubset, Fetch, FetchMut, InsertEntry, TypeIdHasher};
+
+    everything!("let mut data = anymap::hashbrown::AnyMap::new();", hashbrown, S);
+}
+
+// `VacantEntry::insert_entry` for the std and hashbrown maps, whose APIs differ.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+trait InsertEntry {
+    type Value;
+    type Occupied;
+    fn insert_entry(self, value: Self::Value) -> Self::Occupied;
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V> InsertEntry for std::collections::hash_map::VacantEntry<'a, K, V> {
+    type Value = V;
+    type Occupied = std::collections::hash_map::OccupiedEntry<'a, K, V>;
+
+    #[inline]
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
+}
+
+#[cfg(feature = "hashbrown")]
+impl<'a, K, V, S> InsertEntry for ::hashbrown::hash_map::VacantEntry<'a, K, V, S>
+where
+    K: core::hash::Hash,
+    S: core::hash::BuildHasher,
+{
+    type Value = V;
+    type Occupied = ::hashbrown::hash_map::OccupiedEntry<'a, K, V, S>;
+
+    #[inline]
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        ::hashbrown::hash_map::Entry::Vacant(self).insert(value)
+    }
+}
+
+/// Creates a map containing the given values, each stored under its own type.
+///
+/// This is an <code>[AnyMap]</code> (from whichever of the std and hashbrown implementations is
+/// available, preferring std), unless you specify the bound to use as the first thing:
+///
+/// ```rust
+/// use anymap::CloneAny;
+///
+/// let map = anymap::anymap! { 42i32, "str", String::from("string") };
+/// assert_eq!(map.len(), 3);
+/// assert_eq!(map.get::<i32>(), Some(&42));
+///
+/// let map = anymap::anymap! { dyn CloneAny + Send; 42i32, "str" };
+/// let clone = map.clone();
+/// assert_eq!(clone.get::<&str>(), Some(&"str"));
+///
+/// assert!(anymap::anymap! {}.is_empty());
+/// ```
+///
+/// Capacity for all the values is reserved up front. If you specify the same type more than once,
+/// the last value wins, just as with repeated calls to `insert`.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[macro_export]
+macro_rules! anymap {
+    (dyn $bound:ident $(+ $auto:ident)*; $($value:expr),* $(,)?) => {{
+        let mut map = $crate::__Map::<dyn $bound $(+ $auto)*>::with_capacity(
+            <[&str]>::len(&[$(stringify!($value)),*])
+        );
+        $(let _ = map.insert($value);)*
+        map
+    }};
+    ($($value:expr),* $(,)?) => {{
+        let mut map = $crate::__Map::<dyn ::core::any::Any>::with_capacity(
+            <[&str]>::len(&[$(stringify!($value)),*])
+        );
+        $(let _ = map.insert($value);)*
+        map
+    }};
+}
+
+// For macros, which can’t see whether we’re no_std.
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+#[doc(hidden)]
+pub use alloc::boxed::Box as __Box;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use std::boxed::Box as __Box;
+
+// For macros, which can’t see our Cargo features, the default variety of Map.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use crate::Map as __Map;
+#[cfg(all(not(feature = "std"), feature = "hashbrown"))]
+#[doc(hidden)]
+pub use crate::hashbrown::Map as __Map;
+
+// For `register_anymap_type!`, so that its users needn’t depend on inventory themselves.
+#[cfg(feature = "inventory")]
+#[doc(hidden)]
+pub use ::inventory as __inventory;
+
+// Calls the given macro with the path of each variety of `Map` that’s enabled; for derive macros,
+// which can’t see our Cargo features.
+#[cfg(all(feature = "std", feature = "hashbrown"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __for_each_map {
+    ($m:ident) => {
+        $m!($crate::Map);
+        $m!($crate::hashbrown::Map);
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "hashbrown")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __for_each_map {
+    ($m:ident) => {
+        $m!($crate::Map);
+    }
+}
+
+#[cfg(all(not(feature = "std"), feature = "hashbrown"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __for_each_map {
+    ($m:ident) => {
+        $m!($crate::hashbrown::Map);
+    }
+}
+
+#[cfg(not(any(feature = "std", feature = "hashbrown")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __for_each_map {
+    ($m:ident) => {}
+}
+
+/// An error indicating that a map had no value of a type that was required.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissingError {
+    type_name: &'static str,
+}
+
+impl MissingError {
+    /// Creates an error for a missing value of type `T`.
+    #[inline]
+    pub fn new<T: ?Sized>() -> MissingError {
+        MissingError {
+            type_name: core::any::type_name::<T>(),
+        }
+    }
+
+    /// Returns the name of the missing type, as given by `core::any::type_name`.
+    #[inline]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl fmt::Display for MissingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "missing value of type `{}`", self.type_name)
+    }
+}
+
+impl core::error::Error for MissingError { }
+
+/// An error indicating that a raw map had a value under the wrong key; see `Map::try_from_raw`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawMapError {
+    key: core::any::TypeId,
+    value_type_id: core::any::TypeId,
+}
+
+impl RawMapError {
+    /// Returns the key that the value was under.
+    #[inline]
+    pub fn key(&self) -> core::any::TypeId {
+        self.key
+    }
+
+    /// Returns the `TypeId` of the value, which should have been its key.
+    #[inline]
+    pub fn value_type_id(&self) -> core::any::TypeId {
+        self.value_type_id
+    }
 }
 
+impl fmt::Display for RawMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "value of type {:?} under the key {:?}", self.value_type_id, self.key)
+    }
+}
+
+impl core::error::Error for RawMapError { }
+
 /// A hasher designed to eke a little more speed out, given `TypeId`’s known characteristics.
 ///
-/// Specifically, this is a no-op hasher that expects to be fed a u64’s worth of
-/// randomly-distributed bits. It works well for `TypeId` (eliminating start-up time, so that my
-/// get_missing benchmark is ~30ns rather than ~900ns, and being a good deal faster after that, so
-/// that my insert_and_get_on_260_types benchmark is ~12μs instead of ~21.5μs), but will
-/// panic in debug mode and always emit zeros in release mode for any other sorts of inputs, so
-/// yeah, don’t use it! 😀
+/// Specifically, this is a near-no-op hasher that expects to be fed randomly-distributed bits. It
+/// works well for `TypeId` (eliminating start-up time, so that my get_missing benchmark is ~30ns
+/// rather than ~900ns, and being a good deal faster after that, so that my
+/// insert_and_get_on_260_types benchmark is ~12μs instead of ~21.5μs). A single `u64` passes
+/// straight through; anything wider, such as the 128 bits a `TypeId` may hash in future, has its
+/// 64-bit halves folded together, so no part is ignored. It’ll take any input without complaint,
+/// but does nothing to spread out non-random bits, so yeah, don’t use it for anything else! 😀
+///
+/// This is also why there’s no raw-entry API for looking things up by a precomputed hash: with
+/// this hasher, computing the hash *is* just copying the `TypeId`, so there’s nothing to save.
+/// (And std’s `raw_entry`, which such an API would need, was never stabilised.) Nor is there a
+/// token type for caching a lookup: the hash is free, and caching the bucket would need access
+/// to the table’s internals that neither std nor hashbrown (without its `raw` feature) offers.
+/// A successful lookup is a hash, a probe or two, and a pointer chase; if that’s too slow in
+/// your innermost loop, hoist the reference out of it.
 #[derive(Default)]
 pub struct TypeIdHasher {
     value: u64,
 }
 
+impl TypeIdHasher {
+    #[inline]
+    fn mix(&mut self, word: u64) {
+        // For the first word, this is just `word`. Multiplying (by FxHash’s constant) before each
+        // subsequent word means that equal halves don’t cancel one another out.
+        self.value = self.value.wrapping_mul(0x51_7c_c1_b7_27_22_0a_95) ^ word;
+    }
+}
+
 impl Hasher for TypeIdHasher {
     #[inline]
     fn write(&mut self, bytes: &[u8]) {
-        // This expects to receive exactly one 64-bit value, and there’s no realistic chance of
-        // that changing, but I don’t want to depend on something that isn’t expressly part of the
-        // contract for safety. But I’m OK with release builds putting everything in one bucket
-        // if it *did* change (and debug builds panicking).
-        debug_assert_eq!(bytes.len(), 8);
-        let _ = bytes
-            .try_into()
-            .map(|array| self.value = u64::from_ne_bytes(array));
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.mix(u64::from_ne_bytes(word));
+        }
+        let remainder = chunks.remainder();
+        if !remainder.is_empty() {
+            let mut word = [0; 8];
+            word[..remainder.len()].copy_from_slice(remainder);
+            self.mix(u64::from_ne_bytes(word));
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.mix(i);
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        // The same as `write(&i.to_ne_bytes())`, but without going through bytes.
+        let (first, second) = if cfg!(target_endian = "little") {
+            (i as u64, (i >> 64) as u64)
+        } else {
+            ((i >> 64) as u64, i as u64)
+        };
+        self.mix(first);
+        self.mix(second);
     }
 
     #[inline]
@@ -637,18 +4260,26 @@ impl Hasher for TypeIdHasher {
 }
 
 #[test]
+#[cfg(feature = "alloc")]
 fn type_id_hasher() {
     #[cfg(not(feature = "std"))]
     use alloc::vec::Vec;
     use core::any::TypeId;
     use core::hash::Hash;
+    // TypeId is no longer necessarily a u64 under the hood, so rather than transmuting, capture
+    // the bytes it feeds to the hasher and check they’re what comes out the other end.
+    #[derive(Default)]
+    struct BytesHasher(Vec<u8>);
+    impl Hasher for BytesHasher {
+        fn write(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes) }
+        fn finish(&self) -> u64 { unreachable!() }
+    }
     fn verify_hashing_with(type_id: TypeId) {
         let mut hasher = TypeIdHasher::default();
         type_id.hash(&mut hasher);
-        // SAFETY: u64 is valid for all bit patterns.
-        assert_eq!(hasher.finish(), unsafe {
-            core::mem::transmute::<TypeId, u64>(type_id)
-        });
+        let mut bytes = BytesHasher::default();
+        type_id.hash(&mut bytes);
+        assert_eq!(hasher.finish().to_ne_bytes(), &bytes.0[..]);
     }
     // Pick a variety of types, just to demonstrate it’s all sane. Normal, zero-sized, unsized, &c.
     verify_hashing_with(TypeId::of::<usize>());
@@ -657,3 +4288,60 @@ fn type_id_hasher() {
     verify_hashing_with(TypeId::of::<&str>());
     verify_hashing_with(TypeId::of::<Vec<u8>>());
 }
+
+// As above, without Vec to collect the bytes in.
+#[test]
+#[cfg(not(feature = "alloc"))]
+fn type_id_hasher_without_alloc() {
+    use core::any::TypeId;
+    use core::hash::Hash;
+    #[derive(Default)]
+    struct BytesHasher([u8; 16], usize);
+    impl Hasher for BytesHasher {
+        fn write(&mut self, bytes: &[u8]) {
+            self.0[self.1..self.1 + bytes.len()].copy_from_slice(bytes);
+            self.1 += bytes.len();
+        }
+        fn finish(&self) -> u64 { unreachable!() }
+    }
+    fn verify_hashing_with(type_id: TypeId) {
+        let mut hasher = TypeIdHasher::default();
+        type_id.hash(&mut hasher);
+        let mut bytes = BytesHasher::default();
+        type_id.hash(&mut bytes);
+        assert_eq!(hasher.finish().to_ne_bytes(), &bytes.0[..bytes.1]);
+    }
+    verify_hashing_with(TypeId::of::<usize>());
+    verify_hashing_with(TypeId::of::<()>());
+    verify_hashing_with(TypeId::of::<str>());
+    verify_hashing_with(TypeId::of::<&str>());
+    verify_hashing_with(TypeId::of::<[u8]>());
+}
+
+#[test]
+fn type_id_hasher_wider_inputs() {
+    fn hash_bytes(bytes: &[u8]) -> u64 {
+        let mut hasher = TypeIdHasher::default();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+    fn hash_u128(i: u128) -> u64 {
+        let mut hasher = TypeIdHasher::default();
+        hasher.write_u128(i);
+        hasher.finish()
+    }
+    let mut bytes = [0x5a; 16];
+    let base = hash_bytes(&bytes);
+    assert_ne!(base, 0, "equal halves cancelled out");
+    assert_eq!(hash_u128(u128::from_ne_bytes(bytes)), base);
+    // A change in either half must change the hash.
+    bytes[3] = 0;
+    assert_ne!(hash_bytes(&bytes), base);
+    bytes[3] = 0x5a;
+    bytes[12] = 0;
+    assert_ne!(hash_bytes(&bytes), base);
+    assert_eq!(hash_u128(u128::from_ne_bytes(bytes)), hash_bytes(&bytes));
+    // Odd sizes are fine too.
+    assert_ne!(hash_bytes(&[1, 2, 3]), hash_bytes(&[1, 2, 4]));
+    assert_ne!(hash_bytes(&[0; 9]), hash_bytes(&[0, 0, 0, 0, 0, 0, 0, 0, 1]));
+}
@@ -0,0 +1,231 @@
+//! Ambient, thread-local values, one of each type.
+
+use core::any::Any;
+use core::cell::RefCell;
+#[cfg(feature = "async")]
+use core::future::Future;
+#[cfg(feature = "async")]
+use core::pin::Pin;
+#[cfg(feature = "async")]
+use core::task::{Context, Poll};
+
+use crate::Map;
+
+std::thread_local! {
+    static MAP: RefCell<Map<dyn Any>> = RefCell::new(Map::new());
+}
+
+/// Sets the current thread’s value for the type `T` to `value`, or removes it if `None`,
+/// returning what it was.
+fn swap<T: 'static>(value: Option<T>) -> Option<T> {
+    MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        match value {
+            Some(value) => map.insert(value),
+            None => map.remove::<T>(),
+        }
+    })
+}
+
+/// Puts back the current thread’s previous value for the type `T` when dropped.
+struct Restore<T: 'static>(Option<T>);
+
+impl<T: 'static> Drop for Restore<T> {
+    fn drop(&mut self) {
+        let _ = swap(self.0.take());
+    }
+}
+
+/// A thread-local map of ambient values, one of each type, such as a request ID or tracing
+/// baggage, for code deep in a call stack to pick up without it being passed all the way down.
+///
+/// There’s one map per thread, reached through the associated functions here. Values are
+/// usually put in place for a while with [`scope`](ScopedLocalMap::scope), which overrides any
+/// outer value of the same type until its closure returns (or panics), and with the **async**
+/// feature, `scope_async` does the same for each poll of a future, making the value local to a
+/// task rather than a thread.
+///
+/// While a [`with`](ScopedLocalMap::with) closure runs, the map is borrowed, so changing it from
+/// inside one (with `set`, `remove` or `scope`) panics, as `RefCell` would.
+///
+/// ```rust
+/// use anymap::local::ScopedLocalMap;
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct RequestId(u64);
+///
+/// fn log(message: &str) -> String {
+///     ScopedLocalMap::with(|id: Option<&RequestId>| format!("{:?}: {}", id, message))
+/// }
+///
+/// ScopedLocalMap::scope(RequestId(1), || {
+///     assert_eq!(log("outer"), "Some(RequestId(1)): outer");
+///     ScopedLocalMap::scope(RequestId(2), || {
+///         assert_eq!(log("inner"), "Some(RequestId(2)): inner");
+///     });
+///     assert_eq!(ScopedLocalMap::get_cloned::<RequestId>(), Some(RequestId(1)));
+/// });
+/// assert_eq!(log("none"), "None: none");
+/// ```
+#[derive(Debug)]
+pub enum ScopedLocalMap {}
+
+impl ScopedLocalMap {
+    /// Calls `f` with a reference to the current thread’s value for the type `T`, if there is
+    /// one, returning what `f` returns.
+    #[inline]
+    pub fn with<T: 'static, R, F: FnOnce(Option<&T>) -> R>(f: F) -> R {
+        MAP.with(|map| f(map.borrow().get::<T>()))
+    }
+
+    /// Returns a clone of the current thread’s value for the type `T`, if there is one.
+    #[inline]
+    pub fn get_cloned<T: 'static + Clone>() -> Option<T> {
+        ScopedLocalMap::with(|value: Option<&T>| value.cloned())
+    }
+
+    /// Returns true if the current thread has a value for the type `T`.
+    #[inline]
+    pub fn contains<T: 'static>() -> bool {
+        MAP.with(|map| map.borrow().contains::<T>())
+    }
+
+    /// Sets the current thread’s value for the type `T`, until it’s set again or removed,
+    /// returning the previous value, if there was one.
+    #[inline]
+    pub fn set<T: 'static>(value: T) -> Option<T> {
+        swap(Some(value))
+    }
+
+    /// Removes the current thread’s value for the type `T`, returning it if there was one.
+    #[inline]
+    pub fn remove<T: 'static>() -> Option<T> {
+        swap::<T>(None)
+    }
+
+    /// Sets the current thread’s value for the type `T` to `value` while `f` runs, putting back
+    /// what was there before (or nothing) afterwards, even if `f` panics.
+    #[inline]
+    pub fn scope<T: 'static, R, F: FnOnce() -> R>(value: T, f: F) -> R {
+        let _restore = Restore(swap(Some(value)));
+        f()
+    }
+
+    /// Wraps `future` so that while it is polled, the current thread’s value for the type `T` is
+    /// `value`, making it local to the task rather than the thread: it follows the future from
+    /// thread to thread, and other tasks on the same thread don’t see it.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn scope_async<T: 'static, F: Future>(value: T, future: F) -> ScopedFuture<T, F> {
+        ScopedFuture {
+            value: Some(value),
+            future: Box::pin(future),
+        }
+    }
+}
+
+/// A future with a value for the [`ScopedLocalMap`] while it is polled.
+///
+/// This is created by [`ScopedLocalMap::scope_async`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct ScopedFuture<T, F> {
+    value: Option<T>,
+    future: Pin<Box<F>>,
+}
+
+// The value is moved in and out of the map on each poll, so it’s never pinned.
+#[cfg(feature = "async")]
+impl<T, F> Unpin for ScopedFuture<T, F> { }
+
+#[cfg(feature = "async")]
+impl<T: 'static, F: Future> Future for ScopedFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        let mut restore = Restore(swap(this.value.take()));
+        let output = this.future.as_mut().poll(cx);
+        // Take the value back for the next poll, and only then put back what was there before.
+        let previous = restore.0.take();
+        core::mem::forget(restore);
+        this.value = swap(previous);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_scoped_local_map() {
+        assert_eq!(ScopedLocalMap::set(A(1)), None);
+        let result = ScopedLocalMap::scope(A(2), || {
+            ScopedLocalMap::scope(B(3), || {
+                ScopedLocalMap::with(|a: Option<&A>| {
+                    ScopedLocalMap::with(|b: Option<&B>| (a.cloned(), b.cloned()))
+                })
+            })
+        });
+        assert_eq!(result, (Some(A(2)), Some(B(3))));
+        assert_eq!(ScopedLocalMap::get_cloned::<A>(), Some(A(1)));
+        assert!(!ScopedLocalMap::contains::<B>());
+
+        // Other threads have their own.
+        std::thread::spawn(|| assert!(!ScopedLocalMap::contains::<A>())).join().unwrap();
+
+        // Panicking still restores the outer value.
+        let panicked = std::panic::catch_unwind(|| ScopedLocalMap::scope(A(4), || panic!()));
+        assert!(panicked.is_err());
+        assert_eq!(ScopedLocalMap::remove::<A>(), Some(A(1)));
+        assert!(!ScopedLocalMap::contains::<A>());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_scope_async() {
+        use std::sync::Arc;
+        use std::task::{Wake, Waker};
+
+        struct Noop;
+
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) { }
+        }
+
+        /// Sees `A` on its first poll and returns it on its second.
+        struct Peek(Option<Option<A>>);
+
+        impl Future for Peek {
+            type Output = (Option<A>, Option<A>);
+
+            fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+                let now = ScopedLocalMap::get_cloned::<A>();
+                match self.0.take() {
+                    None => {
+                        self.0 = Some(now);
+                        Poll::Pending
+                    }
+                    Some(before) => Poll::Ready((before, now)),
+                }
+            }
+        }
+
+        fn assert_send<T: Send>(t: T) -> T {
+            t
+        }
+
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = assert_send(ScopedLocalMap::scope_async(A(1), Peek(None)));
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert!(!ScopedLocalMap::contains::<A>());
+        let _ = ScopedLocalMap::set(A(2));
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Ready((Some(A(1)), Some(A(1)))));
+        assert_eq!(ScopedLocalMap::get_cloned::<A>(), Some(A(2)));
+    }
+}
@@ -0,0 +1,173 @@
+//! A map of any number of values of each type.
+
+use core::any::Any;
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::vec;
+
+use crate::any::{Downcast, IntoBox};
+#[cfg(not(feature = "std"))]
+use crate::hashbrown::Map;
+#[cfg(feature = "std")]
+use crate::Map;
+
+/// A collection containing any number of values of each type, in the order they were pushed.
+///
+/// Where [`Map`] holds one value of each type, this holds a `Vec<T>` for each `T`, for event
+/// queues, handler registries and the like. It’s a `Map` underneath, with each `Vec<T>` stored
+/// as a value, and so the type of the vector, `Vec<T>`, must fit in `A`: for a
+/// `MultiMap<dyn CloneAny>`, `T` must be `Clone`.
+///
+/// ```rust
+/// use anymap::multi::MultiMap;
+///
+/// let mut handlers = MultiMap::<dyn core::any::Any>::new();
+/// handlers.push::<fn(u8) -> u8>(|x| x + 1);
+/// handlers.push::<fn(u8) -> u8>(|x| x * 2);
+/// handlers.push("not a handler");
+/// let result = handlers.get_all::<fn(u8) -> u8>().iter().fold(3, |x, f| f(x));
+/// assert_eq!(result, 8);
+/// assert_eq!(handlers.drain_all::<&str>().collect::<Vec<_>>(), ["not a handler"]);
+/// assert!(handlers.get_all::<&str>().is_empty());
+/// ```
+pub struct MultiMap<A: ?Sized + Downcast = dyn Any> {
+    map: Map<A>,
+}
+
+impl<A: ?Sized + Downcast> Default for MultiMap<A> {
+    #[inline]
+    fn default() -> MultiMap<A> {
+        MultiMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> Clone for MultiMap<A> where Box<A>: Clone {
+    #[inline]
+    fn clone(&self) -> MultiMap<A> {
+        MultiMap { map: self.map.clone() }
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Debug for MultiMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultiMap").field("types", &self.map.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> MultiMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> MultiMap<A> {
+        MultiMap { map: Map::new() }
+    }
+
+    /// Returns the number of types with at least one value in the collection.
+    #[inline]
+    pub fn type_count(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if there are no values in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes all values from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    /// Adds `value` after any other values of type `T`.
+    #[inline]
+    pub fn push<T>(&mut self, value: T) where Vec<T>: IntoBox<A> {
+        self.map.entry::<Vec<T>>().or_default().push(value)
+    }
+
+    /// Removes the last value of type `T` and returns it, or `None` if there are none.
+    pub fn pop<T>(&mut self) -> Option<T> where Vec<T>: IntoBox<A> {
+        let values = self.map.get_mut::<Vec<T>>()?;
+        let value = values.pop();
+        if values.is_empty() {
+            let _ = self.map.remove::<Vec<T>>();
+        }
+        value
+    }
+
+    /// Returns the values of type `T`, in the order they were pushed; empty if there are none.
+    #[inline]
+    pub fn get_all<T>(&self) -> &[T] where Vec<T>: IntoBox<A> {
+        self.map.get::<Vec<T>>().map_or(&[], |values| &values[..])
+    }
+
+    /// Returns the values of type `T` mutably, in the order they were pushed; empty if there are
+    /// none.
+    #[inline]
+    pub fn get_all_mut<T>(&mut self) -> &mut [T] where Vec<T>: IntoBox<A> {
+        self.map.get_mut::<Vec<T>>().map_or(&mut [], |values| &mut values[..])
+    }
+
+    /// Returns the number of values of type `T`.
+    #[inline]
+    pub fn len_of<T>(&self) -> usize where Vec<T>: IntoBox<A> {
+        self.get_all::<T>().len()
+    }
+
+    /// Removes all the values of type `T`, returning them in the order they were pushed.
+    #[inline]
+    pub fn drain_all<T>(&mut self) -> vec::IntoIter<T> where Vec<T>: IntoBox<A> {
+        self.map.remove::<Vec<T>>().unwrap_or_default().into_iter()
+    }
+
+    /// Returns the underlying map, of a `Vec<T>` for each `T`.
+    #[inline]
+    pub fn as_map(&self) -> &Map<A> {
+        &self.map
+    }
+
+    /// Returns the underlying map, of a `Vec<T>` for each `T`.
+    #[inline]
+    pub fn into_inner(self) -> Map<A> {
+        self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+    use crate::CloneAny;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_multi_map() {
+        let mut map = MultiMap::<dyn CloneAny>::new();
+        assert!(map.is_empty());
+        map.push(A(1));
+        map.push(A(2));
+        map.push(B(3));
+        assert_eq!((map.type_count(), map.len_of::<A>(), map.len_of::<u8>()), (2, 2, 0));
+        assert_eq!(map.get_all::<A>(), [A(1), A(2)]);
+        assert!(map.get_all::<u8>().is_empty());
+        map.get_all_mut::<A>()[0].0 = 10;
+        assert!(map.get_all_mut::<u8>().is_empty());
+
+        let clone = map.clone();
+        assert_eq!(map.pop(), Some(B(3)));
+        assert_eq!(map.pop::<B>(), None);
+        assert_eq!(map.type_count(), 1);
+        assert_eq!(map.drain_all::<A>().collect::<Vec<_>>(), [A(10), A(2)]);
+        assert_eq!(map.drain_all::<A>().count(), 0);
+        assert!(map.is_empty());
+        assert_eq!(format!("{:?}", clone), "MultiMap { types: 2, .. }");
+        assert_eq!(clone.as_map().get::<Vec<B>>(), Some(&vec![B(3)]));
+        let mut map = clone.into_inner();
+        map.clear();
+    }
+}
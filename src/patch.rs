@@ -0,0 +1,220 @@
+//! Structured differences between maps, and applying them.
+
+use core::any::TypeId;
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::any::{ClonePartialEqAny, Downcast, IntoBox};
+
+#[inline]
+fn find<T: IntoBox<A>, A: ?Sized + Downcast>(values: &[Box<A>]) -> Option<&T> {
+    let value = values.iter().find(|value| Downcast::type_id(&***value) == TypeId::of::<T>())?;
+    // SAFETY: we just checked the type.
+    Some(unsafe { value.downcast_ref_unchecked() })
+}
+
+/// The changes that turn one map into another, as returned by [`Map::diff`](crate::Map::diff).
+///
+/// It lists the values added, those removed, and, for types whose value changed, both the old
+/// value and the new. All of them are clones, so a patch stands on its own, ready to be kept in
+/// an undo stack or sent off to bring a replica up to date.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use anymap::{ClonePartialEqAny, Map};
+///
+/// let mut before: Map<dyn ClonePartialEqAny> = Map::new();
+/// before.insert(1i32);
+/// before.insert('a');
+/// let mut after = before.clone();
+/// after.insert(2i32);
+/// after.remove::<char>();
+/// after.insert("new");
+///
+/// let patch = before.diff(&after);
+/// assert_eq!(patch.len(), 3);
+/// assert_eq!(patch.get_changed::<i32>(), Some((&1, &2)));
+/// assert_eq!(patch.get_removed::<char>(), Some(&'a'));
+/// assert_eq!(patch.get_added::<&str>(), Some(&"new"));
+/// assert!(before.diff(&before).is_empty());
+///
+/// // Applying it brings a copy of `before` up to date, and its inverse takes it back again.
+/// let mut replica = before.clone();
+/// replica.apply(patch.clone()).unwrap();
+/// assert_eq!(replica.get::<i32>(), Some(&2));
+/// replica.apply(patch.invert()).unwrap();
+/// assert_eq!(replica.get::<char>(), Some(&'a'));
+/// ```
+pub struct MapPatch<A: ?Sized + Downcast = dyn ClonePartialEqAny> {
+    pub(crate) added: Vec<Box<A>>,
+    pub(crate) removed: Vec<Box<A>>,
+    pub(crate) changed: Vec<(Box<A>, Box<A>)>,
+}
+
+impl<A: ?Sized + Downcast> MapPatch<A> {
+    /// Create an empty patch, which changes nothing.
+    #[inline]
+    pub fn new() -> MapPatch<A> {
+        MapPatch {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    /// Returns the values of all the types that were added, in arbitrary order.
+    #[inline]
+    pub fn added(&self) -> &[Box<A>] {
+        &self.added
+    }
+
+    /// Returns the values of all the types that were removed, as they were, in arbitrary order.
+    #[inline]
+    pub fn removed(&self) -> &[Box<A>] {
+        &self.removed
+    }
+
+    /// Returns the old and new values, in that order, of all the types whose value changed, in
+    /// arbitrary order.
+    #[inline]
+    pub fn changed(&self) -> &[(Box<A>, Box<A>)] {
+        &self.changed
+    }
+
+    /// Returns the value of type `T` that was added, if one was.
+    #[inline]
+    pub fn get_added<T: IntoBox<A>>(&self) -> Option<&T> {
+        find(&self.added)
+    }
+
+    /// Returns the value of type `T` that was removed, if one was.
+    #[inline]
+    pub fn get_removed<T: IntoBox<A>>(&self) -> Option<&T> {
+        find(&self.removed)
+    }
+
+    /// Returns the old and new values of type `T`, if its value changed.
+    #[inline]
+    pub fn get_changed<T: IntoBox<A>>(&self) -> Option<(&T, &T)> {
+        let (old, new) = self.changed.iter()
+            .find(|(old, _)| Downcast::type_id(&**old) == TypeId::of::<T>())?;
+        // SAFETY: we just checked the type, and the new value is always of the same one.
+        Some(unsafe { (old.downcast_ref_unchecked(), new.downcast_ref_unchecked()) })
+    }
+
+    /// Returns the patch that undoes this one, turning the map it produces back into the one it
+    /// was made from.
+    #[inline]
+    pub fn invert(self) -> MapPatch<A> {
+        MapPatch {
+            added: self.removed,
+            removed: self.added,
+            changed: self.changed.into_iter().map(|(old, new)| (new, old)).collect(),
+        }
+    }
+
+    /// Returns the number of types the patch adds, removes or changes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    /// Returns true if the patch changes nothing.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<A: ?Sized + Downcast> Default for MapPatch<A> {
+    #[inline]
+    fn default() -> MapPatch<A> {
+        MapPatch::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> Clone for MapPatch<A> where Box<A>: Clone {
+    #[inline]
+    fn clone(&self) -> MapPatch<A> {
+        MapPatch {
+            added: self.added.clone(),
+            removed: self.removed.clone(),
+            changed: self.changed.clone(),
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast + fmt::Debug> fmt::Debug for MapPatch<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapPatch")
+            .field("added", &self.added)
+            .field("removed", &self.removed)
+            .field("changed", &self.changed)
+            .finish()
+    }
+}
+
+/// A type for which a map wasn’t as a patch expected, found by [`Map::apply`](crate::Map::apply).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Conflict {
+    /// The type whose value wasn’t as expected.
+    pub type_id: TypeId,
+    /// How it differed.
+    pub kind: ConflictKind,
+}
+
+/// How the map differed from what the patch expected, in a [`Conflict`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// The patch adds a value, but the map already had one of that type.
+    Present,
+    /// The patch removes or changes a value, but the map had none of that type.
+    Missing,
+    /// The patch removes or changes a value, but the map’s value differed from the patch’s old
+    /// one.
+    Changed,
+    /// The patch has more than one entry for the type, whether to add, remove or change it, so
+    /// there’s no telling which it means.
+    Duplicate,
+}
+
+/// An error indicating that a patch didn’t fit the map it was applied to; see
+/// [`Map::apply`](crate::Map::apply).
+///
+/// It hands the patch back, unapplied, with the conflicts found.
+pub struct PatchError<A: ?Sized + Downcast = dyn ClonePartialEqAny> {
+    pub(crate) patch: MapPatch<A>,
+    pub(crate) conflicts: Vec<Conflict>,
+}
+
+impl<A: ?Sized + Downcast> PatchError<A> {
+    /// Returns the conflicts between the patch and the map, in arbitrary order.
+    #[inline]
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Returns the patch that couldn’t be applied.
+    #[inline]
+    pub fn into_patch(self) -> MapPatch<A> {
+        self.patch
+    }
+}
+
+impl<A: ?Sized + Downcast + fmt::Debug> fmt::Debug for PatchError<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PatchError")
+            .field("patch", &self.patch)
+            .field("conflicts", &self.conflicts)
+            .finish()
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Display for PatchError<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "patch conflicts with the map for {} type(s)", self.conflicts.len())
+    }
+}
+
+impl<A: ?Sized + Downcast + fmt::Debug> core::error::Error for PatchError<A> { }
@@ -0,0 +1,416 @@
+//! A persistent map, whose changes make new versions sharing structure with the old.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::hash::{BuildHasher, BuildHasherDefault};
+#[cfg(not(feature = "std"))]
+use alloc::{sync::Arc, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use crate::any::{Downcast, IntoBox};
+use crate::TypeIdHasher;
+
+/// How many bits of the hash each level of the trie consumes.
+const BITS: u32 = 5;
+
+#[inline]
+fn hash_of(type_id: TypeId) -> u64 {
+    BuildHasherDefault::<TypeIdHasher>::default().hash_one(type_id)
+}
+
+/// A node of a hash array mapped trie: at each level, the next five bits of an entry’s hash pick
+/// which of up to 32 children it goes in, and only the children present are stored.
+enum Node<A: ?Sized> {
+    Branch { bitmap: u32, children: Vec<Child<A>> },
+    /// Entries whose hashes are equal in every bit, so they can’t be told apart by going deeper.
+    Collision(u64, Vec<(TypeId, Arc<A>)>),
+}
+
+enum Child<A: ?Sized> {
+    /// An entry, with its hash kept so it needn’t be worked out again when it has to move down.
+    Leaf(u64, TypeId, Arc<A>),
+    Node(Arc<Node<A>>),
+}
+
+impl<A: ?Sized> Clone for Child<A> {
+    #[inline]
+    fn clone(&self) -> Child<A> {
+        match self {
+            Child::Leaf(hash, type_id, value) => Child::Leaf(*hash, *type_id, value.clone()),
+            Child::Node(node) => Child::Node(node.clone()),
+        }
+    }
+}
+
+impl<A: ?Sized> Node<A> {
+    #[inline]
+    fn slot(hash: u64, shift: u32) -> (u32, u32) {
+        let bit = 1 << ((hash >> shift) & 31);
+        (bit, bit - 1)
+    }
+
+    fn get(&self, hash: u64, shift: u32, type_id: TypeId) -> Option<&Arc<A>> {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let (bit, below) = Node::<A>::slot(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                match &children[(bitmap & below).count_ones() as usize] {
+                    Child::Leaf(_, id, value) => if *id == type_id { Some(value) } else { None },
+                    Child::Node(node) => node.get(hash, shift + BITS, type_id),
+                }
+            },
+            Node::Collision(_, entries) => {
+                entries.iter().find(|(id, _)| *id == type_id).map(|(_, value)| value)
+            },
+        }
+    }
+
+    /// A node holding just these two entries, with distinct `TypeId`s, at the level `shift`.
+    fn pair(shift: u32, a: (u64, TypeId, Arc<A>), b: (u64, TypeId, Arc<A>)) -> Node<A> {
+        if shift >= u64::BITS {
+            return Node::Collision(a.0, vec![(a.1, a.2), (b.1, b.2)]);
+        }
+        let (a_bit, _) = Node::<A>::slot(a.0, shift);
+        let (b_bit, _) = Node::<A>::slot(b.0, shift);
+        if a_bit == b_bit {
+            let child = Node::pair(shift + BITS, a, b);
+            return Node::Branch { bitmap: a_bit, children: vec![Child::Node(Arc::new(child))] };
+        }
+        let (a, b) = (Child::Leaf(a.0, a.1, a.2), Child::Leaf(b.0, b.1, b.2));
+        let children = if a_bit < b_bit { vec![a, b] } else { vec![b, a] };
+        Node::Branch { bitmap: a_bit | b_bit, children }
+    }
+
+    /// Returns a copy of this node with `value` stored for `type_id`, sharing everything off the
+    /// path to it, and whatever value it replaced.
+    fn insert(&self, hash: u64, shift: u32, type_id: TypeId, value: Arc<A>)
+        -> (Node<A>, Option<Arc<A>>)
+    {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let (bit, below) = Node::<A>::slot(hash, shift);
+                let index = (bitmap & below).count_ones() as usize;
+                let mut children = children.clone();
+                if bitmap & bit == 0 {
+                    children.insert(index, Child::Leaf(hash, type_id, value));
+                    return (Node::Branch { bitmap: bitmap | bit, children }, None);
+                }
+                let (child, previous) = match &children[index] {
+                    Child::Leaf(_, id, existing) if *id == type_id => {
+                        (Child::Leaf(hash, type_id, value), Some(existing.clone()))
+                    },
+                    Child::Leaf(other_hash, id, existing) => {
+                        let other = (*other_hash, *id, existing.clone());
+                        let node = Node::pair(shift + BITS, other, (hash, type_id, value));
+                        (Child::Node(Arc::new(node)), None)
+                    },
+                    Child::Node(node) => {
+                        let (node, previous) = node.insert(hash, shift + BITS, type_id, value);
+                        (Child::Node(Arc::new(node)), previous)
+                    },
+                };
+                children[index] = child;
+                (Node::Branch { bitmap: *bitmap, children }, previous)
+            },
+            Node::Collision(collision_hash, entries) => {
+                let mut entries = entries.clone();
+                let previous = match entries.iter_mut().find(|(id, _)| *id == type_id) {
+                    Some((_, existing)) => Some(core::mem::replace(existing, value)),
+                    None => {
+                        entries.push((type_id, value));
+                        None
+                    },
+                };
+                (Node::Collision(*collision_hash, entries), previous)
+            },
+        }
+    }
+
+    /// Returns a copy of this node without `type_id`’s entry, or `None` if that leaves it empty,
+    /// along with the value removed; or `None` if there was no such entry, and nothing to copy.
+    fn remove(&self, hash: u64, shift: u32, type_id: TypeId)
+        -> Option<(Option<Node<A>>, Arc<A>)>
+    {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let (bit, below) = Node::<A>::slot(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                let index = (bitmap & below).count_ones() as usize;
+                let (replacement, removed) = match &children[index] {
+                    Child::Leaf(_, id, value) if *id == type_id => (None, value.clone()),
+                    Child::Leaf(..) => return None,
+                    Child::Node(node) => {
+                        let (node, removed) = node.remove(hash, shift + BITS, type_id)?;
+                        // A node left with a single entry is pulled up into its parent, so the
+                        // trie stays no deeper than it needs to be.
+                        let child = node.map(|node| match node.into_leaf() {
+                            Ok(leaf) => leaf,
+                            Err(node) => Child::Node(Arc::new(node)),
+                        });
+                        (child, removed)
+                    },
+                };
+                let mut children = children.clone();
+                let bitmap = match replacement {
+                    Some(child) => {
+                        children[index] = child;
+                        *bitmap
+                    },
+                    None => {
+                        let _ = children.remove(index);
+                        bitmap & !bit
+                    },
+                };
+                if children.is_empty() {
+                    return Some((None, removed));
+                }
+                Some((Some(Node::Branch { bitmap, children }), removed))
+            },
+            Node::Collision(collision_hash, entries) => {
+                let index = entries.iter().position(|(id, _)| *id == type_id)?;
+                let mut entries = entries.clone();
+                let (_, removed) = entries.remove(index);
+                Some((Some(Node::Collision(*collision_hash, entries)), removed))
+            },
+        }
+    }
+
+    /// Returns this node’s only entry as a leaf, if it has just one.
+    fn into_leaf(self) -> Result<Child<A>, Node<A>> {
+        match self {
+            Node::Branch { mut children, bitmap } => {
+                if children.len() == 1 && matches!(children[0], Child::Leaf(..)) {
+                    Ok(children.remove(0))
+                } else {
+                    Err(Node::Branch { bitmap, children })
+                }
+            },
+            Node::Collision(hash, mut entries) => {
+                if entries.len() == 1 {
+                    let (type_id, value) = entries.remove(0);
+                    Ok(Child::Leaf(hash, type_id, value))
+                } else {
+                    Err(Node::Collision(hash, entries))
+                }
+            },
+        }
+    }
+}
+
+/// A persistent collection containing zero or one values for any given type: rather than
+/// changing the map, [`insert`](PersistentMap::insert) and [`remove`](PersistentMap::remove)
+/// return a new version of it, leaving the old one as it was.
+///
+/// The versions share structure, after the manner of the `im` crate: the map is a hash array
+/// mapped trie whose nodes and values are each held by `Arc`, so a new version copies only the
+/// handful of nodes on the path to the changed entry, and shares every other node and value with
+/// the version it came from. Cloning is just an `Arc` clone. That makes it a good fit for undo
+/// histories, or for handing a consistent configuration to other threads while preparing the
+/// next.
+///
+/// Values are never changed in place, so there’s no `get_mut`: insert a changed copy instead.
+/// Sharing versions across threads needs the values to be `Send + Sync`, as they are by default.
+///
+/// It needs only the `alloc` feature.
+///
+/// ```rust
+/// use anymap::persistent::PersistentMap;
+///
+/// let empty = PersistentMap::<dyn core::any::Any + Send + Sync>::new();
+/// let one = empty.insert(1i32);
+/// let two = one.insert(String::from("shared"));
+/// let three = two.insert(3i32);
+/// assert!(empty.is_empty());
+/// assert_eq!(one.get::<i32>(), Some(&1));
+/// assert_eq!(three.get::<i32>(), Some(&3));
+/// // The string was never replaced, so both versions share it.
+/// assert!(core::ptr::eq(two.get::<String>().unwrap(), three.get::<String>().unwrap()));
+/// assert_eq!(three.remove::<String>().len(), 1);
+/// ```
+pub struct PersistentMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    root: Arc<Node<A>>,
+    len: usize,
+}
+
+impl<A: ?Sized + Downcast> Clone for PersistentMap<A> {
+    #[inline]
+    fn clone(&self) -> PersistentMap<A> {
+        PersistentMap {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> Default for PersistentMap<A> {
+    #[inline]
+    fn default() -> PersistentMap<A> {
+        PersistentMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Debug for PersistentMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PersistentMap").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> PersistentMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> PersistentMap<A> {
+        PersistentMap {
+            root: Arc::new(Node::Branch { bitmap: 0, children: Vec::new() }),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
+        self.root.get(hash_of(type_id), 0, type_id)
+            // SAFETY: the trie only ever holds a T under TypeId::of::<T>().
+            .map(|value| unsafe { value.downcast_ref_unchecked::<T>() })
+    }
+
+    /// Returns a new version of the collection with `value` stored for the type `T`, replacing
+    /// any value of that type. This version is left as it was.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&self, value: T) -> PersistentMap<A> {
+        let type_id = TypeId::of::<T>();
+        let value = Arc::from(value.into_box());
+        let (root, previous) = self.root.insert(hash_of(type_id), 0, type_id, value);
+        PersistentMap {
+            root: Arc::new(root),
+            len: self.len + previous.is_none() as usize,
+        }
+    }
+
+    /// Returns a new version of the collection without the value for the type `T`. This version
+    /// is left as it was; if it had no such value, the new version simply shares all of it.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&self) -> PersistentMap<A> {
+        let type_id = TypeId::of::<T>();
+        match self.root.remove(hash_of(type_id), 0, type_id) {
+            None => self.clone(),
+            Some((None, _)) => PersistentMap::new(),
+            Some((Some(root), _)) => PersistentMap {
+                root: Arc::new(root),
+                len: self.len - 1,
+            },
+        }
+    }
+
+    /// Returns true if the two versions are the same one, or one is an unchanged clone of the
+    /// other, in which case they necessarily hold the same values. This is a pointer comparison,
+    /// and so is cheap.
+    #[inline]
+    pub fn ptr_eq(&self, other: &PersistentMap<A>) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+    #[derive(Clone, Debug, PartialEq)] struct C(i32);
+
+    type Map = PersistentMap<dyn Any + Send + Sync>;
+
+    #[test]
+    fn test_persistent_map() {
+        let empty = Map::new();
+        let one = empty.insert(A(1));
+        let two = one.insert(B(2));
+        let three = two.insert(A(3));
+        assert_eq!((empty.len(), one.len(), two.len(), three.len()), (0, 1, 2, 2));
+        assert_eq!(one.get::<A>(), Some(&A(1)));
+        assert!(!one.contains::<B>());
+        assert_eq!(three.get::<A>(), Some(&A(3)));
+        assert!(core::ptr::eq(two.get::<B>().unwrap(), three.get::<B>().unwrap()));
+
+        let removed = three.remove::<B>();
+        assert_eq!((removed.len(), removed.get::<B>()), (1, None));
+        assert_eq!(three.get::<B>(), Some(&B(2)));
+        assert!(removed.remove::<C>().ptr_eq(&removed));
+        assert!(removed.remove::<A>().is_empty());
+        assert_eq!(format!("{:?}", three), "PersistentMap { len: 2, .. }");
+    }
+
+    #[test]
+    fn test_persistent_map_many() {
+        // Enough types to need more than one level of the trie.
+        let mut map = Map::new();
+        macro_rules! each {
+            ($($t:ty = $v:expr),*) => {
+                $(map = map.insert::<$t>($v);)*
+                $(assert_eq!(map.get::<$t>(), Some(&$v));)*
+                let full = map.clone();
+                $(map = map.remove::<$t>(); assert!(!map.contains::<$t>());)*
+                assert!(map.is_empty());
+                $(assert_eq!(full.get::<$t>(), Some(&$v));)*
+            }
+        }
+        each!(
+            u8 = 1, u16 = 2, u32 = 3, u64 = 4, u128 = 5, usize = 6, i8 = 7, i16 = 8, i32 = 9,
+            i64 = 10, i128 = 11, isize = 12, char = 'a', bool = true, A = A(13), B = B(14),
+            C = C(15), (u8,) = (16,), (u16,) = (17,), (u32,) = (18,), (u64,) = (19,),
+            (i8,) = (20,), (i16,) = (21,), (i32,) = (22,), (i64,) = (23,), (char,) = ('b',),
+            (bool,) = (false,), [u8; 1] = [24], [u8; 2] = [25; 2], [u8; 3] = [26; 3],
+            [u8; 4] = [27; 4], [u8; 5] = [28; 5], [u8; 6] = [29; 6], [u8; 7] = [30; 7]
+        );
+    }
+
+    #[test]
+    fn test_persistent_map_collisions() {
+        // Force every entry into the same hash, so they all end up in one collision node.
+        let ids = [TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>()];
+        let mut root: Node<dyn Any + Send + Sync> = Node::Branch { bitmap: 0, children: Vec::new() };
+        for (i, id) in ids.iter().enumerate() {
+            root = root.insert(0, 0, *id, Arc::new(i)).0;
+        }
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(root.get(0, 0, *id).unwrap().downcast_ref::<usize>(), Some(&i));
+        }
+        let (replaced, previous) = root.insert(0, 0, ids[1], Arc::new(10usize));
+        assert_eq!(previous.unwrap().downcast_ref::<usize>(), Some(&1));
+        assert_eq!(replaced.get(0, 0, ids[1]).unwrap().downcast_ref::<usize>(), Some(&10));
+        let root = root.remove(0, 0, ids[0]).unwrap().0.unwrap();
+        let root = root.remove(0, 0, ids[1]).unwrap().0.unwrap();
+        // The last entry is pulled all the way back up to the root.
+        match &root {
+            Node::Branch { children, .. } => assert!(matches!(children[..], [Child::Leaf(..)])),
+            Node::Collision(..) => panic!("collision node left behind"),
+        }
+        assert!(root.remove(0, 0, ids[2]).unwrap().0.is_none());
+    }
+}
@@ -0,0 +1,51 @@
+//! A registered way of making a value of a type, for storing in a map.
+
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+/// How to make a value of type `T` when a map has none, as registered by
+/// [`Map::register_default`](crate::Map::register_default).
+///
+/// A map holds one for each `T` under the type `DefaultFactory<T>`, so it’s counted among the
+/// map’s values. The function is shared, so a `DefaultFactory` is cheap to clone and can go in
+/// any map, be it `Map<dyn CloneAny + Send + Sync>` or just an `AnyMap`.
+///
+/// ```rust
+/// use anymap::DefaultFactory;
+///
+/// let factory = DefaultFactory::new(|| String::from("default"));
+/// assert_eq!(factory.make(), "default");
+/// ```
+pub struct DefaultFactory<T> {
+    make: Arc<dyn Fn() -> T + Send + Sync>,
+}
+
+impl<T> DefaultFactory<T> {
+    /// Creates a factory that makes values with `f`.
+    #[inline]
+    pub fn new<F: Fn() -> T + Send + Sync + 'static>(f: F) -> DefaultFactory<T> {
+        DefaultFactory { make: Arc::new(f) }
+    }
+
+    /// Makes a value.
+    #[inline]
+    pub fn make(&self) -> T {
+        (self.make)()
+    }
+}
+
+impl<T> Clone for DefaultFactory<T> {
+    #[inline]
+    fn clone(&self) -> DefaultFactory<T> {
+        DefaultFactory { make: self.make.clone() }
+    }
+}
+
+impl<T> fmt::Debug for DefaultFactory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("DefaultFactory")
+    }
+}
@@ -0,0 +1,73 @@
+//! Support for [`bevy_reflect`], so that a `Map<dyn Reflect>` can be inspected and edited by
+//! reflection, as game editors and other tooling do.
+//!
+//! With the `reflect` feature, `dyn Reflect` can be a map’s `A` parameter, like `dyn Any`: such a
+//! map accepts every `T: Reflect`, and hands its values out as `&dyn Reflect` from
+//! [`values`](crate::Map::values) and [`get_reflect`](crate::Map::get_reflect). On top of that,
+//! [`reflect_path`](crate::Map::reflect_path) reaches into a value by a reflection path, and
+//! [`try_apply`](crate::Map::try_apply) applies a partial update, such as a `DynamicStruct`,
+//! to the value of the type it represents.
+//!
+//! ```rust
+//! use anymap::Map;
+//! use bevy_reflect::{DynamicStruct, Reflect};
+//!
+//! #[derive(Reflect, Debug, PartialEq)]
+//! struct Window { title: String, width: u32, height: u32 }
+//!
+//! let mut map: Map<dyn Reflect> = Map::new();
+//! map.insert(Window { title: "main".into(), width: 800, height: 600 });
+//!
+//! let type_id = core::any::TypeId::of::<Window>();
+//! let width = map.reflect_path(type_id, "width").unwrap();
+//! assert_eq!(width.try_downcast_ref::<u32>(), Some(&800));
+//!
+//! let mut update = DynamicStruct::default();
+//! update.set_represented_type(Some(<Window as bevy_reflect::Typed>::type_info()));
+//! update.insert("width", 1024u32);
+//! map.try_apply(&update).unwrap();
+//! assert_eq!(map.get::<Window>().unwrap().width, 1024);
+//! assert_eq!(map.get::<Window>().unwrap().height, 600);
+//! ```
+
+use core::any::TypeId;
+use core::fmt;
+
+use bevy_reflect::{ApplyError, Reflect, ReflectPathError};
+
+__implement_any!(Reflect);
+
+/// An error indicating that a value in a map couldn’t be reached or updated by reflection; see
+/// [`Map::reflect_path`](crate::Map::reflect_path) and [`Map::try_apply`](crate::Map::try_apply).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReflectError<'p> {
+    /// The map had no value of the type.
+    Missing(TypeId),
+    /// The value given to apply doesn’t say what type it represents.
+    Unrepresented,
+    /// The path didn’t lead anywhere in the value.
+    Path(ReflectPathError<'p>),
+    /// The update couldn’t be applied to the value.
+    Apply(ApplyError),
+}
+
+impl fmt::Display for ReflectError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReflectError::Missing(type_id) => write!(f, "no value of type {:?} in the map", type_id),
+            ReflectError::Unrepresented => f.write_str("value doesn’t represent any type"),
+            ReflectError::Path(error) => fmt::Display::fmt(error, f),
+            ReflectError::Apply(error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for ReflectError<'_> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReflectError::Apply(error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Values that last only for a scope, after which whatever they replaced comes back.
+
+use core::any::TypeId;
+use core::fmt;
+use core::hash::BuildHasherDefault;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashSet;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+
+use crate::TypeIdHasher;
+
+/// A guard that puts back a map’s previous value for the type `T` when dropped,
+/// dereferencing to the map meanwhile.
+///
+/// This is created by `Map::insert_scoped`, from either of the std and hashbrown
+/// implementations; `M` is the map.
+pub struct ScopedGuard<'a, T, M> {
+    pub(crate) map: &'a mut M,
+    pub(crate) previous: Option<T>,
+    // Puts `previous` back, or removes the value if there was none. It’s a function pointer
+    // because `Drop` can’t be implemented for just the maps that know how to do that.
+    pub(crate) restore: fn(&mut M, Option<T>),
+}
+
+impl<'a, T, M> ScopedGuard<'a, T, M> {
+    /// Returns a reference to the value that will be put back, if there was one. This is
+    /// an associated function, lest it get in the way of `Map`’s methods.
+    #[inline]
+    pub fn previous(this: &Self) -> Option<&T> {
+        this.previous.as_ref()
+    }
+}
+
+impl<'a, T, M> core::ops::Deref for ScopedGuard<'a, T, M> {
+    type Target = M;
+
+    #[inline]
+    fn deref(&self) -> &M {
+        self.map
+    }
+}
+
+impl<'a, T, M> core::ops::DerefMut for ScopedGuard<'a, T, M> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut M {
+        self.map
+    }
+}
+
+impl<'a, T, M> Drop for ScopedGuard<'a, T, M> {
+    fn drop(&mut self) {
+        (self.restore)(self.map, self.previous.take());
+    }
+}
+
+impl<'a, T: fmt::Debug, M: fmt::Debug> fmt::Debug for ScopedGuard<'a, T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScopedGuard")
+            .field("map", &self.map)
+            .field("previous", &self.previous)
+            .finish()
+    }
+}
+
+/// A map with nested scopes, as of variables in a language: a value inserted in a scope shadows
+/// any of the same type from outer scopes until that scope is popped, when everything inserted in
+/// it goes and the shadowed values come back.
+///
+/// `M` is the map it wraps, a `Map` from either of the std and hashbrown implementations, made
+/// into a `ScopedMap` with `Map::into_scoped`. Before any scope is pushed, the map is in its base
+/// scope, which can’t be popped and where changes are just as in a `Map`. Changes made in place,
+/// through a mutable reference to a value, are like assigning to a variable: they stick to the
+/// value in whichever scope it was inserted. Everything that can’t change the map is available
+/// through `Deref` to the inner `Map`.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// let mut data = anymap::AnyMap::new();
+/// data.insert(1i32);
+/// let mut data = data.into_scoped();
+/// data.push_scope();
+/// data.insert(2i32);
+/// data.insert("inner");
+/// assert_eq!(data.get::<i32>(), Some(&2));
+/// data.pop_scope();
+/// assert_eq!(data.get::<i32>(), Some(&1));
+/// assert!(!data.contains::<&str>());
+/// ```
+#[derive(Debug)]
+pub struct ScopedMap<M> {
+    pub(crate) map: M,
+    pub(crate) scopes: Vec<Scope<M>>,
+}
+
+/// One scope pushed onto a `ScopedMap`.
+#[derive(Debug)]
+pub(crate) struct Scope<M> {
+    /// The types with a value inserted in this scope.
+    pub(crate) inserted: HashSet<TypeId, BuildHasherDefault<TypeIdHasher>>,
+    /// The values those shadowed, for the types that had one.
+    pub(crate) shadowed: M,
+}
+
+impl<M> ScopedMap<M> {
+    /// Returns the number of scopes pushed and not yet popped, which is zero in the base scope.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+}
+
+impl<M> core::ops::Deref for ScopedMap<M> {
+    type Target = M;
+
+    #[inline]
+    fn deref(&self) -> &M {
+        &self.map
+    }
+}
+
+// The methods for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+macro_rules! impl_scoped_map {
+    () => {
+        impl<A: ?Sized + Downcast> crate::scoped::ScopedMap<Map<A>> {
+            /// Create an empty collection.
+            #[inline]
+            pub fn new() -> Self {
+                Map::new().into_scoped()
+            }
+        }
+
+        impl<A: ?Sized + Downcast> Default for crate::scoped::ScopedMap<Map<A>> {
+            #[inline]
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> Map<A, S> {
+            /// Converts this into a [`ScopedMap`](crate::scoped::ScopedMap), with all the values
+            /// already in it in the base scope.
+            #[inline]
+            pub fn into_scoped(self) -> crate::scoped::ScopedMap<Self> {
+                crate::scoped::ScopedMap {
+                    map: self,
+                    scopes: Vec::new(),
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> crate::scoped::ScopedMap<Map<A, S>> {
+            /// Enters a new scope.
+            #[inline]
+            pub fn push_scope(&mut self) where S: Clone {
+                self.scopes.push(crate::scoped::Scope {
+                    inserted: Default::default(),
+                    shadowed: Map::with_hasher(self.map.hasher().clone()),
+                });
+            }
+
+            /// Leaves the innermost scope, removing every value inserted in it and putting back
+            /// those they shadowed. Returns false, doing nothing, if in the base scope.
+            pub fn pop_scope(&mut self) -> bool {
+                let mut scope = match self.scopes.pop() {
+                    Some(scope) => scope,
+                    None => return false,
+                };
+                for type_id in scope.inserted {
+                    let _ = match scope.shadowed.raw.remove(&type_id) {
+                        Some(value) => self.map.raw.insert(type_id, value),
+                        None => self.map.remove_raw(&type_id),
+                    };
+                }
+                true
+            }
+
+            /// Sets the value stored in the innermost scope for the type `T`, shadowing any from
+            /// outer scopes. If that scope already had a value of type `T`, that value is
+            /// returned. Otherwise, `None` is returned.
+            #[inline]
+            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+                let previous = self.map.insert(value);
+                if let Some(scope) = self.scopes.last_mut() {
+                    if scope.inserted.insert(TypeId::of::<T>()) {
+                        if let Some(previous) = previous {
+                            let _ = scope.shadowed.raw.insert(TypeId::of::<T>(), previous.into_box());
+                        }
+                        return None;
+                    }
+                }
+                previous
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// if it exists, from whichever scope it was inserted in.
+            #[inline]
+            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+                self.map.get_mut()
+            }
+
+            /// Removes the `T` value inserted in the innermost scope, returning it if there was
+            /// one or `None` if there was not. Any value it shadowed shows through again; values
+            /// from outer scopes are never removed.
+            #[inline]
+            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+                let scope = match self.scopes.last_mut() {
+                    Some(scope) => scope,
+                    None => return self.map.remove(),
+                };
+                if !scope.inserted.remove(&TypeId::of::<T>()) {
+                    return None;
+                }
+                let removed = self.map.remove();
+                if let Some(value) = scope.shadowed.raw.remove(&TypeId::of::<T>()) {
+                    self.map.record_type_name::<T>();
+                    let _ = self.map.raw.insert(TypeId::of::<T>(), value);
+                }
+                removed
+            }
+
+            /// Leaves every scope, and converts this into the inner `Map`, with the values of
+            /// the base scope.
+            #[inline]
+            pub fn into_inner(mut self) -> Map<A, S> {
+                while self.pop_scope() { }
+                self.map
+            }
+        }
+    }
+}
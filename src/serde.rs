@@ -0,0 +1,544 @@
+//! Serializing maps with [serde](https://docs.rs/serde), by way of
+//! [erased-serde](https://docs.rs/erased-serde).
+//!
+//! A `TypeId` is no good for telling a type apart in a serialized map, since it can differ
+//! between builds, so each type to be serialized is given a stable name in a [`Registry`], and
+//! the map is serialized as a map from those names to the values. The values must implement
+//! `Serialize`, which a map of [`dyn SerializeAny`](SerializeAny) ensures. To deserialize the
+//! map again, the registry also needs to know how to deserialize each type.
+//!
+//! ```rust
+//! use anymap::Map;
+//! use anymap::serde::{Registry, SerializeAny};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+//! struct Volume(u8);
+//!
+//! let mut registry = Registry::new();
+//! registry.register_deserialize::<Volume>("volume");
+//! registry.register_deserialize::<String>("player");
+//!
+//! let mut map: Map<dyn SerializeAny> = Map::new();
+//! map.insert(Volume(7));
+//! map.insert(String::from("Ferris"));
+//!
+//! let json = serde_json::to_string(&map.serialize_with(&registry)).unwrap();
+//! assert_eq!(json, r#"{"player":"Ferris","volume":7}"#);
+//!
+//! let mut deserializer = serde_json::Deserializer::from_str(&json);
+//! let loaded: Map<dyn SerializeAny> = Map::deserialize(&mut deserializer, &registry).unwrap();
+//! assert_eq!(loaded.get::<Volume>(), Some(&Volume(7)));
+//! ```
+//!
+//! Types change, so a type can be registered at a version, with a function to migrate older
+//! versions’ values, by [`Registry::register_versioned`].
+//!
+//! Alternatively, a map of a trait object that serializes along with its type, as
+//! [typetag](https://docs.rs/typetag) arranges, needs no registry: see
+//! [`Map::serialize_tagged`](crate::Map::serialize_tagged).
+
+use core::any::{Any, TypeId};
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::Cow, boxed::Box, collections::{BTreeMap, BTreeSet}, format, sync::Arc, vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{borrow::Cow, collections::{BTreeMap, BTreeSet}, sync::Arc};
+
+use ::serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, Deserializer, MapAccess, Visitor,
+};
+use ::serde::ser::{Error as _, Serialize, SerializeMap as _, Serializer};
+
+use crate::any::{Downcast, IntoBox};
+use crate::StableKey;
+
+#[doc(hidden)]
+pub trait SerializeToAny {
+    /// Get `self` as an erased-serde trait object; trait upcasting is too new for us.
+    fn as_serialize(&self) -> &dyn erased_serde::Serialize;
+}
+
+impl<T: Serialize> SerializeToAny for T {
+    #[inline]
+    fn as_serialize(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+}
+
+/// [`Any`], but with serialization.
+///
+/// Every type with no non-`'static` references that implements `Serialize` implements
+/// `SerializeAny`, and `dyn SerializeAny` implements `Serialize` in turn.
+pub trait SerializeAny: Any + SerializeToAny { }
+impl<T: Any + Serialize> SerializeAny for T { }
+__implement_any!(SerializeAny);
+__implement_any!(SerializeAny + Send);
+__implement_any!(SerializeAny + Send + Sync);
+
+macro_rules! impl_serialize {
+    ($t:ty) => {
+        impl Serialize for $t {
+            #[inline]
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                erased_serde::serialize(self.as_serialize(), serializer)
+            }
+        }
+    }
+}
+
+impl_serialize!(dyn SerializeAny);
+impl_serialize!(dyn SerializeAny + Send);
+impl_serialize!(dyn SerializeAny + Send + Sync);
+
+/// A function deserializing a value into a box of `A`.
+type DeserializeFn<A> = dyn for<'de> Fn(&mut dyn erased_serde::Deserializer<'de>)
+    -> Result<Box<A>, erased_serde::Error> + Send + Sync;
+
+/// A function deserializing a value of an older version into a box of `A`.
+type MigrateFn<A> = dyn for<'de> Fn(u32, &mut dyn erased_serde::Deserializer<'de>)
+    -> Result<Box<A>, erased_serde::Error> + Send + Sync;
+
+struct Entry<A: ?Sized> {
+    name: &'static str,
+    version: u32,
+    deserialize: Option<Arc<DeserializeFn<A>>>,
+    migrate: Option<Arc<MigrateFn<A>>>,
+}
+
+impl<A: ?Sized> Entry<A> {
+    #[inline]
+    fn new(name: &'static str, deserialize: Option<Arc<DeserializeFn<A>>>) -> Entry<A> {
+        Entry { name, version: 0, deserialize, migrate: None }
+    }
+}
+
+impl<A: ?Sized> Clone for Entry<A> {
+    #[inline]
+    fn clone(&self) -> Entry<A> {
+        Entry {
+            name: self.name,
+            version: self.version,
+            deserialize: self.deserialize.clone(),
+            migrate: self.migrate.clone(),
+        }
+    }
+}
+
+/// Returns the key for version `version` of the type named `name`: the name itself for version
+/// 0, and `name@version` after that.
+fn versioned_name(name: &'static str, version: u32) -> Cow<'static, str> {
+    match version {
+        0 => Cow::Borrowed(name),
+        _ => Cow::Owned(format!("{}@{}", name, version)),
+    }
+}
+
+/// Returns the tag for version `version` of the type named `name`.
+#[inline]
+fn versioned_tag(name: &'static str, version: u32) -> StableKey {
+    StableKey::from_name(&versioned_name(name, version))
+}
+
+/// The stable names of the types that may be serialized in maps of `A`, for keying them in
+/// serialized maps, and how to deserialize them.
+///
+/// A type registered with [`register`](Registry::register) can only be serialized; one
+/// registered with [`register_deserialize`](Registry::register_deserialize) or
+/// [`register_with`](Registry::register_with) can be deserialized by
+/// [`Map::deserialize`](crate::Map::deserialize) too.
+///
+/// Each name also gives its type a fixed-size tag, the name’s [`StableKey`], which
+/// [`Map::serialize_compact`](crate::Map::serialize_compact) uses in place of the name.
+///
+/// A type registered with [`register_versioned`](Registry::register_versioned) at a version
+/// after 0 is keyed by `name@version` (or its tag) instead, and values keyed by its name alone or
+/// by an older version are handed to its migration function.
+pub struct Registry<A: ?Sized = dyn SerializeAny> {
+    entries: BTreeMap<TypeId, Entry<A>>,
+    by_name: BTreeMap<&'static str, TypeId>,
+    by_tag: BTreeMap<StableKey, (TypeId, u32)>,
+}
+
+impl<A: ?Sized> Registry<A> {
+    /// Create an empty registry.
+    #[inline]
+    pub fn new() -> Registry<A> {
+        Registry {
+            entries: BTreeMap::new(),
+            by_name: BTreeMap::new(),
+            by_tag: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, type_id: TypeId, entry: Entry<A>) {
+        if self.by_name.get(entry.name).is_some_and(|&other| other != type_id) {
+            panic!("stable name {:?} registered for two types", entry.name);
+        }
+        for version in 0..=entry.version {
+            let tag = versioned_tag(entry.name, version);
+            if self.by_tag.get(&tag).is_some_and(|&(other, _)| other != type_id) {
+                panic!("stable name {:?} has the same tag as another type’s", entry.name);
+            }
+        }
+        if let Some(old) = self.entries.get(&type_id) {
+            let _ = self.by_name.remove(old.name);
+            for version in 0..=old.version {
+                let _ = self.by_tag.remove(&versioned_tag(old.name, version));
+            }
+        }
+        let _ = self.by_name.insert(entry.name, type_id);
+        for version in 0..=entry.version {
+            let _ = self.by_tag.insert(versioned_tag(entry.name, version), (type_id, version));
+        }
+        let _ = self.entries.insert(type_id, entry);
+    }
+
+    /// Registers `name` as the stable name of the type `T`, for serializing only, replacing
+    /// anything registered for `T` before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is already registered for another type, since the two couldn’t be told
+    /// apart once serialized.
+    #[inline]
+    pub fn register<T: 'static>(&mut self, name: &'static str) {
+        self.insert(TypeId::of::<T>(), Entry::new(name, None));
+    }
+
+    /// Registers `name` as the stable name of the type `T`, which is deserialized with its own
+    /// `Deserialize` implementation, replacing anything registered for `T` before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is already registered for another type.
+    #[inline]
+    pub fn register_deserialize<T>(&mut self, name: &'static str)
+    where
+        T: IntoBox<A> + DeserializeOwned,
+        A: Downcast,
+    {
+        self.register_with(name, |deserializer| erased_serde::deserialize::<T>(deserializer));
+    }
+
+    /// Registers `name` as the stable name of the type `T`, which is deserialized with
+    /// `deserialize`, replacing anything registered for `T` before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is already registered for another type.
+    pub fn register_with<T, F>(&mut self, name: &'static str, deserialize: F)
+    where
+        T: IntoBox<A>,
+        A: Downcast,
+        F: for<'de> Fn(&mut dyn erased_serde::Deserializer<'de>) -> Result<T, erased_serde::Error>,
+        F: Send + Sync + 'static,
+    {
+        let deserialize = move |deserializer: &mut dyn erased_serde::Deserializer<'_>| {
+            deserialize(deserializer).map(T::into_box)
+        };
+        self.insert(TypeId::of::<T>(), Entry::new(name, Some(Arc::new(deserialize))));
+    }
+
+    /// Registers `name` as the stable name of the type `T` at version `version`, replacing
+    /// anything registered for `T` before.
+    ///
+    /// Values of the current version are deserialized with `T`’s own `Deserialize`
+    /// implementation. A value serialized by an older version of the program, keyed by the name
+    /// alone (version 0) or by `name@old_version`, is passed to `migrate(old_version,
+    /// deserializer)` instead, to be deserialized as whatever it was then and turned into a `T`.
+    /// Values of versions later than `version` can’t be deserialized.
+    ///
+    /// ```rust
+    /// use anymap::Map;
+    /// use anymap::serde::{Registry, SerializeAny};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(serde::Serialize, Deserialize, Debug, PartialEq)]
+    /// struct Volume { level: u8, muted: bool }
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register_versioned::<Volume, _>("volume", 1, |old_version, deserializer| {
+    ///     assert_eq!(old_version, 0);
+    ///     // Version 0 stored the level alone.
+    ///     let level = u8::deserialize(deserializer)?;
+    ///     Ok(Volume { level, muted: level == 0 })
+    /// });
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"{"volume":7}"#);
+    /// let map: Map<dyn SerializeAny> = Map::deserialize(&mut deserializer, &registry).unwrap();
+    /// assert_eq!(map.get(), Some(&Volume { level: 7, muted: false }));
+    /// let json = serde_json::to_string(&map.serialize_with(&registry)).unwrap();
+    /// assert_eq!(json, r#"{"volume@1":{"level":7,"muted":false}}"#);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is already registered for another type, or if one of the type’s keys
+    /// has the same tag as another type’s.
+    pub fn register_versioned<T, F>(&mut self, name: &'static str, version: u32, migrate: F)
+    where
+        T: IntoBox<A> + DeserializeOwned,
+        A: Downcast,
+        F: for<'de> Fn(u32, &mut dyn erased_serde::Deserializer<'de>)
+            -> Result<T, erased_serde::Error>,
+        F: Send + Sync + 'static,
+    {
+        let deserialize = |deserializer: &mut dyn erased_serde::Deserializer<'_>| {
+            erased_serde::deserialize::<T>(deserializer).map(T::into_box)
+        };
+        let migrate = move |old_version, deserializer: &mut dyn erased_serde::Deserializer<'_>| {
+            migrate(old_version, deserializer).map(T::into_box)
+        };
+        self.insert(TypeId::of::<T>(), Entry {
+            name,
+            version,
+            deserialize: Some(Arc::new(deserialize)),
+            migrate: Some(Arc::new(migrate)),
+        });
+    }
+
+    /// Returns the stable name registered for the type with the given `TypeId`, if any.
+    #[inline]
+    pub fn name_of(&self, type_id: &TypeId) -> Option<&'static str> {
+        self.entries.get(type_id).map(|entry| entry.name)
+    }
+
+    /// Returns the `TypeId` of the type registered with the stable name `name`, if any.
+    #[inline]
+    pub fn type_id_of(&self, name: &str) -> Option<TypeId> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Returns the version registered for the type with the given `TypeId`, if any; 0 unless
+    /// it was registered with [`register_versioned`](Registry::register_versioned).
+    #[inline]
+    pub fn version_of(&self, type_id: &TypeId) -> Option<u32> {
+        self.entries.get(type_id).map(|entry| entry.version)
+    }
+
+    /// Returns the tag of the type with the given `TypeId`, the [`StableKey`] of its stable
+    /// name (with its version, if after 0), if it’s registered.
+    #[inline]
+    pub fn tag_of(&self, type_id: &TypeId) -> Option<StableKey> {
+        self.entries.get(type_id).map(|entry| versioned_tag(entry.name, entry.version))
+    }
+
+    /// Returns the `TypeId` of the type registered with the tag `tag`, of its current version or
+    /// an older one, if any.
+    #[inline]
+    pub fn type_id_of_tag(&self, tag: StableKey) -> Option<TypeId> {
+        self.by_tag.get(&tag).map(|&(type_id, _)| type_id)
+    }
+
+    /// Returns true if the type with the given `TypeId` is registered with a way to
+    /// deserialize it.
+    #[inline]
+    pub fn can_deserialize(&self, type_id: &TypeId) -> bool {
+        self.entries.get(type_id).is_some_and(|entry| entry.deserialize.is_some())
+    }
+
+    /// Returns the number of types registered.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no types are registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<A: ?Sized> Default for Registry<A> {
+    #[inline]
+    fn default() -> Registry<A> {
+        Registry::new()
+    }
+}
+
+impl<A: ?Sized> Clone for Registry<A> {
+    #[inline]
+    fn clone(&self) -> Registry<A> {
+        Registry {
+            entries: self.entries.clone(),
+            by_name: self.by_name.clone(),
+            by_tag: self.by_tag.clone(),
+        }
+    }
+}
+
+impl<A: ?Sized> fmt::Debug for Registry<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.by_name.keys()).finish()
+    }
+}
+
+/// A map’s values, ready to serialize as a map from their types’ stable names, or their tags;
+/// see [`Map::serialize_with`](crate::Map::serialize_with) and
+/// [`Map::serialize_compact`](crate::Map::serialize_compact).
+///
+/// Entries are serialized in order of name, so that equal maps serialize the same. Serializing
+/// fails if a value’s type has no name in the registry.
+pub struct Serializable<'a, A: ?Sized> {
+    values: Vec<(TypeId, &'a A)>,
+    registry: &'a Registry<A>,
+    compact: bool,
+}
+
+impl<'a, A: ?Sized> Serializable<'a, A> {
+    #[inline]
+    pub(crate) fn new(
+        values: Vec<(TypeId, &'a A)>,
+        registry: &'a Registry<A>,
+        compact: bool,
+    ) -> Serializable<'a, A> {
+        Serializable { values, registry, compact }
+    }
+}
+
+impl<A: ?Sized> fmt::Debug for Serializable<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Serializable")
+            .field("len", &self.values.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + SerializeToAny> Serialize for Serializable<'_, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries = self.values.iter()
+            .map(|&(type_id, value)| match self.registry.entries.get(&type_id) {
+                Some(entry) => Ok((entry, value)),
+                None => Err(S::Error::custom(
+                    format!("no stable name registered for {:?}", type_id),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort_unstable_by_key(|&(entry, _)| entry.name);
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (entry, value) in entries {
+            if self.compact {
+                let tag = versioned_tag(entry.name, entry.version).get().to_le_bytes();
+                map.serialize_entry(&tag, value.as_serialize())?;
+            } else {
+                let name = versioned_name(entry.name, entry.version);
+                map.serialize_entry(&*name, value.as_serialize())?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Deserializes a map from stable names, or tags if `compact`, to values, as serialized by
+/// [`Serializable`], into the values’ boxes, for [`Map::deserialize`](crate::Map::deserialize).
+pub(crate) fn deserialize_values<'de, A, D>(
+    deserializer: D,
+    registry: &Registry<A>,
+    compact: bool,
+) -> Result<Vec<Box<A>>, D::Error>
+where
+    A: ?Sized + Downcast,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(ValuesVisitor { registry, compact })
+}
+
+struct ValuesVisitor<'a, A: ?Sized> {
+    registry: &'a Registry<A>,
+    compact: bool,
+}
+
+impl<'de, A: ?Sized + Downcast> Visitor<'de> for ValuesVisitor<'_, A> {
+    type Value = Vec<Box<A>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map from stable type names to values")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Vec<Box<A>>, M::Error> {
+        let mut values = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        let mut seen = BTreeSet::new();
+        let (registry, compact) = (self.registry, self.compact);
+        while let Some(key) = map.next_key_seed(NameSeed { registry, compact })? {
+            let (type_id, entry, version) = key;
+            if !seen.insert(type_id) {
+                return Err(de::Error::custom(format!("duplicate entry {:?}", entry.name)));
+            }
+            values.push(map.next_value_seed(ValueSeed { entry, version })?);
+        }
+        Ok(values)
+    }
+}
+
+/// Looks a stable name, or a tag if `compact`, up in the registry, with the version it’s for.
+struct NameSeed<'a, A: ?Sized> {
+    registry: &'a Registry<A>,
+    compact: bool,
+}
+
+type Key<'a, A> = (TypeId, &'a Entry<A>, u32);
+
+impl<'de, 'a, A: ?Sized> DeserializeSeed<'de> for NameSeed<'a, A> {
+    type Value = Key<'a, A>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        if !self.compact {
+            return deserializer.deserialize_str(self);
+        }
+        let tag = StableKey::from_u64(u64::from_le_bytes(Deserialize::deserialize(deserializer)?));
+        self.registry.by_tag.get(&tag)
+            .map(|&(type_id, version)| (type_id, &self.registry.entries[&type_id], version))
+            .ok_or_else(|| de::Error::custom(format!("unknown stable type tag {:?}", tag)))
+    }
+}
+
+impl<'de, 'a, A: ?Sized> Visitor<'de> for NameSeed<'a, A> {
+    type Value = Key<'a, A>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a registered stable type name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+        let versioned = name.rsplit_once('@').and_then(|(name, version)| {
+            Some((self.registry.type_id_of(name)?, version.parse().ok()?))
+        });
+        self.registry.type_id_of(name).map(|type_id| (type_id, 0)).or(versioned)
+            .map(|(type_id, version)| (type_id, &self.registry.entries[&type_id], version))
+            .ok_or_else(|| E::custom(format!("unknown stable type name {:?}", name)))
+    }
+}
+
+/// Deserializes a value of version `version` with its registered function, or by migrating it.
+struct ValueSeed<'a, A: ?Sized> {
+    entry: &'a Entry<A>,
+    version: u32,
+}
+
+impl<'de, A: ?Sized> DeserializeSeed<'de> for ValueSeed<'_, A> {
+    type Value = Box<A>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<A>, D::Error> {
+        let (entry, version) = (self.entry, self.version);
+        if version > entry.version {
+            return Err(de::Error::custom(format!(
+                "version {} of {:?} is newer than the registered version {}",
+                version, entry.name, entry.version,
+            )));
+        }
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        if version < entry.version {
+            let migrate = entry.migrate.as_ref().ok_or_else(|| de::Error::custom(format!(
+                "no migration from version {} of {:?} registered", version, entry.name,
+            )))?;
+            return migrate(version, &mut deserializer).map_err(de::Error::custom);
+        }
+        let deserialize = entry.deserialize.as_ref().ok_or_else(|| {
+            de::Error::custom(format!("no way to deserialize {:?} registered", entry.name))
+        })?;
+        deserialize(&mut deserializer).map_err(de::Error::custom)
+    }
+}
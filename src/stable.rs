@@ -0,0 +1,280 @@
+//! Keys for types that stay the same from one build to the next, unlike `TypeId`s, and a map
+//! whose entries are also keyed by them.
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use core::any::TypeId;
+use core::fmt;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use core::hash::BuildHasherDefault;
+#[cfg(all(not(feature = "std"), feature = "hashbrown"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::hash_map::HashMap;
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use crate::TypeIdHasher;
+
+/// A 64-bit key for a type that, unlike its `TypeId`, is the same in every build of the
+/// program, for persisting values or passing them between processes; see `StableMap`.
+///
+/// It’s a hash (FNV-1a) of a name: either one you choose, with
+/// [`from_name`](StableKey::from_name), which is as stable as you keep it, or the type’s
+/// `core::any::type_name`, with [`of`](StableKey::of), which is stable so long as the type
+/// isn’t renamed or moved and the compiler doesn’t change how it writes type names.
+///
+/// ```rust
+/// use anymap::StableKey;
+///
+/// const SCORE: StableKey = StableKey::from_name("score");
+/// assert_eq!(SCORE, StableKey::from_name("score"));
+/// assert_ne!(SCORE, StableKey::from_name("scores"));
+/// assert_eq!(StableKey::of::<u32>(), StableKey::from_name("u32"));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StableKey(u64);
+
+impl StableKey {
+    /// Returns the key for the name `name`.
+    pub const fn from_name(name: &str) -> StableKey {
+        let bytes = name.as_bytes();
+        let mut hash = 0xcb_f2_9c_e4_84_22_23_25_u64;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x00_00_01_00_00_00_01_b3);
+            i += 1;
+        }
+        StableKey(hash)
+    }
+
+    /// Returns the key for the type `T`, from its name as given by `core::any::type_name`.
+    #[inline]
+    pub fn of<T: ?Sized>() -> StableKey {
+        StableKey::from_name(core::any::type_name::<T>())
+    }
+
+    /// Returns the key with the given value, as returned by [`get`](StableKey::get).
+    #[inline]
+    pub const fn from_u64(key: u64) -> StableKey {
+        StableKey(key)
+    }
+
+    /// Returns the key’s value.
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Debug for StableKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StableKey({:#018x})", self.0)
+    }
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub(crate) type KeyTable = HashMap<StableKey, TypeId, BuildHasherDefault<TypeIdHasher>>;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+pub(crate) type KeyOfTable = HashMap<TypeId, StableKey, BuildHasherDefault<TypeIdHasher>>;
+
+/// A map whose entries are also keyed by a [`StableKey`], which, unlike a `TypeId`, is the same
+/// in every build, for persisting the map or passing its values between processes.
+///
+/// `M` is the map it wraps, a `Map` from either of the std and hashbrown implementations, made
+/// into a `StableMap` with `Map::into_stable`. A value inserted with `insert` is keyed by its
+/// type’s name, and one inserted with `insert_named` by a name of your choosing, which survives
+/// the type being renamed. Values can then be found by key, without knowing their type.
+/// Everything that can’t change the map is available through `Deref` to the inner `Map`.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use anymap::StableKey;
+///
+/// let mut data = anymap::AnyMap::new();
+/// # let _ = data.insert(());
+/// let mut data = data.into_stable();
+/// data.insert_named("retries", 3u8);
+/// data.insert(1.5f32);
+/// let key = StableKey::from_name("retries");
+/// assert_eq!(data.key_of(&core::any::TypeId::of::<u8>()), Some(key));
+/// assert!(data.get_by_key(key).is_some());
+/// assert!(data.get_by_key(StableKey::of::<f32>()).is_some());
+/// // Values from before it was a `StableMap` have no key.
+/// assert_eq!(data.iter_keyed().count(), 2);
+/// ```
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+#[derive(Clone, Debug)]
+pub struct StableMap<M> {
+    pub(crate) map: M,
+    pub(crate) types: KeyTable,
+    pub(crate) keys: KeyOfTable,
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+impl<M> StableMap<M> {
+    pub(crate) fn set_key(&mut self, type_id: TypeId, key: StableKey) {
+        match self.types.get(&key) {
+            Some(&other) if other != type_id => {
+                panic!("stable key {:?} is already used by another type", key);
+            },
+            _ => (),
+        }
+        if let Some(old) = self.keys.insert(type_id, key) {
+            let _ = self.types.remove(&old);
+        }
+        let _ = self.types.insert(key, type_id);
+    }
+
+    #[inline]
+    pub(crate) fn unset_key(&mut self, type_id: &TypeId) {
+        if let Some(key) = self.keys.remove(type_id) {
+            let _ = self.types.remove(&key);
+        }
+    }
+
+    /// Returns the key of the value of the type with the given `TypeId`, if it has one.
+    #[inline]
+    pub fn key_of(&self, type_id: &TypeId) -> Option<StableKey> {
+        self.keys.get(type_id).copied()
+    }
+
+    /// Returns the `TypeId` of the value with the key `key`, if there is one.
+    #[inline]
+    pub fn type_id_of(&self, key: StableKey) -> Option<TypeId> {
+        self.types.get(&key).copied()
+    }
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+impl<M> core::ops::Deref for StableMap<M> {
+    type Target = M;
+
+    #[inline]
+    fn deref(&self) -> &M {
+        &self.map
+    }
+}
+
+// The methods for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_stable_map {
+    () => {
+        impl<A: ?Sized + Downcast> crate::stable::StableMap<Map<A>> {
+            /// Create an empty collection.
+            #[inline]
+            pub fn new() -> Self {
+                Map::new().into_stable()
+            }
+        }
+
+        impl<A: ?Sized + Downcast> Default for crate::stable::StableMap<Map<A>> {
+            #[inline]
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> Map<A, S> {
+            /// Converts this into a [`StableMap`](crate::stable::StableMap), with none of the
+            /// values already in it keyed.
+            #[inline]
+            pub fn into_stable(self) -> crate::stable::StableMap<Self> {
+                crate::stable::StableMap {
+                    map: self,
+                    types: Default::default(),
+                    keys: Default::default(),
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> crate::stable::StableMap<Map<A, S>> {
+            /// Sets the value stored in the collection for the type `T`, keyed by the type’s
+            /// name, unless it already had a key. If the collection already had a value of type
+            /// `T`, that value is returned. Otherwise, `None` is returned.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the key is already used by another type.
+            #[inline]
+            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+                if !self.keys.contains_key(&TypeId::of::<T>()) {
+                    self.set_key(TypeId::of::<T>(), crate::StableKey::of::<T>());
+                }
+                self.map.insert(value)
+            }
+
+            /// Sets the value stored in the collection for the type `T`, keyed by `name`. If the
+            /// collection already had a value of type `T`, that value is returned. Otherwise,
+            /// `None` is returned.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the key is already used by another type.
+            #[inline]
+            pub fn insert_named<T: IntoBox<A>>(&mut self, name: &str, value: T) -> Option<T> {
+                self.set_key(TypeId::of::<T>(), crate::StableKey::from_name(name));
+                self.map.insert(value)
+            }
+
+            /// Returns a reference to the value with the key `key`, if there is one.
+            #[inline]
+            pub fn get_by_key(&self, key: crate::StableKey) -> Option<&A> {
+                self.map.raw.get(self.types.get(&key)?).map(|value| &**value)
+            }
+
+            /// Returns a mutable reference to the value with the key `key`, if there is one.
+            #[inline]
+            pub fn get_by_key_mut(&mut self, key: crate::StableKey) -> Option<&mut A> {
+                self.map.raw.get_mut(self.types.get(&key)?).map(|value| &mut **value)
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type
+            /// `T`, if it exists.
+            #[inline]
+            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+                self.map.get_mut()
+            }
+
+            /// Removes the `T` value from the collection, and its key,
+            /// returning it if there was one or `None` if there was not.
+            #[inline]
+            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+                self.unset_key(&TypeId::of::<T>());
+                self.map.remove()
+            }
+
+            /// Removes the value with the key `key` from the collection, returning it if there
+            /// was one or `None` if there was not.
+            #[inline]
+            pub fn remove_by_key(&mut self, key: crate::StableKey) -> Option<Box<A>> {
+                let type_id = self.types.remove(&key)?;
+                let _ = self.keys.remove(&type_id);
+                self.map.remove_raw(&type_id)
+            }
+
+            /// Returns an iterator over the keys and references to the values that have keys,
+            /// in arbitrary order.
+            #[inline]
+            pub fn iter_keyed(&self) -> impl Iterator<Item = (crate::StableKey, &A)> + '_ {
+                self.types.iter().filter_map(move |(&key, type_id)| {
+                    self.map.raw.get(type_id).map(|value| (key, &**value))
+                })
+            }
+
+            /// Removes all items from the collection, and their keys. Keeps the allocated memory
+            /// for reuse.
+            #[inline]
+            pub fn clear(&mut self) {
+                self.types.clear();
+                self.keys.clear();
+                self.map.clear()
+            }
+
+            /// Converts this into the inner `Map`, forgetting the keys.
+            #[inline]
+            pub fn into_inner(self) -> Map<A, S> {
+                self.map
+            }
+        }
+    }
+}
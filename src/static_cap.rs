@@ -0,0 +1,461 @@
+//! A fixed-capacity map that stores its values inline, needing no allocator.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use crate::any::{Downcast, IntoBox};
+
+/// A collection containing zero or one values for any given type, like [`Map`](crate::Map), but
+/// with room for at most `N` values of at most `W` words each, all stored inline.
+///
+/// This needs no heap at all (it’s available without the `alloc` feature), so it suits
+/// microcontrollers and the like. Since nothing is boxed, each value must fit in a slot: its size
+/// may not exceed `W` machine words, nor its alignment that of `usize`. Trying to store a value
+/// that doesn’t fit fails to compile.
+///
+/// Inserting into a full map fails, handing the value back in an `Err`.
+///
+/// ```rust
+/// use anymap::static_cap::StaticCapMap;
+/// use core::any::Any;
+///
+/// let mut data = StaticCapMap::<dyn Any, 2>::new();
+/// assert_eq!(data.insert(42i32), Ok(None));
+/// assert_eq!(data.insert("str"), Ok(None));
+/// assert_eq!(data.insert(1u8), Err(1u8));
+/// assert_eq!(data.insert(43i32), Ok(Some(42)));
+/// assert_eq!(data.get::<i32>(), Some(&43));
+/// ```
+pub struct StaticCapMap<A: ?Sized + Downcast = dyn Any, const N: usize = 8, const W: usize = 4> {
+    slots: [Option<Slot<W>>; N],
+    // A value of some `T: IntoBox<A>` lives in each slot, so this carries A’s auto traits.
+    _marker: PhantomData<A>,
+}
+
+struct Slot<const W: usize> {
+    type_id: TypeId,
+    drop: unsafe fn(*mut u8),
+    data: [MaybeUninit<usize>; W],
+}
+
+unsafe fn drop_value<T>(value: *mut u8) {
+    ptr::drop_in_place(value as *mut T)
+}
+
+impl<const W: usize> Slot<W> {
+    #[inline]
+    fn new<T: 'static>(value: T) -> Slot<W> {
+        const {
+            assert!(
+                mem::size_of::<T>() <= W * mem::size_of::<usize>()
+                    && mem::align_of::<T>() <= mem::align_of::<usize>(),
+                "value is too big for a StaticCapMap slot",
+            )
+        };
+        let mut data = [MaybeUninit::uninit(); W];
+        // SAFETY: the assertion above ensures that there’s room, suitably aligned.
+        unsafe { ptr::write(data.as_mut_ptr() as *mut T, value) };
+        Slot {
+            type_id: TypeId::of::<T>(),
+            drop: drop_value::<T>,
+            data,
+        }
+    }
+
+    // SAFETY for these three: T must be the type the slot was created with.
+
+    #[inline]
+    unsafe fn get_unchecked<T: 'static>(&self) -> &T {
+        &*(self.data.as_ptr() as *const T)
+    }
+
+    #[inline]
+    unsafe fn get_unchecked_mut<T: 'static>(&mut self) -> &mut T {
+        &mut *(self.data.as_mut_ptr() as *mut T)
+    }
+
+    #[inline]
+    unsafe fn into_inner_unchecked<T: 'static>(self) -> T {
+        let value = ptr::read(self.data.as_ptr() as *const T);
+        mem::forget(self);
+        value
+    }
+}
+
+impl<const W: usize> Drop for Slot<W> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { (self.drop)(self.data.as_mut_ptr() as *mut u8) }
+    }
+}
+
+impl<A: ?Sized + Downcast, const N: usize, const W: usize> Default for StaticCapMap<A, N, W> {
+    #[inline]
+    fn default() -> StaticCapMap<A, N, W> {
+        StaticCapMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast, const N: usize, const W: usize> fmt::Debug for StaticCapMap<A, N, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StaticCapMap")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+impl<A: ?Sized + Downcast, const N: usize, const W: usize> StaticCapMap<A, N, W> {
+    /// Create an empty collection.
+    #[inline]
+    pub const fn new() -> StaticCapMap<A, N, W> {
+        StaticCapMap {
+            slots: [const { None }; N],
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements the collection can hold, which is always `N`.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_none())
+    }
+
+    /// Returns true if there’s no room for any more items in the collection.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_some())
+    }
+
+    /// Removes all items from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot = None;
+        }
+    }
+
+    #[inline]
+    fn position<T: 'static>(&self) -> Option<usize> {
+        let type_id = TypeId::of::<T>();
+        self.slots.iter().position(|slot| matches!(slot, Some(slot) if slot.type_id == type_id))
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`,
+    /// if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        let i = self.position::<T>()?;
+        self.slots[i].as_ref().map(|slot| unsafe { slot.get_unchecked::<T>() })
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// if it exists.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+        let i = self.position::<T>()?;
+        self.slots[i].as_mut().map(|slot| unsafe { slot.get_unchecked_mut::<T>() })
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.position::<T>().is_some()
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    ///
+    /// If the collection already had a value of type `T`, that value is returned in `Ok(Some)`;
+    /// if not, `Ok(None)`. If it had no value of type `T` and is full, `value` is returned in
+    /// `Err` and the collection is unchanged.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Result<Option<T>, T> {
+        if let Some(existing) = self.get_mut::<T>() {
+            return Ok(Some(mem::replace(existing, value)));
+        }
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(Slot::new(value));
+                Ok(None)
+            }
+            None => Err(value),
+        }
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// inserting `value` first if there was none, or returning it in `Err` if there was no room.
+    #[inline]
+    pub fn get_or_insert<T: IntoBox<A>>(&mut self, value: T) -> Result<&mut T, T> {
+        self.get_or_insert_with(|| value).map_err(|f| f())
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// inserting the result of `f` first if there was none, or returning `f` uncalled in `Err` if
+    /// there was no room.
+    #[inline]
+    pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&mut self, f: F)
+        -> Result<&mut T, F>
+    {
+        let i = match self.position::<T>() {
+            Some(i) => i,
+            None => match self.slots.iter().position(|slot| slot.is_none()) {
+                Some(i) => {
+                    self.slots[i] = Some(Slot::new(f()));
+                    i
+                }
+                None => return Err(f),
+            },
+        };
+        match &mut self.slots[i] {
+            Some(slot) => Ok(unsafe { slot.get_unchecked_mut::<T>() }),
+            None => unreachable!(),
+        }
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+        let i = self.position::<T>()?;
+        self.slots[i].take().map(|slot| unsafe { slot.into_inner_unchecked::<T>() })
+    }
+
+    /// Gets the entry for the given type in the collection for in-place manipulation
+    #[inline]
+    pub fn entry<T: IntoBox<A>>(&mut self) -> Entry<'_, A, T, W> {
+        match self.position::<T>() {
+            Some(i) => Entry::Occupied(OccupiedEntry {
+                slot: &mut self.slots[i],
+                type_: PhantomData,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                slot: self.slots.iter_mut().find(|slot| slot.is_none()),
+                type_: PhantomData,
+            }),
+        }
+    }
+}
+
+/// A view into a single occupied location in a `StaticCapMap`.
+pub struct OccupiedEntry<'a, A: ?Sized + Downcast, V: 'a, const W: usize = 4> {
+    // Always `Some`.
+    slot: &'a mut Option<Slot<W>>,
+    type_: PhantomData<(V, &'a mut A)>,
+}
+
+/// A view into a single empty location in a `StaticCapMap`.
+pub struct VacantEntry<'a, A: ?Sized + Downcast, V: 'a, const W: usize = 4> {
+    // The empty slot a value would go in, or `None` if the map is full.
+    slot: Option<&'a mut Option<Slot<W>>>,
+    type_: PhantomData<(V, &'a mut A)>,
+}
+
+/// A view into a single location in a `StaticCapMap`, which may be vacant or occupied.
+pub enum Entry<'a, A: ?Sized + Downcast, V: 'a, const W: usize = 4> {
+    /// An occupied Entry
+    Occupied(OccupiedEntry<'a, A, V, W>),
+    /// A vacant Entry
+    Vacant(VacantEntry<'a, A, V, W>),
+}
+
+impl<'a, A: ?Sized + Downcast, V: IntoBox<A>, const W: usize> Entry<'a, A, V, W> {
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry, or `default` in `Err` if there was no room.
+    #[inline]
+    pub fn or_insert(self, default: V) -> Result<&'a mut V, V> {
+        match self {
+            Entry::Occupied(inner) => Ok(inner.into_mut()),
+            Entry::Vacant(inner) => inner.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if
+    /// empty, and returns a mutable reference to the value in the entry, or the default function
+    /// uncalled in `Err` if there was no room.
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> Result<&'a mut V, F> {
+        match self {
+            Entry::Occupied(inner) => Ok(inner.into_mut()),
+            Entry::Vacant(VacantEntry { slot: Some(slot), .. }) => {
+                Ok(unsafe { slot.insert(Slot::new(default())).get_unchecked_mut::<V>() })
+            },
+            Entry::Vacant(VacantEntry { slot: None, .. }) => Err(default),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts
+    /// into the map.
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut inner) => {
+                f(inner.get_mut());
+                Entry::Occupied(inner)
+            },
+            Entry::Vacant(inner) => Entry::Vacant(inner),
+        }
+    }
+}
+
+impl<'a, A: ?Sized + Downcast, V: IntoBox<A>, const W: usize> OccupiedEntry<'a, A, V, W> {
+    /// Gets a reference to the key in the entry, the `TypeId` of `V`
+    #[inline]
+    pub fn key(&self) -> &TypeId {
+        match &*self.slot {
+            Some(slot) => &slot.type_id,
+            None => unreachable!(),
+        }
+    }
+
+    /// Gets a reference to the value in the entry
+    #[inline]
+    pub fn get(&self) -> &V {
+        match &*self.slot {
+            Some(slot) => unsafe { slot.get_unchecked::<V>() },
+            None => unreachable!(),
+        }
+    }
+
+    /// Gets a mutable reference to the value in the entry
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        match self.slot {
+            Some(slot) => unsafe { slot.get_unchecked_mut::<V>() },
+            None => unreachable!(),
+        }
+    }
+
+    /// Converts the OccupiedEntry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the collection itself
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        match self.slot {
+            Some(slot) => unsafe { slot.get_unchecked_mut::<V>() },
+            None => unreachable!(),
+        }
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value
+    #[inline]
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value out of the entry, and returns it
+    #[inline]
+    pub fn remove(self) -> V {
+        match self.slot.take() {
+            Some(slot) => unsafe { slot.into_inner_unchecked::<V>() },
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<'a, A: ?Sized + Downcast, V: IntoBox<A>, const W: usize> VacantEntry<'a, A, V, W> {
+    /// Returns true if there’s no room in the collection to insert a value through this entry.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.slot.is_none()
+    }
+
+    /// Sets the value of the entry with the VacantEntry's key,
+    /// and returns a mutable reference to it, or `value` in `Err` if there was no room.
+    #[inline]
+    pub fn insert(self, value: V) -> Result<&'a mut V, V> {
+        match self.slot {
+            Some(slot) => Ok(unsafe { slot.insert(Slot::new(value)).get_unchecked_mut::<V>() }),
+            None => Err(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)] struct A(i32);
+    #[derive(Debug, PartialEq)] struct B(i32);
+    #[derive(Debug, PartialEq)] struct C(i32);
+
+    #[test]
+    fn test_static_cap_map() {
+        let mut map = StaticCapMap::<dyn Any, 2, 1>::new();
+        assert_eq!(map.capacity(), 2);
+        assert_eq!(map.insert(A(1)), Ok(None));
+        assert_eq!(map.insert(B(2)), Ok(None));
+        assert!(map.is_full());
+        assert_eq!(map.insert(C(3)), Err(C(3)));
+        assert_eq!(map.insert(A(10)), Ok(Some(A(1))));
+        assert_eq!(map.get_or_insert(C(3)), Err(C(3)));
+        map.get_mut::<B>().unwrap().0 = 20;
+        assert_eq!(map.remove::<B>(), Some(B(20)));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get_or_insert_with(|| C(3)).ok(), Some(&mut C(3)));
+        assert_eq!(map.get_or_insert_with(|| C(4)).ok(), Some(&mut C(3)));
+        assert_eq!(map.get::<A>(), Some(&A(10)));
+        assert!(!map.contains::<B>());
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_static_cap_map_entry() {
+        let mut map = StaticCapMap::<dyn Any, 2, 1>::new();
+        assert_eq!(map.entry::<A>().or_insert(A(1)), Ok(&mut A(1)));
+        assert_eq!(map.entry::<A>().or_insert(A(2)), Ok(&mut A(1)));
+        let _ = map.entry::<A>().and_modify(|a| a.0 += 10);
+        assert_eq!(map.entry::<B>().or_insert_with(|| B(2)).ok(), Some(&mut B(2)));
+        match map.entry::<C>() {
+            Entry::Vacant(e) => {
+                assert!(e.is_full());
+                assert_eq!(e.insert(C(3)), Err(C(3)));
+            },
+            Entry::Occupied(_) => unreachable!(),
+        }
+        match map.entry::<A>() {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(A(12)), A(11));
+                assert_eq!(e.remove(), A(12));
+            },
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.entry::<C>().or_insert(C(3)), Ok(&mut C(3)));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_static_cap_map_drops_values() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        struct D;
+        impl Drop for D {
+            fn drop(&mut self) {
+                let _ = DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let mut map = StaticCapMap::<dyn Any, 4>::new();
+        assert!(matches!(map.insert(D), Ok(None)));
+        assert!(matches!(map.insert(D), Ok(Some(_))));
+        assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+        assert!(map.remove::<D>().is_some());
+        assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+        assert!(map.insert(D).is_ok());
+        drop(map);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 3);
+    }
+}
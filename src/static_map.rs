@@ -0,0 +1,269 @@
+//! A map whose set of types is fixed at compile time, and the process-wide map.
+
+use core::mem;
+#[cfg(feature = "std")]
+use core::any::Any;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "std")]
+use crate::any::IntoBox;
+#[cfg(feature = "std")]
+use crate::sync::{MappedReadGuard, MappedWriteGuard, SyncMap};
+
+/// A collection containing exactly one value for each of the types in the tuple `L`, which is
+/// fixed at compile time.
+///
+/// Where a [`Map`](crate::Map) has to hash, box and hand back an `Option`, `StaticMap` knows
+/// statically where each type lives, so [`get`](StaticMap::get) is just a field access. The price
+/// is the second generic parameter on the accessors, an inferred index: write `get::<T, _>()`.
+/// (Asking for a type that isn’t in `L`, or is in it more than once, fails to compile.)
+///
+/// It converts into a `Map` with `From`, and back with `TryFrom`, which fails if the map is
+/// missing any of the types (including if `L` names a type more than once, since the map only
+/// has one value of it).
+///
+/// ```rust
+/// use anymap::static_map::StaticMap;
+///
+/// let mut data = StaticMap::new((42i32, "str", false));
+/// assert_eq!(*data.get::<i32, _>(), 42);
+/// *data.get_mut::<bool, _>() = true;
+/// assert_eq!(data.insert::<&str, _>("string"), "str");
+/// assert_eq!(data.into_inner(), (42, "string", true));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StaticMap<L> {
+    values: L,
+}
+
+impl<L> StaticMap<L> {
+    /// Creates a collection holding the given tuple of values.
+    #[inline]
+    pub const fn new(values: L) -> StaticMap<L> {
+        StaticMap { values }
+    }
+
+    /// Returns the tuple of values this collection holds.
+    #[inline]
+    pub fn into_inner(self) -> L {
+        self.values
+    }
+
+    /// Returns a reference to the value for the type `T`.
+    #[inline]
+    pub fn get<T, I>(&self) -> &T where L: Has<T, I> {
+        self.values.get()
+    }
+
+    /// Returns a mutable reference to the value for the type `T`.
+    #[inline]
+    pub fn get_mut<T, I>(&mut self) -> &mut T where L: Has<T, I> {
+        self.values.get_mut()
+    }
+
+    /// Sets the value for the type `T`, returning the old one.
+    #[inline]
+    pub fn insert<T, I>(&mut self, value: T) -> T where L: Has<T, I> {
+        mem::replace(self.values.get_mut(), value)
+    }
+}
+
+impl<L> From<L> for StaticMap<L> {
+    #[inline]
+    fn from(values: L) -> StaticMap<L> {
+        StaticMap::new(values)
+    }
+}
+
+/// A tuple with a field of type `T`, at the position indicated by the marker type `I`; see
+/// [`StaticMap`].
+///
+/// This is implemented for tuples of up to twelve types. The index is there so that the
+/// implementations for the different positions don’t overlap; it’s always inferred.
+pub trait Has<T, I> {
+    /// Returns a reference to the field of type `T`.
+    fn get(&self) -> &T;
+
+    /// Returns a mutable reference to the field of type `T`.
+    fn get_mut(&mut self) -> &mut T;
+}
+
+macro_rules! index_types {
+    ($($I:ident)+) => {
+        $(
+            #[doc(hidden)]
+            #[allow(missing_debug_implementations)]
+            pub enum $I { }
+        )+
+    }
+}
+
+index_types!(I0 I1 I2 I3 I4 I5 I6 I7 I8 I9 I10 I11);
+
+macro_rules! impl_has {
+    ($($T:ident $I:ident $i:tt),+) => {
+        impl_has!(@each [$($T),+] $($T $I $i),+);
+    };
+    (@each $all:tt $($T:ident $I:ident $i:tt),+) => {
+        $(impl_has!(@one $all $T $I $i);)+
+    };
+    (@one [$($All:ident),+] $T:ident $I:ident $i:tt) => {
+        impl<$($All),+> Has<$T, $I> for ($($All,)+) {
+            #[inline]
+            fn get(&self) -> &$T {
+                &self.$i
+            }
+
+            #[inline]
+            fn get_mut(&mut self) -> &mut $T {
+                &mut self.$i
+            }
+        }
+    };
+}
+
+impl_has!(T1 I0 0);
+impl_has!(T1 I0 0, T2 I1 1);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5, T7 I6 6);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5, T7 I6 6, T8 I7 7);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5, T7 I6 6, T8 I7 7, T9 I8 8);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5, T7 I6 6, T8 I7 7, T9 I8 8,
+          T10 I9 9);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5, T7 I6 6, T8 I7 7, T9 I8 8,
+          T10 I9 9, T11 I10 10);
+impl_has!(T1 I0 0, T2 I1 1, T3 I2 2, T4 I3 3, T5 I4 4, T6 I5 5, T7 I6 6, T8 I7 7, T9 I8 8,
+          T10 I9 9, T11 I10 10, T12 I11 11);
+
+// The conversions to and from a particular `Map`; invoked by `everything!`, with `Map` in scope.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_static_map_try_from {
+    ($($T:ident)+) => {
+        impl<A, S, $($T),+> core::convert::TryFrom<Map<A, S>> for crate::static_map::StaticMap<($($T,)+)>
+        where
+            A: ?Sized + Downcast,
+            S: BuildHasher,
+            $($T: IntoBox<A>,)+
+        {
+            type Error = crate::MissingError;
+
+            #[inline]
+            fn try_from(mut map: Map<A, S>) -> Result<Self, crate::MissingError> {
+                // A type repeated in the tuple finds nothing left the second time, and so is
+                // reported missing.
+                Ok(crate::static_map::StaticMap::new(($(
+                    map.remove::<$T>().ok_or_else(crate::MissingError::new::<$T>)?,
+                )+)))
+            }
+        }
+    }
+}
+
+/// The process-wide [`SyncMap`], with typed access through associated functions.
+///
+/// This is the global type-keyed store that would otherwise be wired up by hand with a `static`
+/// and a lock: it’s a `SyncMap` in a `OnceLock`, created on first use. Values put here stay until
+/// they’re replaced or removed, which can be done from anywhere, so for library code, types
+/// private to the library make good keys.
+///
+/// ```rust
+/// use anymap::static_map::Global;
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Verbosity(u8);
+///
+/// assert_eq!(Global::set(Verbosity(1)), None);
+/// assert_eq!(*Global::get::<Verbosity>().unwrap(), Verbosity(1));
+/// Global::init_with(|| Verbosity(2)).0 += 1;
+/// assert_eq!(Global::get_cloned::<Verbosity>(), Some(Verbosity(2)));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum Global {}
+
+#[cfg(feature = "std")]
+impl Global {
+    /// Returns the map itself, for anything the other functions don’t cover.
+    #[inline]
+    pub fn map() -> &'static SyncMap {
+        static MAP: OnceLock<SyncMap> = OnceLock::new();
+        MAP.get_or_init(SyncMap::new)
+    }
+
+    /// Returns a guard dereferencing to the global value for the type `T`, if it exists. The map
+    /// is locked for reading until the guard is dropped.
+    #[inline]
+    pub fn get<T: IntoBox<dyn Any + Send + Sync>>() -> Option<MappedReadGuard<'static, T>> {
+        Global::map().get::<T>()
+    }
+
+    /// Returns a clone of the global value for the type `T`, if it exists, holding the lock only
+    /// for as long as that takes.
+    #[inline]
+    pub fn get_cloned<T: IntoBox<dyn Any + Send + Sync> + Clone>() -> Option<T> {
+        Global::map().get_cloned::<T>()
+    }
+
+    /// Sets the global value for the type `T`, returning the previous value if there was one.
+    #[inline]
+    pub fn set<T: IntoBox<dyn Any + Send + Sync>>(value: T) -> Option<T> {
+        Global::map().insert(value)
+    }
+
+    /// Returns a guard dereferencing mutably to the global value for the type `T`, setting it to
+    /// the result of `f` first if there was none. The map is locked for writing until the guard
+    /// is dropped (and while `f` runs).
+    #[inline]
+    pub fn init_with<T: IntoBox<dyn Any + Send + Sync>, F: FnOnce() -> T>(f: F)
+        -> MappedWriteGuard<'static, T>
+    {
+        Global::map().get_or_insert_with(f)
+    }
+
+    /// Removes the global value for the type `T`, returning it if there was one.
+    #[inline]
+    pub fn remove<T: IntoBox<dyn Any + Send + Sync>>() -> Option<T> {
+        Global::map().remove::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)] struct A(i32);
+    #[derive(Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_static_map() {
+        let mut map = StaticMap::from((A(1), B(2), 3u8));
+        assert_eq!(map.get::<A, _>(), &A(1));
+        assert_eq!(map.get::<B, _>(), &B(2));
+        assert_eq!(map.get::<u8, _>(), &3);
+        map.get_mut::<B, _>().0 = 20;
+        assert_eq!(map.insert::<A, _>(A(10)), A(1));
+        assert_eq!(map.into_inner(), (A(10), B(20), 3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_global() {
+        // The map is shared by every test, so this sticks to a type of its own.
+        struct Mine(i32);
+
+        assert!(Global::get::<Mine>().is_none());
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| Global::init_with(|| Mine(0)).0 += 1);
+            }
+        });
+        assert_eq!(Global::get::<Mine>().unwrap().0, 4);
+        assert_eq!(Global::set(Mine(10)).map(|mine| mine.0), Some(4));
+        assert!(Global::map().contains::<Mine>());
+        assert_eq!(Global::remove::<Mine>().map(|mine| mine.0), Some(10));
+    }
+}
@@ -0,0 +1,637 @@
+//! Maps for sharing between threads.
+
+use core::any::Any;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use std::sync::{OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "arc-swap")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "arc-swap")]
+use arc_swap::ArcSwap;
+
+use crate::any::{Downcast, IntoBox};
+#[cfg(feature = "arc-swap")]
+use crate::any::CloneAny;
+use crate::{Entry, Map};
+
+/// A [`Map`] behind an `RwLock`, with typed access through `&self`.
+///
+/// Each method takes the lock for just as long as it needs, or, for those returning a guard, for
+/// as long as you hold that guard; use [`read`](SyncMap::read) or [`write`](SyncMap::write) to
+/// do several things under one lock.
+///
+/// Lock poisoning is ignored: a panic while holding the lock can’t leave the map itself in an
+/// inconsistent state, just perhaps one of its values, and that’s the same as it would be without
+/// the lock.
+///
+/// ```rust
+/// use anymap::sync::SyncMap;
+///
+/// let data: SyncMap = SyncMap::new();
+/// assert_eq!(data.insert(42i32), None);
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| *data.get_mut::<i32>().unwrap() += 1);
+/// });
+/// assert_eq!(*data.get::<i32>().unwrap(), 43);
+/// ```
+#[derive(Debug, Default)]
+pub struct SyncMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    lock: RwLock<Map<A>>,
+}
+
+impl<A: ?Sized + Downcast> From<Map<A>> for SyncMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> SyncMap<A> {
+        SyncMap {
+            lock: RwLock::new(map),
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> SyncMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> SyncMap<A> {
+        SyncMap::from(Map::new())
+    }
+
+    /// Returns the map, taking it out of the lock.
+    #[inline]
+    pub fn into_inner(self) -> Map<A> {
+        self.lock.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns a mutable reference to the map; this needs no locking, since the borrow is
+    /// exclusive.
+    #[inline]
+    pub fn get_map_mut(&mut self) -> &mut Map<A> {
+        self.lock.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the map for reading, blocking until that’s possible.
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, Map<A>> {
+        self.lock.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the map for writing, blocking until that’s possible.
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, Map<A>> {
+        self.lock.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Removes all items from the collection.
+    #[inline]
+    pub fn clear(&self) {
+        self.write().clear()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.read().contains::<T>()
+    }
+
+    /// Returns a guard dereferencing to the value stored in the collection for the type `T`, if
+    /// it exists. The map is locked for reading until the guard is dropped.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<MappedReadGuard<'_, T, A>> {
+        let guard = self.read();
+        let value: *const T = guard.get::<T>()?;
+        Some(MappedReadGuard { _guard: guard, value })
+    }
+
+    /// Returns a clone of the value stored in the collection for the type `T`, if it exists,
+    /// holding the lock only for as long as that takes.
+    #[inline]
+    pub fn get_cloned<T: IntoBox<A> + Clone>(&self) -> Option<T> {
+        self.read().get_cloned::<T>()
+    }
+
+    /// Returns a guard dereferencing mutably to the value stored in the collection for the type
+    /// `T`, if it exists. The map is locked for writing until the guard is dropped.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&self) -> Option<MappedWriteGuard<'_, T, A>> {
+        let mut guard = self.write();
+        let value: *mut T = guard.get_mut::<T>()?;
+        Some(MappedWriteGuard { _guard: guard, value })
+    }
+
+    /// Returns a guard dereferencing mutably to the value stored in the collection for the type
+    /// `T`, inserting the result of `f` first if there was none. The map is locked for writing
+    /// until the guard is dropped (and while `f` runs).
+    #[inline]
+    pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&self, f: F)
+        -> MappedWriteGuard<'_, T, A>
+    {
+        let mut guard = self.write();
+        let value: *mut T = guard.get_or_insert_with(f);
+        MappedWriteGuard { _guard: guard, value }
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&self, value: T) -> Option<T> {
+        self.write().insert(value)
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&self) -> Option<T> {
+        self.write().remove::<T>()
+    }
+}
+
+/// A collection containing zero or one values for any given type, each behind its own `RwLock`.
+///
+/// Where a [`SyncMap`] has one lock for the lot, here each value can be locked separately, with
+/// [`read`](ShareMap::read) and [`write`](ShareMap::write), so threads working with different
+/// types never contend. The set of types is changed through `&mut self` (typically while setting
+/// up, before sharing the map around in an `Arc`), which is what lets this need no outer lock.
+///
+/// Lock poisoning is ignored, as for `SyncMap`.
+///
+/// ```rust
+/// use anymap::sync::ShareMap;
+///
+/// let mut data: ShareMap = ShareMap::new();
+/// data.insert(42i32);
+/// data.insert(String::from("str"));
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| *data.write::<i32>().unwrap() += 1);
+///     scope.spawn(|| data.write::<String>().unwrap().push_str("ing"));
+/// });
+/// assert_eq!(*data.read::<i32>().unwrap(), 43);
+/// assert_eq!(*data.read::<String>().unwrap(), "string");
+/// ```
+#[derive(Debug, Default)]
+pub struct ShareMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    map: Map<A>,
+}
+
+impl<A: ?Sized + Downcast> ShareMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> ShareMap<A> {
+        ShareMap {
+            map: Map::new(),
+        }
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes all items from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T>(&self) -> bool where RwLock<T>: IntoBox<A> {
+        self.map.contains::<RwLock<T>>()
+    }
+
+    /// Locks the value stored in the collection for the type `T` for reading, if it exists,
+    /// blocking until that’s possible.
+    #[inline]
+    pub fn read<T>(&self) -> Option<RwLockReadGuard<'_, T>> where RwLock<T>: IntoBox<A> {
+        self.map.get::<RwLock<T>>()
+            .map(|lock| lock.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Locks the value stored in the collection for the type `T` for writing, if it exists,
+    /// blocking until that’s possible.
+    #[inline]
+    pub fn write<T>(&self) -> Option<RwLockWriteGuard<'_, T>> where RwLock<T>: IntoBox<A> {
+        self.map.get::<RwLock<T>>()
+            .map(|lock| lock.write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`, if it
+    /// exists; this needs no locking, since the borrow is exclusive.
+    #[inline]
+    pub fn get_mut<T>(&mut self) -> Option<&mut T> where RwLock<T>: IntoBox<A> {
+        self.map.get_mut::<RwLock<T>>()
+            .map(|lock| lock.get_mut().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T>(&mut self, value: T) -> Option<T> where RwLock<T>: IntoBox<A> {
+        self.map.insert(RwLock::new(value))
+            .map(|lock| lock.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T>(&mut self) -> Option<T> where RwLock<T>: IntoBox<A> {
+        self.map.remove::<RwLock<T>>()
+            .map(|lock| lock.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// A collection containing zero or one values for any given type, each initialised at most once
+/// through `&self`.
+///
+/// [`get_or_init`](OnceAnyMap::get_or_init) is to each type as `OnceLock::get_or_init` is to a
+/// single value: however many threads call it at once, the closure runs at most once per type
+/// (not counting any that panic), and everyone gets back a plain `&T`. That works because values
+/// are never removed or replaced through `&self`, and being boxed, they never move.
+///
+/// The map’s lock is only held to find or add the type’s slot, not while the closure runs, so a
+/// slow initialisation holds up nothing but other callers for the same type.
+///
+/// ```rust
+/// use anymap::sync::OnceAnyMap;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Logger(&'static str);
+///
+/// let registry: OnceAnyMap = OnceAnyMap::new();
+/// std::thread::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| registry.get_or_init(|| Logger("stderr")));
+///     }
+/// });
+/// assert_eq!(registry.get::<Logger>(), Some(&Logger("stderr")));
+/// ```
+#[derive(Debug, Default)]
+pub struct OnceAnyMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    lock: RwLock<Map<A>>,
+}
+
+impl<A: ?Sized + Downcast> OnceAnyMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> OnceAnyMap<A> {
+        OnceAnyMap {
+            lock: RwLock::new(Map::new()),
+        }
+    }
+
+    #[inline]
+    fn slot<T>(&self) -> Option<&OnceLock<T>> where OnceLock<T>: IntoBox<A> {
+        let map = self.lock.read().unwrap_or_else(PoisonError::into_inner);
+        let slot: *const OnceLock<T> = map.get::<OnceLock<T>>()?;
+        // SAFETY: the slot is boxed, so it stays put while the map changes around it, and nothing
+        // removes it while `self` is borrowed.
+        Some(unsafe { &*slot })
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, if it has been
+    /// initialised.
+    #[inline]
+    pub fn get<T>(&self) -> Option<&T> where OnceLock<T>: IntoBox<A> {
+        self.slot::<T>()?.get()
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`, initialising
+    /// it with `f` if it hasn’t been already. Concurrent callers for the same type wait for the
+    /// one whose `f` is running, rather than running their own.
+    pub fn get_or_init<T, F: FnOnce() -> T>(&self, f: F) -> &T where OnceLock<T>: IntoBox<A> {
+        let slot = match self.slot::<T>() {
+            Some(slot) => slot,
+            None => {
+                let mut map = self.lock.write().unwrap_or_else(PoisonError::into_inner);
+                // Another thread may have added the slot meanwhile, and be using it, so it mustn’t
+                // be mutably borrowed, as `get_or_insert_with` would.
+                let slot: *const OnceLock<T> = match map.entry::<OnceLock<T>>() {
+                    Entry::Occupied(e) => e.get(),
+                    Entry::Vacant(e) => e.insert(OnceLock::new()),
+                };
+                // SAFETY: as in `slot`.
+                unsafe { &*slot }
+            }
+        };
+        slot.get_or_init(f)
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`, if it
+    /// has been initialised.
+    #[inline]
+    pub fn get_mut<T>(&mut self) -> Option<&mut T> where OnceLock<T>: IntoBox<A> {
+        self.lock.get_mut().unwrap_or_else(PoisonError::into_inner)
+            .get_mut::<OnceLock<T>>()?
+            .get_mut()
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T>(&mut self) -> Option<T> where OnceLock<T>: IntoBox<A> {
+        self.lock.get_mut().unwrap_or_else(PoisonError::into_inner)
+            .remove::<OnceLock<T>>()?
+            .into_inner()
+    }
+}
+
+/// A collection containing zero or one values for any given type, read through immutable
+/// snapshots and written by copying.
+///
+/// [`load`](SnapshotMap::load) returns the current version of the map as an `Arc<Map<A>>`,
+/// which the caller can hold and read for as long as they like without locking anything or
+/// holding up writers. Writes, through [`update`](SnapshotMap::update) and its shorthands, clone
+/// the current version, change the clone, and swap it in for subsequent `load`s to see; writers
+/// are serialised among themselves, but the copying happens with readers free to carry on.
+///
+/// This suits maps that are written rarely but read constantly, such as configuration or
+/// extensions read on every request. The current version is kept in an [`ArcSwap`], so reads
+/// take no lock at all: they never wait for writers, nor for each other.
+///
+/// This needs the `arc-swap` feature. As with [`SyncMap`], lock poisoning is ignored (writers
+/// take a lock among themselves).
+///
+/// ```rust
+/// use anymap::sync::SnapshotMap;
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Timeout(u32);
+///
+/// let config: SnapshotMap = SnapshotMap::new();
+/// let _ = config.insert(Timeout(30));
+/// let before = config.load();
+/// let _ = config.insert(Timeout(60));
+/// assert_eq!(before.get::<Timeout>(), Some(&Timeout(30)));
+/// assert_eq!(config.load().get::<Timeout>(), Some(&Timeout(60)));
+/// ```
+#[cfg(feature = "arc-swap")]
+#[derive(Debug, Default)]
+pub struct SnapshotMap<A: ?Sized + Downcast = dyn CloneAny + Send + Sync> {
+    current: ArcSwap<Map<A>>,
+    writer: Mutex<()>,
+}
+
+#[cfg(feature = "arc-swap")]
+impl<A: ?Sized + Downcast> From<Map<A>> for SnapshotMap<A> {
+    #[inline]
+    fn from(map: Map<A>) -> SnapshotMap<A> {
+        SnapshotMap {
+            current: ArcSwap::from_pointee(map),
+            writer: Mutex::new(()),
+        }
+    }
+}
+
+#[cfg(feature = "arc-swap")]
+impl<A: ?Sized + Downcast> SnapshotMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> SnapshotMap<A> {
+        SnapshotMap::from(Map::new())
+    }
+
+    /// Returns the current version of the map.
+    #[inline]
+    pub fn load(&self) -> Arc<Map<A>> {
+        self.current.load_full()
+    }
+
+    /// Replaces the map with `map`, returning the version it replaced.
+    #[inline]
+    pub fn store(&self, map: Map<A>) -> Arc<Map<A>> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        self.swap(Arc::new(map))
+    }
+
+    #[inline]
+    fn swap(&self, map: Arc<Map<A>>) -> Arc<Map<A>> {
+        self.current.swap(map)
+    }
+
+    /// Returns the map, as it is now.
+    #[inline]
+    pub fn into_inner(self) -> Arc<Map<A>> {
+        self.current.into_inner()
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.current.load().contains::<T>()
+    }
+
+    /// Returns a clone of the value stored in the collection for the type `T`, if it exists.
+    #[inline]
+    pub fn get_cloned<T: IntoBox<A> + Clone>(&self) -> Option<T> {
+        self.current.load().get_cloned::<T>()
+    }
+}
+
+#[cfg(feature = "arc-swap")]
+impl<A: ?Sized + Downcast> SnapshotMap<A> where Box<A>: Clone {
+    /// Makes a new version of the map by running `f` on a copy of the current one, returning
+    /// what `f` returns. Other writers wait until it’s done; readers don’t.
+    pub fn update<R, F: FnOnce(&mut Map<A>) -> R>(&self, f: F) -> R {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut map = Map::clone(&self.current.load());
+        let result = f(&mut map);
+        drop(self.swap(Arc::new(map)));
+        result
+    }
+
+    /// Sets the value stored in the collection for the type `T`, in a new version.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&self, value: T) -> Option<T> {
+        self.update(|map| map.insert(value))
+    }
+
+    /// Removes the `T` value from the collection, in a new version,
+    /// returning it if there was one or `None` if there was not.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&self) -> Option<T> {
+        self.update(|map| map.remove::<T>())
+    }
+}
+
+/// A reference to a value in a [`SyncMap`] (or a shard of a
+/// [`ConcurrentMap`](crate::concurrent::ConcurrentMap)), holding its read lock.
+///
+/// This is created by the `get` method of each.
+pub struct MappedReadGuard<'a, T, A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    // The value is boxed in the map, which can’t change while this guard exists.
+    pub(crate) _guard: RwLockReadGuard<'a, Map<A>>,
+    pub(crate) value: *const T,
+}
+
+impl<T, A: ?Sized + Downcast> Deref for MappedReadGuard<'_, T, A> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<T: fmt::Debug, A: ?Sized + Downcast> fmt::Debug for MappedReadGuard<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A mutable reference to a value in a [`SyncMap`] (or a shard of a
+/// [`ConcurrentMap`](crate::concurrent::ConcurrentMap)), holding its write lock.
+///
+/// This is created by the `get_mut` and `get_or_insert_with` methods of each.
+pub struct MappedWriteGuard<'a, T, A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    // The guard is never touched again, so this remains the only reference into the map.
+    pub(crate) _guard: RwLockWriteGuard<'a, Map<A>>,
+    pub(crate) value: *mut T,
+}
+
+impl<T, A: ?Sized + Downcast> Deref for MappedWriteGuard<'_, T, A> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<T, A: ?Sized + Downcast> DerefMut for MappedWriteGuard<'_, T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<T: fmt::Debug, A: ?Sized + Downcast> fmt::Debug for MappedWriteGuard<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_sync_map() {
+        let map = SyncMap::<dyn Any + Send + Sync>::new();
+        assert_eq!(map.insert(A(1)), None);
+        assert!(map.get::<B>().is_none());
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| map.get_mut::<A>().unwrap().0 += 1);
+                let _ = scope.spawn(|| map.get_or_insert_with(|| B(0)).0 += 1);
+            }
+        });
+        assert_eq!(*map.get::<A>().unwrap(), A(5));
+        assert_eq!(map.get_cloned::<B>(), Some(B(4)));
+        {
+            let a = map.get::<A>().unwrap();
+            let b = map.get::<B>().unwrap();
+            assert_eq!((a.0, b.0), (5, 4));
+        }
+        assert_eq!(map.remove::<A>(), Some(A(5)));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.into_inner().get::<B>(), Some(&B(4)));
+    }
+
+    #[test]
+    fn test_once_any_map() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let map = OnceAnyMap::<dyn Any + Send + Sync>::new();
+        let calls = AtomicUsize::new(0);
+        let (map, calls) = (&map, &calls);
+        let a = std::thread::scope(|scope| {
+            let threads = (0..4).map(|i| scope.spawn(move || {
+                let _ = map.get_or_init(|| B(i));
+                map.get_or_init(|| {
+                    let _ = calls.fetch_add(1, Ordering::Relaxed);
+                    A(i)
+                })
+            })).collect::<Vec<_>>();
+            threads.into_iter().map(|thread| thread.join().unwrap() as *const A).collect::<Vec<_>>()
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert!(a.iter().all(|&a| core::ptr::eq(a, map.get::<A>().unwrap())));
+        assert!(map.get::<u8>().is_none());
+        let mut map = OnceAnyMap::<dyn Any + Send + Sync>::new();
+        let _ = map.get_or_init(|| B(0));
+        map.get_mut::<B>().unwrap().0 = 10;
+        assert_eq!(map.remove::<B>(), Some(B(10)));
+        assert_eq!(map.get::<B>(), None);
+    }
+
+    #[cfg(feature = "arc-swap")]
+    #[test]
+    fn test_snapshot_map() {
+        let map = SnapshotMap::<dyn CloneAny + Send + Sync>::new();
+        assert_eq!(map.insert(A(1)), None);
+        let snapshot = map.load();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| map.update(|map| map.get_mut::<A>().unwrap().0 += 1));
+                let _ = scope.spawn(|| map.insert(B(2)));
+            }
+        });
+        assert_eq!(snapshot.get::<A>(), Some(&A(1)));
+        assert!(!snapshot.contains::<B>());
+        assert_eq!(map.get_cloned::<A>(), Some(A(5)));
+        assert_eq!(map.remove::<B>(), Some(B(2)));
+        assert!(!map.contains::<B>());
+        let old = map.store(Map::new());
+        assert_eq!(old.len(), 1);
+        assert!(map.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_share_map() {
+        let mut map = ShareMap::<dyn Any + Send + Sync>::new();
+        assert_eq!(map.insert(A(1)), None);
+        assert_eq!(map.insert(B(2)), None);
+        {
+            // Locks on different types are independent.
+            let mut a = map.write::<A>().unwrap();
+            let b = map.read::<B>().unwrap();
+            a.0 += b.0;
+        }
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|| map.write::<B>().unwrap().0 += 1);
+            }
+        });
+        assert_eq!(*map.read::<A>().unwrap(), A(3));
+        assert!(map.read::<u8>().is_none());
+        map.get_mut::<B>().unwrap().0 += 1;
+        assert_eq!(map.remove::<B>(), Some(B(7)));
+        assert!(!map.contains::<B>());
+        assert_eq!(map.len(), 1);
+    }
+}
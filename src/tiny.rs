@@ -0,0 +1,452 @@
+//! A map for a handful of values, stored in a `Vec` rather than a hash map.
+
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::any::{Downcast, IntoBox};
+
+/// A collection containing zero or one values for any given type, like [`Map`](crate::Map), but
+/// kept in a `Vec` and searched linearly.
+///
+/// This is for maps you know will stay tiny, such as per-entity component blobs: there’s no
+/// hasher, no hash table overhead, and lookups are a scan over a few contiguous `TypeId`s. Beyond
+/// a dozen or so types, you’ll want a `Map` instead; they convert into one another with `From`.
+///
+/// It needs only the `alloc` feature, so it’s available even with neither `std` nor `hashbrown`.
+///
+/// ```rust
+/// let mut data = anymap::tiny::TinyMap::<dyn core::any::Any>::new();
+/// assert_eq!(data.insert(42i32), None);
+/// assert_eq!(data.get::<i32>(), Some(&42));
+/// *data.get_mut::<i32>().unwrap() += 1;
+/// assert_eq!(data.remove::<i32>(), Some(43));
+/// assert!(data.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct TinyMap<A: ?Sized + Downcast = dyn Any> {
+    raw: Vec<(TypeId, Box<A>)>,
+}
+
+// #[derive(Clone)] would want A to implement Clone, but in reality only Box<A> can.
+impl<A: ?Sized + Downcast> Clone for TinyMap<A> where Box<A>: Clone {
+    #[inline]
+    fn clone(&self) -> TinyMap<A> {
+        TinyMap {
+            raw: self.raw.clone(),
+        }
+    }
+}
+
+impl<A: ?Sized + Downcast> Default for TinyMap<A> {
+    #[inline]
+    fn default() -> TinyMap<A> {
+        TinyMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> TinyMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> TinyMap<A> {
+        TinyMap {
+            raw: Vec::new(),
+        }
+    }
+
+    /// Creates an empty collection with the given initial capacity.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> TinyMap<A> {
+        TinyMap {
+            raw: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of elements the collection can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.raw.capacity()
+    }
+
+    /// Reserves capacity for at least `additional` more elements to be inserted
+    /// in the collection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new allocation size overflows `usize`.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.raw.reserve(additional)
+    }
+
+    /// Shrinks the capacity of the collection as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.raw.shrink_to_fit()
+    }
+
+    /// Returns the number of items in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Returns true if there are no items in the collection.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Removes all items from the collection. Keeps the allocated memory for reuse.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.raw.clear()
+    }
+
+    #[inline]
+    fn position<T: 'static>(&self) -> Option<usize> {
+        let type_id = TypeId::of::<T>();
+        self.raw.iter().position(|&(key, _)| key == type_id)
+    }
+
+    /// Returns a reference to the value stored in the collection for the type `T`,
+    /// if it exists.
+    #[inline]
+    pub fn get<T: IntoBox<A>>(&self) -> Option<&T> {
+        self.position::<T>()
+            .map(|i| unsafe { self.raw[i].1.downcast_ref_unchecked::<T>() })
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// if it exists.
+    #[inline]
+    pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+        match self.position::<T>() {
+            Some(i) => Some(unsafe { self.raw[i].1.downcast_mut_unchecked::<T>() }),
+            None => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value stored in the collection for the type `T`,
+    /// inserting the result of `f` first if there was none.
+    #[inline]
+    pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&mut self, f: F) -> &mut T {
+        let i = match self.position::<T>() {
+            Some(i) => i,
+            None => {
+                self.raw.push((TypeId::of::<T>(), f().into_box()));
+                self.raw.len() - 1
+            }
+        };
+        unsafe { self.raw[i].1.downcast_mut_unchecked::<T>() }
+    }
+
+    /// Sets the value stored in the collection for the type `T`.
+    /// If the collection already had a value of type `T`, that value is returned.
+    /// Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+        let value = value.into_box();
+        match self.position::<T>() {
+            Some(i) => {
+                let old = core::mem::replace(&mut self.raw[i].1, value);
+                Some(unsafe { *old.downcast_unchecked::<T>() })
+            }
+            None => {
+                self.raw.push((TypeId::of::<T>(), value));
+                None
+            }
+        }
+    }
+
+    /// Removes the `T` value from the collection,
+    /// returning it if there was one or `None` if there was not.
+    ///
+    /// This doesn’t preserve the order of the remaining items, which is not observable through
+    /// anything but [`as_raw`](TinyMap::as_raw) anyway.
+    #[inline]
+    pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+        self.position::<T>()
+            .map(|i| unsafe { *self.raw.swap_remove(i).1.downcast_unchecked::<T>() })
+    }
+
+    /// Returns true if the collection contains a value of type `T`.
+    #[inline]
+    pub fn contains<T: IntoBox<A>>(&self) -> bool {
+        self.position::<T>().is_some()
+    }
+
+    /// Gets the entry for the given type in the collection for in-place manipulation
+    #[inline]
+    pub fn entry<T: IntoBox<A>>(&mut self) -> Entry<'_, A, T> {
+        match self.position::<T>() {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                raw: &mut self.raw,
+                index,
+                type_: PhantomData,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                raw: &mut self.raw,
+                type_: PhantomData,
+            }),
+        }
+    }
+
+    /// Get access to the raw `Vec` that backs this.
+    #[inline]
+    pub fn as_raw(&self) -> &Vec<(TypeId, Box<A>)> {
+        &self.raw
+    }
+
+    /// Get mutable access to the raw `Vec` that backs this.
+    ///
+    /// # Safety
+    ///
+    /// If you insert any values to the raw `Vec`, the key (a `TypeId`) must match the value’s
+    /// type, or *undefined behaviour* will occur when you access those values; and each `TypeId`
+    /// must appear only once, or the later ones will be unreachable.
+    ///
+    /// (*Removing* entries is perfectly safe.)
+    #[inline]
+    pub unsafe fn as_raw_mut(&mut self) -> &mut Vec<(TypeId, Box<A>)> {
+        &mut self.raw
+    }
+
+    /// Convert this into the raw `Vec` that backs this.
+    #[inline]
+    pub fn into_raw(self) -> Vec<(TypeId, Box<A>)> {
+        self.raw
+    }
+
+    /// Construct a map from a raw `Vec`.
+    ///
+    /// # Safety
+    ///
+    /// For all entries in the raw `Vec`, the key (a `TypeId`) must match the value’s type,
+    /// or *undefined behaviour* will occur when you access that entry. Each `TypeId` should
+    /// appear only once.
+    #[inline]
+    pub unsafe fn from_raw(raw: Vec<(TypeId, Box<A>)>) -> TinyMap<A> {
+        TinyMap { raw }
+    }
+}
+
+impl<A: ?Sized + Downcast> Extend<Box<A>> for TinyMap<A> {
+    #[inline]
+    fn extend<T: IntoIterator<Item = Box<A>>>(&mut self, iter: T) {
+        for item in iter {
+            let type_id = Downcast::type_id(&*item);
+            match self.raw.iter_mut().find(|&&mut (key, _)| key == type_id) {
+                Some(slot) => slot.1 = item,
+                None => self.raw.push((type_id, item)),
+            }
+        }
+    }
+}
+
+/// A view into a single occupied location in a `TinyMap`.
+pub struct OccupiedEntry<'a, A: ?Sized + Downcast, V: 'a> {
+    raw: &'a mut Vec<(TypeId, Box<A>)>,
+    index: usize,
+    type_: PhantomData<V>,
+}
+
+/// A view into a single empty location in a `TinyMap`.
+pub struct VacantEntry<'a, A: ?Sized + Downcast, V: 'a> {
+    raw: &'a mut Vec<(TypeId, Box<A>)>,
+    type_: PhantomData<V>,
+}
+
+/// A view into a single location in a `TinyMap`, which may be vacant or occupied.
+pub enum Entry<'a, A: ?Sized + Downcast, V: 'a> {
+    /// An occupied Entry
+    Occupied(OccupiedEntry<'a, A, V>),
+    /// A vacant Entry
+    Vacant(VacantEntry<'a, A, V>),
+}
+
+impl<'a, A: ?Sized + Downcast, V: IntoBox<A>> Entry<'a, A, V> {
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
+    #[inline]
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(inner) => inner.into_mut(),
+            Entry::Vacant(inner) => inner.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if
+    /// empty, and returns a mutable reference to the value in the entry.
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(inner) => inner.into_mut(),
+            Entry::Vacant(inner) => inner.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty,
+    /// and returns a mutable reference to the value in the entry.
+    #[inline]
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        match self {
+            Entry::Occupied(inner) => inner.into_mut(),
+            Entry::Vacant(inner) => inner.insert(Default::default()),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts
+    /// into the map.
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut inner) => {
+                f(inner.get_mut());
+                Entry::Occupied(inner)
+            },
+            Entry::Vacant(inner) => Entry::Vacant(inner),
+        }
+    }
+
+    /// Sets the value of the entry, and returns an `OccupiedEntry`.
+    #[inline]
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, A, V> {
+        match self {
+            Entry::Occupied(mut inner) => {
+                let _ = inner.insert(value);
+                inner
+            },
+            Entry::Vacant(inner) => inner.insert_entry(value),
+        }
+    }
+}
+
+impl<'a, A: ?Sized + Downcast, V: IntoBox<A>> OccupiedEntry<'a, A, V> {
+    /// Gets a reference to the key in the entry, the `TypeId` of `V`
+    #[inline]
+    pub fn key(&self) -> &TypeId {
+        &self.raw[self.index].0
+    }
+
+    /// Gets a reference to the value in the entry
+    #[inline]
+    pub fn get(&self) -> &V {
+        unsafe { self.raw[self.index].1.downcast_ref_unchecked() }
+    }
+
+    /// Gets a mutable reference to the value in the entry
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { self.raw[self.index].1.downcast_mut_unchecked() }
+    }
+
+    /// Converts the OccupiedEntry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the collection itself
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { self.raw[self.index].1.downcast_mut_unchecked() }
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value
+    #[inline]
+    pub fn insert(&mut self, value: V) -> V {
+        let old = core::mem::replace(&mut self.raw[self.index].1, value.into_box());
+        unsafe { *old.downcast_unchecked() }
+    }
+
+    /// Takes the value out of the entry, and returns it
+    #[inline]
+    pub fn remove(self) -> V {
+        unsafe { *self.raw.swap_remove(self.index).1.downcast_unchecked() }
+    }
+}
+
+impl<'a, A: ?Sized + Downcast, V: IntoBox<A>> VacantEntry<'a, A, V> {
+    /// Sets the value of the entry with the VacantEntry's key,
+    /// and returns a mutable reference to it
+    #[inline]
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    /// Sets the value of the entry with the VacantEntry's key,
+    /// and returns an `OccupiedEntry`
+    #[inline]
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, A, V> {
+        self.raw.push((TypeId::of::<V>(), value.into_box()));
+        OccupiedEntry {
+            index: self.raw.len() - 1,
+            raw: self.raw,
+            type_: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CloneAny;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+    #[derive(Clone, Debug, PartialEq)] struct C(i32);
+
+    #[test]
+    fn test_tiny_map() {
+        let mut map = TinyMap::<dyn Any>::new();
+        assert_eq!(map.insert(A(1)), None);
+        assert_eq!(map.insert(B(2)), None);
+        assert_eq!(map.insert(C(3)), None);
+        assert_eq!(map.insert(B(20)), Some(B(2)));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get::<B>(), Some(&B(20)));
+        map.get_mut::<C>().unwrap().0 = 30;
+        assert_eq!(map.remove::<A>(), Some(A(1)));
+        assert_eq!(map.remove::<A>(), None);
+        assert!(!map.contains::<A>());
+        assert_eq!(map.get::<B>(), Some(&B(20)));
+        assert_eq!(map.get::<C>(), Some(&C(30)));
+        assert_eq!(map.get_or_insert_with(|| A(4)), &A(4));
+        assert_eq!(map.get_or_insert_with(|| A(5)), &A(4));
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn test_tiny_map_entry() {
+        let mut map = TinyMap::<dyn Any>::new();
+        assert_eq!(map.entry::<A>().or_insert(A(1)), &A(1));
+        assert_eq!(map.entry::<A>().or_insert(A(2)), &A(1));
+        let _ = map.entry::<A>().and_modify(|a| a.0 += 10).or_insert_with(|| unreachable!());
+        assert_eq!(map.get::<A>(), Some(&A(11)));
+        match map.entry::<B>() {
+            Entry::Vacant(e) => { let _ = e.insert(B(2)); },
+            Entry::Occupied(_) => unreachable!(),
+        }
+        let mut view = map.entry::<C>().insert_entry(C(3));
+        assert_eq!(view.insert(C(30)), C(3));
+        assert_eq!(view.get(), &C(30));
+        match map.entry::<A>() {
+            Entry::Occupied(e) => assert_eq!(e.remove(), A(11)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get::<B>(), Some(&B(2)));
+        assert_eq!(map.get::<C>(), Some(&C(30)));
+    }
+
+    #[test]
+    fn test_tiny_map_clone_and_extend() {
+        let mut map = TinyMap::<dyn CloneAny>::new();
+        let _ = map.insert(A(1));
+        let mut other = map.clone();
+        other.extend(vec![Box::new(A(2)) as Box<dyn CloneAny>, Box::new(B(3))]);
+        assert_eq!(map.get::<A>(), Some(&A(1)));
+        assert_eq!(other.get::<A>(), Some(&A(2)));
+        assert_eq!(other.get::<B>(), Some(&B(3)));
+        assert_eq!(other.len(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+//! A map that records when each value was last changed.
+
+use core::any::TypeId;
+use core::hash::BuildHasherDefault;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::hash_map::HashMap;
+
+use crate::TypeIdHasher;
+
+pub(crate) type TickTable = HashMap<TypeId, u64, BuildHasherDefault<TypeIdHasher>>;
+
+/// A map that records when each value was last changed, for reacting only to what’s changed,
+/// as an ECS does with its resources.
+///
+/// `M` is the map it wraps, a `Map` from either of the std and hashbrown implementations, made
+/// into a `TrackedMap` with `Map::into_tracked`. The map keeps a counter, its
+/// [`tick`](TrackedMap::tick), which goes up by one with each change, and each value remembers
+/// the tick of its last change. Changes are insertions and mutable accesses, whether or not
+/// anything is actually written through them; so to tell what’s changed since some moment, note
+/// the tick then and ask `changed_since` or `iter_changed` later. Everything that can’t change
+/// the map is available through `Deref` to the inner `Map`.
+///
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// let mut data = anymap::AnyMap::new().into_tracked();
+/// data.insert(1i32);
+/// let seen = data.tick();
+/// assert!(!data.changed_since::<i32>(seen));
+/// *data.get_mut::<i32>().unwrap() += 1;
+/// assert!(data.changed_since::<i32>(seen));
+/// assert!(!data.changed_since::<()>(seen));
+/// assert_eq!(data.iter_changed(seen).count(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TrackedMap<M> {
+    pub(crate) map: M,
+    pub(crate) ticks: TickTable,
+    pub(crate) tick: u64,
+}
+
+impl<M> TrackedMap<M> {
+    #[inline]
+    pub(crate) fn mark<T: 'static>(&mut self) {
+        self.tick += 1;
+        let _ = self.ticks.insert(TypeId::of::<T>(), self.tick);
+    }
+
+    /// Returns the tick of the latest change, or zero if there have been none.
+    #[inline]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+impl<M> core::ops::Deref for TrackedMap<M> {
+    type Target = M;
+
+    #[inline]
+    fn deref(&self) -> &M {
+        &self.map
+    }
+}
+
+/// An iterator over references to the values in a map that have changed since a tick.
+///
+/// This is created by `TrackedMap::iter_changed`; `I` is the iterator over the map’s entries.
+pub struct IterChanged<'a, I> {
+    pub(crate) iter: I,
+    pub(crate) ticks: &'a TickTable,
+    pub(crate) since: u64,
+}
+
+impl<'a, A, I> Iterator for IterChanged<'a, I>
+where
+    A: ?Sized + 'a,
+    I: Iterator<Item = (&'a TypeId, &'a Box<A>)>,
+{
+    type Item = &'a A;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a A> {
+        let (ticks, since) = (self.ticks, self.since);
+        self.iter
+            .find(|(type_id, _)| ticks.get(type_id).is_some_and(|&tick| tick > since))
+            .map(|(_, value)| &**value)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+// The methods for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+macro_rules! impl_tracked_map {
+    () => {
+        impl<A: ?Sized + Downcast> crate::tracked::TrackedMap<Map<A>> {
+            /// Create an empty collection.
+            #[inline]
+            pub fn new() -> Self {
+                Map::new().into_tracked()
+            }
+        }
+
+        impl<A: ?Sized + Downcast> Default for crate::tracked::TrackedMap<Map<A>> {
+            #[inline]
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> Map<A, S> {
+            /// Converts this into a [`TrackedMap`](crate::tracked::TrackedMap), with all the
+            /// values already in it counted as changed at tick 1.
+            #[inline]
+            pub fn into_tracked(self) -> crate::tracked::TrackedMap<Self> {
+                let tick = if self.is_empty() { 0 } else { 1 };
+                crate::tracked::TrackedMap {
+                    ticks: self.raw.keys().map(|&type_id| (type_id, tick)).collect(),
+                    map: self,
+                    tick,
+                }
+            }
+        }
+
+        impl<A: ?Sized + Downcast, S: BuildHasher> crate::tracked::TrackedMap<Map<A, S>> {
+            /// Returns the tick at which the value for the type `T` was last changed, if there is
+            /// such a value.
+            #[inline]
+            pub fn changed_tick<T: IntoBox<A>>(&self) -> Option<u64> {
+                self.ticks.get(&TypeId::of::<T>()).copied()
+            }
+
+            /// Returns true if the value for the type `T` has changed since `tick`, that is, if
+            /// there is such a value and it was last changed at a later tick.
+            #[inline]
+            pub fn changed_since<T: IntoBox<A>>(&self, tick: u64) -> bool {
+                self.changed_tick::<T>().is_some_and(|changed| changed > tick)
+            }
+
+            /// Returns an iterator over references to the values that have changed since `tick`,
+            /// as `&A`, in arbitrary order.
+            #[inline]
+            pub fn iter_changed(
+                &self,
+                tick: u64,
+            ) -> crate::tracked::IterChanged<'_, hash_map::Iter<'_, TypeId, Box<A>>> {
+                crate::tracked::IterChanged {
+                    iter: self.map.raw.iter(),
+                    ticks: &self.ticks,
+                    since: tick,
+                }
+            }
+
+            /// Sets the value stored in the collection for the type `T`, counting it as changed.
+            /// If the collection already had a value of type `T`, that value is returned.
+            /// Otherwise, `None` is returned.
+            #[inline]
+            pub fn insert<T: IntoBox<A>>(&mut self, value: T) -> Option<T> {
+                self.mark::<T>();
+                self.map.insert(value)
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// if it exists, counting it as changed.
+            #[inline]
+            pub fn get_mut<T: IntoBox<A>>(&mut self) -> Option<&mut T> {
+                if self.map.contains::<T>() {
+                    self.mark::<T>();
+                }
+                self.map.get_mut()
+            }
+
+            /// Returns a mutable reference to the value stored in the collection for the type `T`,
+            /// inserting the result of `f` first if there was none, and counting it as changed.
+            #[inline]
+            pub fn get_or_insert_with<T: IntoBox<A>, F: FnOnce() -> T>(&mut self, f: F) -> &mut T {
+                self.mark::<T>();
+                self.map.get_or_insert_with(f)
+            }
+
+            /// Removes the `T` value from the collection,
+            /// returning it if there was one or `None` if there was not.
+            #[inline]
+            pub fn remove<T: IntoBox<A>>(&mut self) -> Option<T> {
+                let _ = self.ticks.remove(&TypeId::of::<T>());
+                self.map.remove()
+            }
+
+            /// Removes all items from the collection. Keeps the allocated memory for reuse, and
+            /// the tick.
+            #[inline]
+            pub fn clear(&mut self) {
+                self.ticks.clear();
+                self.map.clear()
+            }
+
+            /// Converts this into the inner `Map`, forgetting the ticks.
+            #[inline]
+            pub fn into_inner(self) -> Map<A, S> {
+                self.map
+            }
+        }
+    }
+}
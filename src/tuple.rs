@@ -0,0 +1,188 @@
+//! Traits for working with several types at once, implemented for tuples.
+
+/// A tuple of types that can be fetched from a map together; see `Map::get_all`.
+///
+/// This is implemented for tuples of up to twelve types, for each variety of map. `M` is the map
+/// type, e.g. `AnyMap`.
+pub trait Fetch<'a, M: ?Sized> {
+    /// The tuple of references produced, e.g. `(Option<&'a A>, Option<&'a B>)` for `(A, B)`.
+    type Refs;
+
+    /// Fetches a reference to each of the types from the map.
+    fn fetch(map: &'a M) -> Self::Refs;
+}
+
+/// A tuple of distinct types that can be fetched mutably from a map together; see
+/// `Map::get_many_mut`.
+///
+/// This is implemented for tuples of up to twelve types, for each variety of map. `M` is the map
+/// type, e.g. `AnyMap`.
+pub trait FetchMut<'a, M: ?Sized> {
+    /// The tuple of mutable references produced, e.g. `(&'a mut A, &'a mut B)` for `(A, B)`.
+    type Muts;
+
+    /// Fetches a mutable reference to each of the types from the map, if they all exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any type appears more than once in the tuple.
+    fn fetch_mut(map: &'a mut M) -> Option<Self::Muts>;
+}
+
+/// A group of values that can be inserted into or removed from a map together, each under its own
+/// type; see `Map::{insert_bundle, remove_bundle, contains_all}`.
+///
+/// This is implemented for tuples of up to twelve types, for each variety of map. `M` is the map
+/// type, e.g. `AnyMap`.
+pub trait Bundle<M: ?Sized>: Sized {
+    /// The previous values displaced by insertion, e.g. `(Option<A>, Option<B>)` for `(A, B)`.
+    type Displaced;
+
+    /// Inserts each of the values into the map, returning any previous values.
+    fn insert_into(self, map: &mut M) -> Self::Displaced;
+
+    /// Removes each of the types from the map, returning any values there were.
+    fn remove_from(map: &mut M) -> Self::Displaced;
+
+    /// Returns true if the map contains a value of every one of the types.
+    fn contained_in(map: &M) -> bool;
+}
+
+/// A tuple of types whose values can be cloned out of a map together into a new map; see
+/// `Map::clone_subset`.
+///
+/// This is implemented for tuples of up to twelve types, for each variety of map whose boxes can
+/// be cloned. `M` is the map type, e.g. `Map<dyn CloneAny>`.
+pub trait CloneSubset<M: ?Sized> {
+    /// Clones the value of each of the types that the map has into a new map.
+    fn clone_subset(map: &M) -> M;
+}
+
+// Invokes the given macro once for each supported tuple arity.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! for_each_tuple {
+    ($m:ident) => {
+        $m!(T1);
+        $m!(T1 T2);
+        $m!(T1 T2 T3);
+        $m!(T1 T2 T3 T4);
+        $m!(T1 T2 T3 T4 T5);
+        $m!(T1 T2 T3 T4 T5 T6);
+        $m!(T1 T2 T3 T4 T5 T6 T7);
+        $m!(T1 T2 T3 T4 T5 T6 T7 T8);
+        $m!(T1 T2 T3 T4 T5 T6 T7 T8 T9);
+        $m!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+        $m!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+        $m!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12);
+    }
+}
+
+// The implementations for a particular `Map`; invoked by `everything!`, with `Map` in scope.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_fetch {
+    ($($T:ident)+) => {
+        impl<'a, A, S, $($T),+> crate::Fetch<'a, Map<A, S>> for ($($T,)+)
+        where
+            A: ?Sized + Downcast,
+            S: BuildHasher,
+            $($T: IntoBox<A>,)+
+        {
+            type Refs = ($(Option<&'a $T>,)+);
+
+            #[inline]
+            fn fetch(map: &'a Map<A, S>) -> Self::Refs {
+                ($(map.get::<$T>(),)+)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_fetch_mut {
+    ($($T:ident)+) => {
+        impl<'a, A, S, $($T),+> crate::FetchMut<'a, Map<A, S>> for ($($T,)+)
+        where
+            A: ?Sized + Downcast,
+            S: BuildHasher,
+            $($T: IntoBox<A>,)+
+        {
+            type Muts = ($(&'a mut $T,)+);
+
+            #[inline]
+            #[allow(non_snake_case)]
+            fn fetch_mut(map: &'a mut Map<A, S>) -> Option<Self::Muts> {
+                let type_ids = [$(core::any::TypeId::of::<$T>()),+];
+                for (i, type_id) in type_ids.iter().enumerate() {
+                    assert!(
+                        !type_ids[..i].contains(type_id),
+                        "get_many_mut called with duplicate types",
+                    );
+                }
+                // Each lookup only reborrows the table, not any of the other values, so these
+                // pointers all remain valid; and the types are distinct, so they’re disjoint.
+                $(let $T: *mut A = &mut **map.raw.get_mut(&core::any::TypeId::of::<$T>())?;)+
+                unsafe { Some(($((*$T).downcast_mut_unchecked::<$T>(),)+)) }
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_bundle {
+    ($($T:ident)+) => {
+        impl<A, S, $($T),+> crate::Bundle<Map<A, S>> for ($($T,)+)
+        where
+            A: ?Sized + Downcast,
+            S: BuildHasher,
+            $($T: IntoBox<A>,)+
+        {
+            type Displaced = ($(Option<$T>,)+);
+
+            #[inline]
+            #[allow(non_snake_case)]
+            fn insert_into(self, map: &mut Map<A, S>) -> Self::Displaced {
+                let ($($T,)+) = self;
+                ($(map.insert($T),)+)
+            }
+
+            #[inline]
+            fn remove_from(map: &mut Map<A, S>) -> Self::Displaced {
+                ($(map.remove::<$T>(),)+)
+            }
+
+            #[inline]
+            fn contained_in(map: &Map<A, S>) -> bool {
+                $(map.contains::<$T>())&&+
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+macro_rules! impl_clone_subset {
+    ($($T:ident)+) => {
+        impl<A, S, $($T),+> crate::CloneSubset<Map<A, S>> for ($($T,)+)
+        where
+            A: ?Sized + Downcast,
+            Box<A>: Clone,
+            S: BuildHasher + Clone,
+            $($T: IntoBox<A>,)+
+        {
+            #[inline]
+            fn clone_subset(map: &Map<A, S>) -> Map<A, S> {
+                let mut subset = Map::with_capacity_and_hasher(
+                    <[&str]>::len(&[$(stringify!($T)),+]),
+                    map.raw.hasher().clone(),
+                );
+                $(
+                    let type_id = core::any::TypeId::of::<$T>();
+                    if let Some(value) = map.raw.get(&type_id) {
+                        let _ = subset.raw.insert(type_id, value.clone());
+                        subset.record_type_name::<$T>();
+                    }
+                )+
+                subset
+            }
+        }
+    }
+}
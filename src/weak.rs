@@ -0,0 +1,199 @@
+//! A map of weak references, which doesn’t keep its values alive.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::hash::BuildHasherDefault;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, sync::{Arc, Weak}};
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::hash_map::HashMap;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Weak};
+
+use crate::any::{Downcast, IntoBox};
+use crate::TypeIdHasher;
+
+/// A `Weak<T>`, stored as an `A`, with how to tell whether its value is still alive.
+struct Entry<A: ?Sized> {
+    weak: Box<A>,
+    alive: fn(&A) -> bool,
+}
+
+impl<A: ?Sized> Clone for Entry<A> where Box<A>: Clone {
+    #[inline]
+    fn clone(&self) -> Entry<A> {
+        Entry { weak: self.weak.clone(), alive: self.alive }
+    }
+}
+
+fn alive<A: ?Sized + Downcast, T: 'static>(weak: &A) -> bool {
+    // SAFETY: only ever stored for a Weak<T>.
+    unsafe { weak.downcast_ref_unchecked::<Weak<T>>() }.strong_count() > 0
+}
+
+/// A collection containing zero or one weak references for any given type, which don’t keep
+/// their values alive: once the last `Arc` elsewhere is dropped, the value is gone, and
+/// [`get`](WeakMap::get) returns `None`.
+///
+/// This suits caches of resources that something else owns. Dead entries linger until
+/// [`purge`](WeakMap::purge) drops them, or until the next insert, with
+/// [`set_auto_purge`](WeakMap::set_auto_purge).
+///
+/// A `Weak<T>` is stored as an `A`, so `A` says what `T` must be: for the default,
+/// `dyn Any + Send + Sync`, `T` must be `Send + Sync`.
+///
+/// ```rust
+/// use anymap::weak::WeakMap;
+/// use std::sync::Arc;
+///
+/// struct Texture(u32);
+///
+/// let mut cache = WeakMap::<dyn core::any::Any + Send + Sync>::new();
+/// let texture = Arc::new(Texture(1));
+/// cache.insert(&texture);
+/// assert_eq!(cache.get::<Texture>().map(|t| t.0), Some(1));
+/// drop(texture);
+/// assert!(cache.get::<Texture>().is_none());
+/// assert_eq!((cache.len(), cache.purge(), cache.len()), (1, 1, 0));
+/// ```
+pub struct WeakMap<A: ?Sized + Downcast = dyn Any + Send + Sync> {
+    raw: HashMap<TypeId, Entry<A>, BuildHasherDefault<TypeIdHasher>>,
+    auto_purge: bool,
+}
+
+impl<A: ?Sized + Downcast> Default for WeakMap<A> {
+    #[inline]
+    fn default() -> WeakMap<A> {
+        WeakMap::new()
+    }
+}
+
+impl<A: ?Sized + Downcast> Clone for WeakMap<A> where Box<A>: Clone {
+    #[inline]
+    fn clone(&self) -> WeakMap<A> {
+        WeakMap { raw: self.raw.clone(), auto_purge: self.auto_purge }
+    }
+}
+
+impl<A: ?Sized + Downcast> fmt::Debug for WeakMap<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WeakMap").field("len", &self.raw.len()).finish_non_exhaustive()
+    }
+}
+
+impl<A: ?Sized + Downcast> WeakMap<A> {
+    /// Create an empty collection.
+    #[inline]
+    pub fn new() -> WeakMap<A> {
+        WeakMap { raw: HashMap::default(), auto_purge: false }
+    }
+
+    /// Sets whether every insert first drops the entries whose values are gone, as
+    /// [`purge`](WeakMap::purge) does. This is off to begin with.
+    #[inline]
+    pub fn set_auto_purge(&mut self, auto_purge: bool) {
+        self.auto_purge = auto_purge;
+    }
+
+    /// Returns the number of entries in the collection, including any whose values are gone.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Returns true if there are no entries in the collection, not even ones whose values are
+    /// gone.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Removes all entries from the collection.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.raw.clear()
+    }
+
+    /// Drops the entries whose values are gone, returning how many there were.
+    pub fn purge(&mut self) -> usize {
+        let len = self.raw.len();
+        self.raw.retain(|_, entry| (entry.alive)(&entry.weak));
+        len - self.raw.len()
+    }
+
+    /// Returns true if the collection has a value of type `T` that’s still alive.
+    #[inline]
+    pub fn contains<T>(&self) -> bool where Weak<T>: IntoBox<A> {
+        self.raw.get(&TypeId::of::<T>()).is_some_and(|entry| (entry.alive)(&entry.weak))
+    }
+
+    /// Returns the value of type `T`, if there is one and it’s still alive.
+    #[inline]
+    pub fn get<T>(&self) -> Option<Arc<T>> where Weak<T>: IntoBox<A> {
+        let entry = self.raw.get(&TypeId::of::<T>())?;
+        unsafe { entry.weak.downcast_ref_unchecked::<Weak<T>>() }.upgrade()
+    }
+
+    /// Stores a weak reference to `value` for the type `T`. If the collection already had a
+    /// value of type `T` that’s still alive, it is returned. Otherwise, `None` is returned.
+    #[inline]
+    pub fn insert<T>(&mut self, value: &Arc<T>) -> Option<Arc<T>> where Weak<T>: IntoBox<A> {
+        if self.auto_purge {
+            let _ = self.purge();
+        }
+        let entry = Entry { weak: Arc::downgrade(value).into_box(), alive: alive::<A, T> };
+        self.raw.insert(TypeId::of::<T>(), entry)
+            .and_then(|entry| unsafe { entry.weak.downcast_unchecked::<Weak<T>>() }.upgrade())
+    }
+
+    /// Removes the entry for the type `T`, returning its value if there was one and it’s still
+    /// alive, or `None` if not.
+    #[inline]
+    pub fn remove<T>(&mut self) -> Option<Arc<T>> where Weak<T>: IntoBox<A> {
+        self.raw.remove(&TypeId::of::<T>())
+            .and_then(|entry| unsafe { entry.weak.downcast_unchecked::<Weak<T>>() }.upgrade())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+    use crate::CloneAny;
+
+    #[derive(Clone, Debug, PartialEq)] struct A(i32);
+    #[derive(Clone, Debug, PartialEq)] struct B(i32);
+
+    #[test]
+    fn test_weak_map() {
+        let mut map = WeakMap::<dyn CloneAny + Send + Sync>::new();
+        let (a, b) = (Arc::new(A(1)), Arc::new(B(2)));
+        assert_eq!(map.insert(&a), None);
+        assert_eq!(map.insert(&b), None);
+        assert_eq!(map.get::<A>(), Some(a.clone()));
+        assert_eq!(Arc::strong_count(&a), 1);
+        assert!(map.contains::<B>() && !map.contains::<u8>());
+
+        let clone = map.clone();
+        drop(b);
+        assert!(!map.contains::<B>() && !clone.contains::<B>());
+        assert_eq!((map.purge(), map.len()), (1, 1));
+        assert_eq!((clone.len(), format!("{:?}", clone)), (2, "WeakMap { len: 2, .. }".into()));
+        assert_eq!(map.insert(&Arc::new(A(3))), Some(a.clone()));
+        assert_eq!(map.get::<A>(), None);
+        assert_eq!(map.remove::<A>(), None);
+        assert!(map.is_empty());
+
+        let mut map = WeakMap::<dyn Any>::default();
+        map.set_auto_purge(true);
+        let _ = map.insert(&Arc::new(B(4)));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.insert(&a), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.remove::<A>(), Some(a));
+        map.clear();
+    }
+}
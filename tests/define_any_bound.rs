@@ -0,0 +1,83 @@
+//! `define_any_bound!` and `impl_any_bound!` are for other crates, so they’re tested from outside.
+#![cfg(feature = "std")]
+
+use std::fmt;
+
+use std::any::Any;
+
+use anymap::Map;
+
+anymap::define_any_bound! {
+    /// Anything that can be shown.
+    pub trait ShowAny: fmt::Display + fmt::Debug
+}
+
+anymap::define_any_bound! {
+    pub(crate) trait CloneShowAny(CloneToCloneShowAny): Clone + fmt::Display
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Name(&'static str);
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[test]
+fn test_define_any_bound() {
+    let mut map: Map<dyn ShowAny + Send + Sync> = Map::new();
+    assert_eq!(map.insert(Name("a")), None);
+    assert_eq!(map.insert(1u8), None);
+    assert_eq!(map.insert(Name("b")), Some(Name("a")));
+    assert_eq!(map.get::<Name>(), Some(&Name("b")));
+    let mut shown = map.values().map(|value| value.to_string()).collect::<Vec<_>>();
+    shown.sort();
+    assert_eq!(shown, ["1", "b"]);
+    assert!(format!("{:?}", map).contains("Name(\"b\")"));
+    assert!(map.insert_dyn(Box::new(2u8), "u8").is_some());
+    assert_eq!(map.remove::<u8>(), Some(2));
+}
+
+#[test]
+fn test_define_any_bound_clone() {
+    let mut map: Map<dyn CloneShowAny + Send> = Map::new();
+    let _ = map.insert(Name("a"));
+    let clone = map.clone();
+    let _ = map.insert(Name("b"));
+    assert_eq!(clone.get::<Name>(), Some(&Name("a")));
+    let shown = clone.values().next().map(|value| value.to_string());
+    assert_eq!(shown.as_deref(), Some("a"));
+}
+
+trait Plugin: Any {
+    fn name(&self) -> &'static str;
+    fn enable(&mut self);
+}
+
+anymap::impl_any_bound!(Plugin);
+
+#[derive(Debug, Default, PartialEq)]
+struct Spellcheck(bool);
+
+impl Plugin for Spellcheck {
+    fn name(&self) -> &'static str {
+        "spellcheck"
+    }
+
+    fn enable(&mut self) {
+        self.0 = true;
+    }
+}
+
+#[test]
+fn test_impl_any_bound() {
+    let mut plugins: Map<dyn Plugin + Send> = Map::new();
+    assert_eq!(plugins.insert(Spellcheck::default()), None);
+    for plugin in plugins.values_mut() {
+        plugin.enable();
+    }
+    assert_eq!(plugins.get::<Spellcheck>(), Some(&Spellcheck(true)));
+    assert_eq!(plugins.values().map(|plugin| plugin.name()).collect::<Vec<_>>(), ["spellcheck"]);
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "derive")]
+
+use anymap::{AnyMap, AnyMapBundle, FromAnyMap};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Port(u16);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Host(&'static str);
+
+#[derive(AnyMapBundle)]
+struct Config {
+    host: Host,
+    port: Port,
+}
+
+#[derive(AnyMapBundle)]
+struct Wrapper<T>(T, bool);
+
+#[test]
+fn bundle() {
+    let mut map = AnyMap::new();
+    let _ = map.insert(Port(80));
+    let displaced = map.insert_bundle(Config { host: Host("localhost"), port: Port(8080) });
+    assert_eq!(displaced, (None, Some(Port(80))));
+    assert!(map.contains_all::<Config>());
+    assert_eq!(map.get(), Some(&Host("localhost")));
+    assert_eq!(map.get(), Some(&Port(8080)));
+    assert_eq!(map.remove_bundle::<Config>(), (Some(Host("localhost")), Some(Port(8080))));
+    assert!(!map.contains_all::<Config>());
+    assert!(map.is_empty());
+}
+
+#[test]
+fn generic_tuple_struct() {
+    let mut map = AnyMap::new();
+    let _ = map.insert_bundle(Wrapper(1u8, true));
+    assert!(map.contains_all::<Wrapper<u8>>());
+    assert!(!map.contains_all::<Wrapper<u16>>());
+    assert_eq!(map.remove_bundle::<Wrapper<u8>>(), (Some(1), Some(true)));
+}
+
+#[derive(Debug, PartialEq, FromAnyMap)]
+struct Extracted {
+    host: Host,
+    port: Option<Port>,
+}
+
+#[test]
+fn from_any_map() {
+    use std::convert::TryFrom;
+    let mut map = AnyMap::new();
+    let error = Extracted::try_from(&map).unwrap_err();
+    assert_eq!(error.type_name(), std::any::type_name::<Host>());
+    assert_eq!(error.to_string(), "missing value of type `derive::Host`");
+    let _ = map.insert(Host("localhost"));
+    assert_eq!(Extracted::try_from(&map), Ok(Extracted { host: Host("localhost"), port: None }));
+    let _ = map.insert(Port(80));
+    assert_eq!(
+        Extracted::try_from(&map),
+        Ok(Extracted { host: Host("localhost"), port: Some(Port(80)) }),
+    );
+    #[cfg(feature = "hashbrown")]
+    assert!(Extracted::try_from(&anymap::hashbrown::AnyMap::new()).is_err());
+}
@@ -0,0 +1,48 @@
+//! Zero-sized values shouldn’t cost an allocation each. This is in its own test binary because it
+//! needs a counting global allocator.
+#![cfg(feature = "std")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anymap::AnyMap;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[derive(Debug, PartialEq)]
+struct Marker;
+
+#[derive(Debug, PartialEq)]
+struct Flag;
+
+#[test]
+fn zero_sized_values_do_not_allocate() {
+    let mut map = AnyMap::with_capacity(2);
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    assert_eq!(map.insert(Marker), None);
+    assert_eq!(map.insert(Flag), None);
+    assert_eq!(map.insert(Marker), Some(Marker));
+    assert_eq!(map.get::<Flag>(), Some(&Flag));
+    assert_eq!(map.remove::<Flag>(), Some(Flag));
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+
+    // Whereas anything else does.
+    assert_eq!(map.insert(1u8), None);
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before + 1);
+}